@@ -3,3 +3,36 @@ export PORT=3000
 export FRONTEND_DIR=./static/
 export FILE_STORE_DIR=./store/
 
+# export RUST_LOG=infra=info,image_gallery_server=info,tower_http=debug
+# text or json
+# export LOG_FORMAT=text
+
+# export SHARE_LINK_SECRET=change-me
+# enables /admin and the dashboard at /admin.html
+# export ADMIN_TOKEN=change-me
+# export RATE_LIMIT_PER_SECOND=100
+# export RATE_LIMIT_BURST=1000
+# export IP_RATE_LIMIT_PER_SECOND=200
+# export IP_RATE_LIMIT_BURST=2000
+# export MAX_MESSAGE_SIZE=1048576
+# pods deliver whole images, their sockets get more room
+# export MAX_POD_MESSAGE_SIZE=33554432
+# slow sockets lose images beyond this many queued bytes and are closed after the grace seconds
+# export MAX_QUEUED_BYTES=8388608
+# export SLOW_CONSUMER_GRACE=10
+# ping every 15 seconds, close sockets silent for 45
+# export HEARTBEAT_INTERVAL=15
+# export HEARTBEAT_TIMEOUT=45
+# export ALLOWED_IMAGE_TYPES=image/jpeg,image/png,image/gif,image/webp
+# serve https/wss directly, the files are watched and reloaded on change
+# export TLS_CERT_PATH=./cert.pem
+# export TLS_KEY_PATH=./key.pem
+# export TLS_RELOAD_INTERVAL=30
+# run several servers as one gallery, every node needs its own id and key (`openssl rand -hex 32`),
+# it logs its peer id on startup, the other nodes list it among their allowed peers
+# export CLUSTER_NODE_ID=0
+# export CLUSTER_LISTEN=/ip4/0.0.0.0/tcp/4000
+# export CLUSTER_KEY=<64 hex characters>
+# export CLUSTER_ALLOWED_PEERS=12D3KooW...,12D3KooW...
+# export CLUSTER_PEERS=/ip4/10.0.0.2/tcp/4000,/ip4/10.0.0.3/tcp/4000
+# export CLUSTER_SYNC_INTERVAL=5
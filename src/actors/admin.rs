@@ -0,0 +1,179 @@
+use std::{net::SocketAddr, sync::atomic::{AtomicBool, AtomicU64, Ordering}};
+
+use ::chrono::{DateTime, Utc};
+use kameo::prelude::*;
+use serde_derive::Serialize;
+use tracing::info;
+
+use crate::protocols::{ClientResponse, PodId};
+use super::{Disconnect, Hub, WebClient, websocket::CloseCode};
+
+/// Where a session comes from and how much it exchanged, counted by the webserver
+#[derive(Debug)]
+pub struct ConnectionInfo {
+    pub remote: SocketAddr,
+    pub user_agent: String,
+    pub connected_at: DateTime<Utc>,
+    received_messages: AtomicU64,
+    received_bytes: AtomicU64,
+    sent_messages: AtomicU64,
+    sent_bytes: AtomicU64,
+    /// Set while the socket is registered as pod
+    is_pod: AtomicBool,
+}
+impl ConnectionInfo {
+    pub fn new(remote: SocketAddr, user_agent: String) -> Self {
+        ConnectionInfo {
+            remote,
+            user_agent,
+            connected_at: Utc::now(),
+            received_messages: AtomicU64::new(0),
+            received_bytes: AtomicU64::new(0),
+            sent_messages: AtomicU64::new(0),
+            sent_bytes: AtomicU64::new(0),
+            is_pod: AtomicBool::new(false),
+        }
+    }
+
+    pub fn count_received(&self, bytes: usize) {
+        self.received_messages.fetch_add(1, Ordering::Relaxed);
+        self.received_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn mark_pod(&self) {
+        self.is_pod.store(true, Ordering::Relaxed);
+    }
+
+    pub fn unmark_pod(&self) {
+        self.is_pod.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_pod(&self) -> bool {
+        self.is_pod.load(Ordering::Relaxed)
+    }
+
+    pub fn count_sent(&self, bytes: usize) {
+        self.sent_messages.fetch_add(1, Ordering::Relaxed);
+        self.sent_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// Everything connected to this node, as shown on the admin dashboard
+#[derive(Serialize, Debug, Reply)]
+pub struct ConnectionsOverview {
+    pub clients: Vec<ClientOverview>,
+    pub pods: Vec<PodOverview>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClientOverview {
+    pub id: PodId,
+    pub remote: SocketAddr,
+    pub user_agent: String,
+    pub connected_at: DateTime<Utc>,
+    pub is_pod: bool,
+    /// Set for sessions opened through a share link
+    pub share_link: Option<u64>,
+    pub received_messages: u64,
+    pub received_bytes: u64,
+    pub sent_messages: u64,
+    pub sent_bytes: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PodOverview {
+    pub id: PodId,
+    pub name: String,
+    pub paths: usize,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// Lists the clients and pods connected to this node
+pub struct ListConnections;
+
+/// Closes the session of a client, replies whether it was connected
+pub struct KickClient(pub PodId);
+
+/// Sends [`ClientResponse::ServerNotice`] to every client, replies how many got it
+pub struct BroadcastNotice(pub String);
+
+/// The Hub forgot this pod, the session may register again
+pub(super) struct PodRemoved;
+
+impl Message<ListConnections> for Hub {
+    type Reply = ConnectionsOverview;
+    async fn handle(
+        &mut self,
+        _msg: ListConnections,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let mut clients: Vec<ClientOverview> = self.clients.iter().map(|(&id, client)| {
+            let connection = &client.connection;
+            ClientOverview {
+                id,
+                remote: connection.remote,
+                user_agent: connection.user_agent.clone(),
+                connected_at: connection.connected_at,
+                is_pod: self.pods.contains_key(&id),
+                share_link: client.share_scope.map(|scope| scope.link_id),
+                received_messages: connection.received_messages.load(Ordering::Relaxed),
+                received_bytes: connection.received_bytes.load(Ordering::Relaxed),
+                sent_messages: connection.sent_messages.load(Ordering::Relaxed),
+                sent_bytes: connection.sent_bytes.load(Ordering::Relaxed),
+            }
+        }).collect();
+        clients.sort_by_key(|client| client.id);
+        let mut pods: Vec<PodOverview> = self.pods.iter().map(|(&id, pod)| PodOverview {
+            id,
+            name: pod.name.clone(),
+            paths: pod.image_paths.len(),
+            last_modified: pod.last_modified,
+        }).collect();
+        pods.sort_by_key(|pod| pod.id);
+        ConnectionsOverview { clients, pods }
+    }
+}
+
+impl Message<KickClient> for Hub {
+    type Reply = bool;
+    async fn handle(
+        &mut self,
+        msg: KickClient,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let Some(client) = self.clients.get(&msg.0) else {
+            return false;
+        };
+        info!(client_id = msg.0, remote = %client.connection.remote, "kicking client");
+        // the session unsubscribes itself while closing, but must not be resumed
+        let _ = client.addr.tell(Disconnect((CloseCode::Policy, "disconnected by an admin").into())).try_send();
+        self.end_sessions(|id, _| id == msg.0);
+        true
+    }
+}
+
+impl Message<BroadcastNotice> for Hub {
+    type Reply = usize;
+    async fn handle(
+        &mut self,
+        msg: BroadcastNotice,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        info!(message = %msg.0, clients = self.clients.len(), "broadcasting server notice");
+        self.broadcast_client_response(ClientResponse::ServerNotice { message: msg.0 });
+        self.clients.len()
+    }
+}
+
+impl Message<PodRemoved> for WebClient {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        _msg: PodRemoved,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        info!(parent: &self.span, "pod removed by the hub");
+        self.is_pod = false;
+        self.connection.unmark_pod();
+    }
+}
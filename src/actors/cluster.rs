@@ -0,0 +1,259 @@
+use std::time::Instant;
+
+use kameo::{actor::RemoteActorRef, prelude::*, remote_message};
+use metrics::counter;
+use serde_derive::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::cluster::{NodeId, node_of};
+use crate::monitoring;
+use crate::protocols::{ClientResponse, GalleryPath, PathMetadata, PodDescription, PodId};
+use super::{Hub, PathListing, path_updates};
+
+/// Hub of another node and when we last heard from it
+pub struct Peer {
+    pub(super) hub: RemoteActorRef<Hub>,
+    pub(super) last_seen: Instant,
+}
+
+/// The swarm is up, this is how other nodes reach us
+pub struct ClusterJoined {
+    pub hub: RemoteActorRef<Hub>,
+}
+
+/// Time to send our pods to every peer and to forget silent ones
+pub struct ClusterTick;
+
+/// A Hub registered under the well known name, possibly one we already know
+pub struct PeerFound(pub RemoteActorRef<Hub>);
+
+/// All pods of a node, sent periodically so peers converge and notice dead nodes
+#[derive(Serialize, Deserialize)]
+pub struct ClusterSnapshot {
+    node_id: NodeId,
+    hub: RemoteActorRef<Hub>,
+    pods: Vec<PodDescription>,
+}
+
+/// A pod of the sending node registered or changed name or paths
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClusterPodChanged(pub PodDescription);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClusterPodGone(pub PodId);
+
+/// A client of the sending node wants an image of one of our pods
+#[derive(Serialize, Deserialize)]
+pub struct ClusterRequestImage {
+    pub gallery_id: PodId,
+    pub path: GalleryPath,
+    pub client_id: PodId,
+}
+
+/// Response for a client connected to the receiving node
+#[derive(Serialize, Deserialize)]
+pub struct ClusterDeliver {
+    pub client_id: PodId,
+    pub response: ClientResponse,
+}
+
+impl Hub {
+    fn snapshot(&self) -> Option<ClusterSnapshot> {
+        Some(ClusterSnapshot {
+            node_id: self.node_id,
+            hub: self.cluster_hub.clone()?,
+            pods: self.local_pod_descriptions(),
+        })
+    }
+
+    /// Takes over the state of a pod living on another node and tells our clients what changed.
+    /// Versions are counted by every node for itself, so they stay consecutive for our clients.
+    fn apply_remote_pod(&mut self, mut pod: PodDescription) {
+        let known = self.remote_pods.remove(&pod.id);
+        let empty = PathMetadata::new();
+        let (old, mut version) = match &known {
+            None => {
+                debug!(pod_id = pod.id, name = %pod.name, "remote pod appeared");
+                self.publish_pod_update(ClientResponse::NewPod { id: pod.id, name: pod.name.clone() });
+                (PathListing { paths: &[], metadata: &empty, curated_order: &[] }, 0)
+            }
+            Some(known) => {
+                if known.name != pod.name {
+                    self.publish_pod_update(ClientResponse::PodUpdateName { id: pod.id, name: pod.name.clone() });
+                }
+                (PathListing::from(known), known.version)
+            }
+        };
+        let new = PathListing::from(&pod);
+        let updates = path_updates(pod.id, old, new, &mut version, false, pod.last_modified);
+        pod.version = version;
+        self.remote_pods.insert(pod.id, pod);
+        for update in updates {
+            self.publish_pod_update(update);
+        }
+    }
+
+    fn remove_remote_pods(&mut self, gone: impl Fn(&PodDescription) -> bool) {
+        let gone_ids: Vec<PodId> = self.remote_pods.values().filter(|pod| gone(pod)).map(|pod| pod.id).collect();
+        for id in gone_ids {
+            self.remote_pods.remove(&id);
+            debug!(pod_id = id, "remote pod gone");
+            self.publish_pod_update(ClientResponse::PodGone(id));
+        }
+    }
+}
+
+impl Message<ClusterJoined> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: ClusterJoined,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        debug!(hub = ?msg.hub.id(), "reachable by the cluster");
+        self.cluster_hub = Some(msg.hub);
+    }
+}
+
+impl Message<PeerFound> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: PeerFound,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if self.peers.values().any(|peer| peer.hub == msg.0) {
+            return;
+        }
+        debug!(hub = ?msg.0.id(), "found cluster hub");
+        // it learns about us right away, we learn its node id from its answer
+        if let Some(snapshot) = self.snapshot() {
+            let _ = msg.0.tell(&snapshot).send();
+        }
+    }
+}
+
+impl Message<ClusterTick> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        _msg: ClusterTick,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if let Some(snapshot) = self.snapshot() {
+            for peer in self.peers.values() {
+                let _ = peer.hub.tell(&snapshot).send();
+            }
+        }
+
+        let timeout = self.cluster_sync_interval * 3;
+        let silent: Vec<NodeId> = self.peers.iter()
+            .filter(|(_, peer)| peer.last_seen.elapsed() > timeout)
+            .map(|(&node_id, _)| node_id)
+            .collect();
+        for node_id in silent {
+            warn!(node_id, "cluster node went silent, dropping its pods");
+            self.peers.remove(&node_id);
+            self.remove_remote_pods(|pod| node_of(pod.id) == node_id);
+        }
+    }
+}
+
+#[remote_message("gallery::ClusterSnapshot")]
+impl Message<ClusterSnapshot> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: ClusterSnapshot,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if msg.node_id == self.node_id {
+            warn!(node_id = msg.node_id, "ignoring cluster peer claiming our own node id");
+            return;
+        }
+        let is_new = self.peers.insert(msg.node_id, Peer { hub: msg.hub.clone(), last_seen: Instant::now() }).is_none();
+        if is_new {
+            info!(node_id = msg.node_id, pods = msg.pods.len(), "cluster node joined");
+            if let Some(snapshot) = self.snapshot() {
+                let _ = msg.hub.tell(&snapshot).send();
+            }
+        }
+
+        let node_id = msg.node_id;
+        let ids: Vec<PodId> = msg.pods.iter().map(|pod| pod.id).collect();
+        self.remove_remote_pods(|pod| node_of(pod.id) == node_id && !ids.contains(&pod.id));
+        for pod in msg.pods.into_iter().filter(|pod| node_of(pod.id) == node_id) {
+            self.apply_remote_pod(pod);
+        }
+    }
+}
+
+#[remote_message("gallery::ClusterPodChanged")]
+impl Message<ClusterPodChanged> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: ClusterPodChanged,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if node_of(msg.0.id) != self.node_id {
+            self.apply_remote_pod(msg.0);
+        }
+    }
+}
+
+#[remote_message("gallery::ClusterPodGone")]
+impl Message<ClusterPodGone> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: ClusterPodGone,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.remove_remote_pods(|pod| pod.id == msg.0);
+    }
+}
+
+#[remote_message("gallery::ClusterRequestImage")]
+impl Message<ClusterRequestImage> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: ClusterRequestImage,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        debug!(pod_id = msg.gallery_id, client_id = msg.client_id, path = %msg.path, "image requested by cluster peer");
+        match self.pods.contains_key(&msg.gallery_id) {
+            true => self.request_image(msg.gallery_id, msg.path, msg.client_id),
+            false => self.send_to_client(msg.client_id, ClientResponse::UnknownPod(msg.gallery_id)),
+        }
+    }
+}
+
+#[remote_message("gallery::ClusterDeliver")]
+impl Message<ClusterDeliver> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: ClusterDeliver,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        // our allowed types apply to images of other nodes as well
+        let response = match msg.response {
+            ClientResponse::DeliverImage { gallery_id, path, blob, .. } => match self.validate_image(&blob) {
+                Ok((image_type, blob)) => ClientResponse::DeliverImage { gallery_id, path, mime: image_type.mime().into(), blob },
+                Err(rejection) => {
+                    counter!(monitoring::IMAGES_REJECTED).increment(1);
+                    warn!(pod_id = gallery_id, client_id = msg.client_id, path = %path, %rejection, "cluster peer delivered an invalid image");
+                    ClientResponse::ImageRejected { gallery_id, path, reason: rejection.to_string() }
+                }
+            },
+            response => response,
+        };
+        if let Some(client) = self.clients.get(&msg.client_id) {
+            let _ = client.addr.tell(response).try_send();
+        } else if !self.record_missed(msg.client_id, &response) {
+            debug!(client_id = msg.client_id, "dropping cluster delivery for a client that is gone");
+        }
+    }
+}
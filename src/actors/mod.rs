@@ -1,32 +1,148 @@
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, sync::Arc, time::{Duration, Instant}};
 
-use kameo::{error::Infallible, message::StreamMessage, prelude::*};
+use kameo::{actor::{ActorId, RemoteActorRef}, error::Infallible, mailbox, message::StreamMessage, prelude::*};
 use ::chrono::{Utc, DateTime};
+use metrics::{counter, gauge, histogram};
+use tracing::{Span, debug, info, warn};
 
-use crate::protocols::{ClientRequest, ClientRequestAsync, ClientResponse, JsonProtocol, PodId, PodRequest, PodResponse};
+use crate::protocols::{ClientRequest, ClientRequestAsync, ClientResponse, GalleryPath, JsonProtocol, PathMetadata, PodDescription, PodId, PodRequest, PodResponse, PodSummary};
+use crate::config::Config;
+use crate::image_type::{ImageRejection, ImageType};
+use crate::monitoring;
+use crate::share_link::{ShareClaims, ShareLinkError, ShareLinkSigner};
+use crate::cluster::{NodeId, node_of};
+use self::admin::PodRemoved;
+use self::outbound::{OutboundQueue, Overflow};
+use self::search::{IndexUpdate, Listing, RunSearch, SearchIndex};
+use self::session::Session;
+use self::cluster::{ClusterDeliver, ClusterPodChanged, ClusterPodGone, ClusterRequestImage, Peer};
+use self::websocket::{CloseCode, CloseReason};
 
+pub mod admin;
+pub mod cluster;
+pub mod outbound;
+pub mod search;
+pub mod session;
 pub mod websocket;
 
+pub use self::admin::{BroadcastNotice, ConnectionInfo, ConnectionsOverview, KickClient, ListConnections};
+pub use self::cluster::{ClusterJoined, ClusterTick, PeerFound};
+pub use self::session::{ResumeSession, ResumedSession, TakeOverSession};
+
+/// What a client connected through a share link is allowed to see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShareScope {
+    pub pod_id: PodId,
+    pub link_id: u64,
+}
+
 pub struct WebClient {
     pub id: PodId,
     pub hub: ActorRef<Hub>,
     pub is_pod: bool,
+    /// Set for limited sessions opened through a share link
+    pub share_scope: Option<ShareScope>,
+    /// Frames to be written to the websocket
+    pub outbound: OutboundQueue,
+    /// Span of the connection, the actor runs outside of it so events name it as parent
+    pub span: Span,
+    pub connection: Arc<ConnectionInfo>,
+    /// Answers searches and listings without going through the Hub, handed out by it once subscribed
+    pub search_index: Option<ActorRef<SearchIndex>>,
 }
 impl WebClient {
-    fn new(id: PodId, hub:ActorRef<Hub>,is_pod: bool) -> Self {
-        WebClient{
-            id,
-            hub,
-            is_pod,
+    fn send_json(&self, message: &JsonProtocol) -> Result<(), Overflow> {
+        let text = serde_json::to_string(message).expect("unable to serialize internal state");
+        self.outbound.push(websocket::Message::Text(text))
+    }
+
+    /// A socket that cannot keep up is closed, whatever is still queued for it is dropped
+    async fn disconnect_slow(&self, overflow: Overflow, ctx: &mut Context<Self, ()>) {
+        counter!(monitoring::DROPPED_MESSAGES, "reason" => "slow_client_disconnected").increment(1);
+        warn!(parent: &self.span, queued_bytes = overflow.queued_bytes, "too slow to keep up, closing");
+        self.outbound.close_now((CloseCode::Policy, "too slow to keep up").into());
+        let _ = self.hub.tell(UnsubscribeClient { id: self.id, actor_id: ctx.actor_ref().id() }).await;
+        ctx.stop();
+    }
+
+    /// Share sessions only ever see their own gallery
+    fn in_scope(&self, pod_id: PodId) -> bool {
+        self.share_scope.is_none_or(|scope| scope.pod_id == pod_id)
+    }
+
+    /// Answers a request of the client, `None` if the answer is sent to `client` once ready
+    async fn answer(&self, request: ClientRequest, client: &ActorRef<WebClient>) -> Option<ClientResponse> {
+        let listing = match request {
+            // answered here, the Hub has nothing to do with it
+            ClientRequest::Heartbeat => return Some(ClientResponse::Heartbeat),
+            ClientRequest::ListAllPods => return Some(self.ask_hub(HubRequest::ListAllPods).await),
+            ClientRequest::ListPodSummaries => return Some(self.ask_hub(HubRequest::ListPodSummaries).await),
+            ClientRequest::Search { query, filters, sort, after, limit } => {
+                // answered by the search index once it is done, its mailbox is unbounded
+                let scope = self.share_scope.map(|scope| scope.pod_id);
+                let search = RunSearch { client: client.clone(), query, filters, sort, after, limit, scope };
+                let sent = match &self.search_index {
+                    Some(search_index) => search_index.tell(search).await.is_ok(),
+                    None => false,
+                };
+                if sent {
+                    return None;
+                }
+                warn!(parent: &self.span, "search index unavailable");
+                return Some(ClientResponse::ServerNotice { message: "search is unavailable".into() });
+            }
+            ClientRequest::ListPodStructure(query) => Listing::Structure(query),
+            ClientRequest::ListPodPaths { id, prefix, glob, after, limit, sort } => Listing::Paths { id, prefix, glob, after, limit, sort },
+            ClientRequest::ListFolder { gallery_id, folder, sort, after, limit } => Listing::Folder { gallery_id, folder, sort, after, limit },
+        };
+        let id = listing.id();
+        if !self.in_scope(id) {
+            return Some(ClientResponse::UnknownPod(id));
+        }
+        // the index keeps the paths of every pod sorted in the orders asked for
+        let listed = match &self.search_index {
+            Some(search_index) => search_index.ask(listing).await.ok(),
+            None => None,
+        };
+        Some(listed.unwrap_or_else(|| {
+            warn!(parent: &self.span, "search index unavailable");
+            ClientResponse::ServerNotice { message: "listings are unavailable".into() }
+        }))
+    }
+
+    async fn ask_hub(&self, request: HubRequest) -> ClientResponse {
+        self.hub.ask(request).await.unwrap_or_else(|error| {
+            warn!(parent: &self.span, %error, "hub unavailable");
+            ClientResponse::ServerNotice { message: "the server is unavailable".into() }
+        })
+    }
+
+    /// What of a response the client may see, `None` if nothing
+    fn scoped(&self, response: ClientResponse) -> Option<ClientResponse> {
+        match response {
+            ClientResponse::Pods(pods) => Some(ClientResponse::Pods(
+                pods.into_iter().filter(|pod| self.in_scope(pod.id)).collect()
+            )),
+            ClientResponse::PodSummaries(pods) => Some(ClientResponse::PodSummaries(
+                pods.into_iter().filter(|pod| self.in_scope(pod.id)).collect()
+            )),
+            other if other.pod_id().is_some_and(|id| !self.in_scope(id)) => None,
+            other => Some(other),
         }
     }
 }
 impl Actor for WebClient {
     type Args = Self;
     type Error = Infallible;
-    async fn on_start(state: Self::Args, _actor_ref: ActorRef<Self>) -> Result<Self, Self::Error> {
-        println!("WebClient Actor started");
-        Ok(WebClient::new(state.id, state.hub, state.is_pod))
+    async fn on_start(mut state: Self::Args, actor_ref: ActorRef<Self>) -> Result<Self, Self::Error> {
+        debug!(parent: &state.span, "session started");
+        state.search_index = state.hub.ask(SubscribeClient {
+            id: state.id,
+            addr: actor_ref,
+            share_scope: state.share_scope,
+            connection: state.connection.clone(),
+        }).await.ok().flatten();
+        Ok(state)
     }
 }
 impl Message<StreamMessage<String, &'static str, &'static str>> for WebClient {
@@ -39,47 +155,82 @@ impl Message<StreamMessage<String, &'static str, &'static str>> for WebClient {
     ) -> Self::Reply {
         match msg {
             StreamMessage::Next(json_raw) => {
-                let json_command:Result<JsonProtocol, _> = serde_json::from_str(json_raw.as_str());
-                                match json_command {
+                let started = Instant::now();
+                let (json_command, rejected_paths) = match JsonProtocol::parse_lenient(json_raw.as_str()) {
+                    Ok((message, rejected)) => (Ok(message), rejected),
+                    Err(error) => (Err(error), vec![]),
+                };
+                let (kind, request) = json_command.as_ref().map_or(("invalid", "invalid"), |message| (message.kind(), message.variant()));
+                match json_command {
                     Ok(JsonProtocol::ClientRequest(message)) => {
-                        let response = self.hub.ask(message).await.expect("error processing ClientRequest");
-                        _ = ctx.forward(&ctx.actor_ref().clone(), response);
+                        if let Some(response) = self.answer(message, ctx.actor_ref()).await {
+                            // after the updates already on their way, none of them replaces it
+                            let _ = ctx.actor_ref().tell(Reply(response)).await;
+                        }
                     }
                     Ok(JsonProtocol::ClientRequestAsync(message)) => {
-                        _ = ctx.forward(&ctx.actor_ref().clone(), message);
+                        let _ = ctx.actor_ref().tell(message).await;
                     }
-                    Ok(JsonProtocol::PodRequest(message)) => {
-                        _ = ctx.forward(&ctx.actor_ref().clone(), message);
+                    Ok(JsonProtocol::PodRequest(message)) if self.share_scope.is_none() => {
+                        let _ = ctx.actor_ref().tell(message).await;
+                        if !rejected_paths.is_empty() {
+                            warn!(parent: &self.span, rejected = rejected_paths.len(), "left out invalid paths");
+                            let _ = ctx.actor_ref().tell(PodResponse::PathsRejected { paths: rejected_paths }).await;
+                        }
+                    }
+                    Ok(message) => {
+                        counter!(monitoring::INVALID_MESSAGES).increment(1);
+                        warn!(parent: &self.span, kind = message.kind(), request = message.variant(), "message not allowed for a share session");
+                        return;
                     }
-                    _invalid => {
-                        print!("{{\"invalid request\":{:?}}}", _invalid);
+                    Err(error) => {
+                        counter!(monitoring::INVALID_MESSAGES).increment(1);
+                        warn!(parent: &self.span, %error, "invalid request");
+                        return;
                     }
                 }
+                let elapsed = started.elapsed();
+                debug!(parent: &self.span, kind, request, ?elapsed, "handled request");
+                histogram!(monitoring::REQUEST_SECONDS, "kind" => kind, "request" => request).record(elapsed);
             }
-            StreamMessage::Started(s) => {
-                println!("Started {s}");
+            StreamMessage::Started(stream) => {
+                debug!(parent: &self.span, stream, "stream started");
             }
-            StreamMessage::Finished(s) => {
-                println!("Finished {s}");
-                    ctx.actor_ref().stop_gracefully().await.unwrap();
+            StreamMessage::Finished(stream) => {
+                debug!(parent: &self.span, stream, "stream finished");
+                let _ = self.hub.tell(UnsubscribeClient { id: self.id, actor_id: ctx.actor_ref().id() }).await;
+                ctx.stop();
             }
         }
     }
 }
 
-//impl Message<>
 impl Message<ClientResponse> for WebClient {
-    type Reply = ClientResponse;
+    type Reply = ();
 
     async fn handle(
         &mut self,
         msg: ClientResponse,
-        _ctx: &mut Context<Self, Self::Reply>,
+        ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        //let message = JsonProtocol::ClientResponse(msg);
-        // TODO: some wbsocket text stuff here
-        // ctx.text(serde_json::to_string(&message).expect("unable to serialize internal state"));
-        msg.clone()
+        let Some(msg) = self.scoped(msg) else { return };
+        if let Err(overflow) = self.outbound.push_response(&msg) {
+            self.disconnect_slow(overflow, ctx).await;
+        }
+    }
+}
+impl Message<Reply> for WebClient {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: Reply,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let Some(msg) = self.scoped(msg.0) else { return };
+        if let Err(overflow) = self.outbound.push_reply(&msg) {
+            self.disconnect_slow(overflow, ctx).await;
+        }
     }
 }
 impl Message<ClientRequestAsync> for WebClient{
@@ -87,22 +238,29 @@ impl Message<ClientRequestAsync> for WebClient{
     async fn handle(
         &mut self,
         mut msg: ClientRequestAsync,
-        _ctx: &mut Context<Self, Self::Reply>,
+        ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let ClientRequestAsync::RequestImage { client_id, .. } = &mut msg;
+        let ClientRequestAsync::RequestImage { client_id, gallery_id, .. } = &mut msg;
         *client_id = self.id;
-        let _ = self.hub.tell(msg);
+        if !self.in_scope(*gallery_id) {
+            if let Err(overflow) = self.send_json(&JsonProtocol::ClientResponse(ClientResponse::UnknownPod(*gallery_id))) {
+                self.disconnect_slow(overflow, ctx).await;
+            }
+            return;
+        }
+        let _ = self.hub.tell(msg).await;
     }
 }
 impl Message<PodResponse> for WebClient{
     type Reply = ();
-    // Todo implement MessageResult -PodResponse
     async fn handle(
         &mut self,
         msg: PodResponse,
-        _ctx: &mut Context<Self, Self::Reply>,
+        ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let _msg = JsonProtocol::PodResponse(msg);
+        if let Err(overflow) = self.send_json(&JsonProtocol::PodResponse(msg)) {
+            self.disconnect_slow(overflow, ctx).await;
+        }
     }
 }
 
@@ -113,12 +271,14 @@ impl Message<PodRequest> for WebClient{
         msg: PodRequest,
         ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-                use PodRequest::*;
+        use PodRequest::*;
         match msg {
             RegisterSelf { name, .. } => {
                 if !self.is_pod {
                     self.is_pod = true;
-                    let _ = self.hub.tell(SubscribePod { id: self.id, name, addr: ctx.actor_ref().clone(), });
+                    self.connection.mark_pod();
+                    info!(parent: &self.span, name = %name, "registered as pod");
+                    let _ = self.hub.tell(SubscribePod { id: self.id, name, addr: ctx.actor_ref().clone(), }).await;
                     //actix::Handler::handle(self, PodResponse::Registered { global_id: self.id }, ctx);
                     ctx.forward(&ctx.actor_ref().clone(), PodResponse::Registered { global_id: self.id }).await;
                 } else {
@@ -127,72 +287,478 @@ impl Message<PodRequest> for WebClient{
                 }
             }
             other_messages => {
-                let _ = self.hub.tell(IdedPodRequest { id: self.id, message: other_messages });
+                let _ = self.hub.tell(IdedPodRequest { id: self.id, message: other_messages }).await;
             }
         };
     }
 }
 
+/// Close the websocket with the given reason and stop the actor
+pub struct Disconnect(pub CloseReason);
+
+impl Message<Disconnect> for WebClient {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: Disconnect,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        info!(parent: &self.span, code = ?msg.0.code, reason = msg.0.description.as_deref(), "disconnecting");
+        let _ = self.outbound.push(websocket::Message::Close(Some(msg.0)));
+        let _ = self.hub.tell(UnsubscribeClient { id: self.id, actor_id: ctx.actor_ref().id() }).await;
+        ctx.stop();
+    }
+}
+
 pub struct PodInfo {
     addr: ActorRef<WebClient>,
     name: String,
-    image_paths: Vec<String>,
+    /// Sorted and without duplicates
+    image_paths: Vec<GalleryPath>,
+    /// Only of paths among `image_paths`
+    metadata: PathMetadata,
+    /// Every path of `image_paths` once, if the pod declared an order
+    curated_order: Vec<GalleryPath>,
     last_modified: DateTime<Utc>,
+    version: u64,
 }
 impl std::fmt::Debug for PodInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("PodInfo")
             .field("name", &self.name)
             .field("image_paths", &self.image_paths)
+            .field("version", &self.version)
             .finish()
     }
 }
 
+pub struct ClientInfo {
+    addr: ActorRef<WebClient>,
+    share_scope: Option<ShareScope>,
+    connection: Arc<ConnectionInfo>,
+}
+
+pub struct ShareLink {
+    pod_id: PodId,
+    expires_at: DateTime<Utc>,
+    max_views: Option<u32>,
+    views: u32,
+}
+
+/// Messages the Hub mailbox holds before senders have to wait, the size kameo gives every mailbox.
+/// Everything sent to the Hub waits for room, sockets and peer nodes included, so a busy Hub slows
+/// down reading from them rather than losing messages or queueing without limit.
+/// The Hub itself only `try_send`s to unbounded mailboxes.
+pub const HUB_MAILBOX_CAPACITY: usize = 64;
+/// Image requests remembered for the delivery latency before old ones are forgotten
+const MAX_TRACKED_IMAGE_REQUESTS: usize = 10_000;
+const IMAGE_REQUEST_TRACKING: Duration = Duration::from_secs(60);
+
 pub struct Hub {
     pods: HashMap<PodId, PodInfo>,
-    clients: HashMap<PodId, ActorRef<WebClient>>,
+    clients: HashMap<PodId, ClientInfo>,
+    share_signer: ShareLinkSigner,
+    share_links: HashMap<u64, ShareLink>,
+    next_share_link_id: u64,
+    allowed_image_types: Vec<ImageType>,
+    /// Upper bits of every id issued here, see [`crate::cluster::first_id_of_node`]
+    node_id: NodeId,
+    /// Set once this Hub is reachable by the other nodes
+    cluster_hub: Option<RemoteActorRef<Hub>>,
+    cluster_sync_interval: Duration,
+    peers: HashMap<NodeId, Peer>,
+    /// Pods connected to other nodes, as last reported by them
+    remote_pods: HashMap<PodId, PodDescription>,
+    /// When images were requested from local pods, by client, pod and path
+    image_requests: HashMap<(PodId, PodId, GalleryPath), Instant>,
+    /// Sessions of local clients, including those waiting to be resumed
+    sessions: HashMap<PodId, Session>,
+    /// Started along with the Hub
+    search_index: Option<ActorRef<SearchIndex>>,
 }
 impl Hub{
-    fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let share_signer = match config.get_share_link_secret() {
+            Some(secret) => ShareLinkSigner::new(secret),
+            None => ShareLinkSigner::random(),
+        };
         Hub{
-            pods: HashMap::new(),
-            clients: HashMap::new()
+            share_signer,
+            allowed_image_types: config.get_allowed_image_types().to_vec(),
+            node_id: config.get_cluster_node_id(),
+            cluster_sync_interval: config.get_cluster_sync_interval(),
+            ..Default::default()
+        }
+    }
+    /// Spawns the Hub with a mailbox of [`HUB_MAILBOX_CAPACITY`]
+    pub fn start(self) -> ActorRef<Hub> {
+        Hub::spawn_with_mailbox(self, mailbox::bounded(HUB_MAILBOX_CAPACITY))
+    }
+    /// Messages waiting to be handled, a Hub falling behind shows up here first
+    pub fn mailbox_depth(hub: &ActorRef<Hub>) -> usize {
+        hub.mailbox_sender().capacity().map_or(0, |free| HUB_MAILBOX_CAPACITY.saturating_sub(free))
+    }
+    fn broadcast_client_response(&mut self, message: ClientResponse) {
+        for client in self.clients.values() {
+            if client.addr.tell(message.clone()).try_send().is_err() {
+                counter!(monitoring::DROPPED_MESSAGES, "reason" => "client_gone").increment(1);
+            }
         }
+        self.record_missed_by_all(&message);
     }
-    fn broadcast_client_response(&self, message: ClientResponse) {
-        for addr in self.clients.values() {
-            let _ = addr.tell(message.clone());
+    /// Asks a local pod for an image and remembers when, for the delivery latency
+    fn request_image(&mut self, gallery_id: PodId, path: GalleryPath, client_id: PodId) {
+        let Some(pod) = self.pods.get(&gallery_id) else { return };
+        let _ = pod.addr.tell(PodResponse::RequestImage { path: path.clone(), client_id }).try_send();
+        if self.image_requests.len() >= MAX_TRACKED_IMAGE_REQUESTS {
+            // pods that never answer must not make this grow forever
+            self.image_requests.retain(|_, requested| requested.elapsed() < IMAGE_REQUEST_TRACKING);
+        }
+        self.image_requests.insert((client_id, gallery_id, path), Instant::now());
+    }
+    fn update_connection_gauges(&self) {
+        gauge!(monitoring::CONNECTED_CLIENTS).set(self.clients.len() as f64);
+        gauge!(monitoring::REGISTERED_PODS).set(self.pods.len() as f64);
+    }
+    fn local_pod_description(&self, id: PodId) -> Option<PodDescription> {
+        self.pods.get(&id).map(|info| PodDescription {
+            id,
+            name: info.name.clone(),
+            paths: info.image_paths.clone(),
+            last_modified: info.last_modified,
+            version: info.version,
+            metadata: info.metadata.clone(),
+            curated_order: info.curated_order.clone(),
+        })
+    }
+    fn local_pod_descriptions(&self) -> Vec<PodDescription> {
+        self.pods.keys().filter_map(|&id| self.local_pod_description(id)).collect()
+    }
+    /// Pods of the whole cluster
+    fn pod_descriptions(&self) -> Vec<PodDescription> {
+        let mut pods = self.local_pod_descriptions();
+        pods.extend(self.remote_pods.values().cloned());
+        pods
+    }
+    /// Pods of the whole cluster, without their paths
+    fn pod_summaries(&self) -> Vec<PodSummary> {
+        let local = self.pods.iter().map(|(&id, info)| PodSummary {
+            id,
+            name: info.name.clone(),
+            path_count: info.image_paths.len() as u64,
+            last_modified: info.last_modified,
+            version: info.version,
+        });
+        let remote = self.remote_pods.values().map(|pod| PodSummary {
+            id: pod.id,
+            name: pod.name.clone(),
+            path_count: pod.paths.len() as u64,
+            last_modified: pod.last_modified,
+            version: pod.version,
+        });
+        local.chain(remote).collect()
+    }
+    /// Tells the other nodes about a change of one of our pods, they take over its whole state
+    fn local_pod_changed(&self, id: PodId) {
+        if self.peers.is_empty() {
+            return;
+        }
+        if let Some(pod) = self.local_pod_description(id) {
+            let message = ClusterPodChanged(pod);
+            for peer in self.peers.values() {
+                let _ = peer.hub.tell(&message).send();
+            }
         }
     }
+    /// Tells the search index and every client what changed about a pod,
+    /// the index first so it knows of everything a client may ask it about
+    fn publish_pod_update(&mut self, update: ClientResponse) {
+        if let Some(search_index) = &self.search_index {
+            let _ = search_index.tell(IndexUpdate(update.clone())).try_send();
+        }
+        self.broadcast_client_response(update);
+    }
+    /// Publishes the new paths of a local pod, `paths` sorted and without duplicates.
+    /// Metadata and curated order of other paths are dropped, the first mention in `curated_order` counts.
+    fn set_pod_paths(
+        &mut self,
+        id: PodId,
+        paths: Vec<GalleryPath>,
+        mut metadata: PathMetadata,
+        mut curated_order: Vec<GalleryPath>,
+        replace_images: bool,
+    ) {
+        let now = Utc::now();
+        metadata.retain(|path, _| paths.binary_search(path).is_ok());
+        let mut seen = HashSet::new();
+        curated_order.retain(|path| paths.binary_search(path).is_ok() && seen.insert(path.clone()));
+        let pod_info = self.pods.get_mut(&id).expect("unable to find PodInfo");
+        let old = PathListing { paths: &pod_info.image_paths, metadata: &pod_info.metadata, curated_order: &pod_info.curated_order };
+        let new = PathListing { paths: &paths, metadata: &metadata, curated_order: &curated_order };
+        let updates = path_updates(id, old, new, &mut pod_info.version, replace_images, now);
+        if updates.is_empty() {
+            return;
+        }
+        pod_info.image_paths = paths;
+        pod_info.metadata = metadata;
+        pod_info.curated_order = curated_order;
+        pod_info.last_modified = now;
+        for update in updates {
+            self.publish_pod_update(update);
+        }
+        self.local_pod_changed(id);
+    }
+    /// Responds to a client connected here or to another node of the cluster
+    fn send_to_client(&mut self, client_id: PodId, response: ClientResponse) {
+        if let Some(client) = self.clients.get(&client_id) {
+            let _ = client.addr.tell(response).try_send();
+        } else if self.record_missed(client_id, &response) {
+            // kept for a resume
+        } else if let Some(peer) = self.peers.get(&node_of(client_id)) {
+            let _ = peer.hub.tell(&ClusterDeliver { client_id, response }).send();
+        }
+    }
+    /// Checks what a pod delivers really is an allowed image, returns mime type and a data url declaring it
+    fn validate_image(&self, blob: &str) -> Result<(ImageType, String), ImageRejection> {
+        let (image_type, payload) = ImageType::sniff_data_url(blob)?;
+        if !self.allowed_image_types.contains(&image_type) {
+            return Err(ImageRejection::NotAllowed(image_type));
+        }
+        Ok((image_type, image_type.data_url(payload)))
+    }
+    fn respond_to_pod(&self, id: PodId, message: PodResponse) {
+        if let Some(pod) = self.pods.get(&id) {
+            let _ = pod.addr.tell(message).try_send();
+        }
+    }
+    /// Forget all share links matching `revoked` along with the expired ones and close the sessions opened through them
+    fn revoke_share_links(&mut self, revoked: impl Fn(u64, &ShareLink) -> bool) {
+        let now = Utc::now();
+        let mut ended = HashMap::new();
+        self.share_links.retain(|&link_id, link| {
+            let reason = if revoked(link_id, link) {
+                ShareLinkError::Revoked
+            } else if link.expires_at <= now {
+                ShareLinkError::Expired
+            } else {
+                return true;
+            };
+            ended.insert(link_id, reason);
+            false
+        });
+        for client in self.clients.values() {
+            if let Some(reason) = client.share_scope.and_then(|scope| ended.get(&scope.link_id)) {
+                let _ = client.addr.tell(Disconnect((CloseCode::Policy, reason.to_string()).into())).try_send();
+            }
+        }
+        self.end_sessions(|_, scope| scope.is_some_and(|scope| ended.contains_key(&scope.link_id)));
+    }
+    /// Forgets a client whose socket is gone, its session can be resumed for a while
+    fn unsubscribe_client(&mut self, id: PodId) {
+        self.clients.remove(&id);
+        self.image_requests.retain(|(client_id, _, _), _| *client_id != id);
+        self.update_connection_gauges();
+        self.detach_session(id);
+
+        // and pod if it was serving data
+        if let Some(lost_pod) = self.remove_pod(id) {
+            info!(pod_id = id, name = %lost_pod.name, remaining = self.pods.len(), "pod disconnected");
+        }
+        debug!(client_id = id, "client unsubscribed");
+    }
+    fn remove_pod(&mut self, id: PodId) -> Option<PodInfo> {
+        let lost_pod = self.pods.remove(&id)?;
+        self.image_requests.retain(|(_, gallery_id, _), _| *gallery_id != id);
+        self.update_connection_gauges();
+        self.revoke_share_links(|_, link| link.pod_id == id);
+        self.publish_pod_update(ClientResponse::PodGone(id));
+        for peer in self.peers.values() {
+            let _ = peer.hub.tell(&ClusterPodGone(id)).send();
+        }
+        Some(lost_pod)
+    }
+    /// The sessions opened through the link are closed once it expires, the Hub is reminded of that by a timer
+    fn create_share_link(&mut self, pod_id: PodId, expires_at: DateTime<Utc>, max_views: Option<u32>, hub: WeakActorRef<Hub>) -> PodResponse {
+        let Ok(lifetime) = (expires_at - Utc::now()).to_std() else {
+            return PodResponse::ShareLinkDenied { reason: ShareLinkError::Expired.to_string() };
+        };
+        let link_id = self.next_share_link_id;
+        self.next_share_link_id += 1;
+        let claims = ShareClaims { pod_id, link_id, expires_at };
+        let token = self.share_signer.sign(&claims);
+        self.share_links.insert(link_id, ShareLink { pod_id, expires_at, max_views, views: 0 });
+        tokio::spawn(async move {
+            tokio::time::sleep(lifetime).await;
+            if let Some(hub) = hub.upgrade() {
+                let _ = hub.tell(ExpireShareLinks).await;
+            }
+        });
+        PodResponse::ShareLink { token, expires_at, max_views }
+    }
+}
+/// The sorted paths of a pod with the metadata of its images
+#[derive(Clone, Copy)]
+struct PathListing<'a> {
+    paths: &'a [GalleryPath],
+    metadata: &'a PathMetadata,
+    /// Empty unless the pod declared an order
+    curated_order: &'a [GalleryPath],
+}
+impl<'a> From<&'a PodInfo> for PathListing<'a> {
+    fn from(info: &'a PodInfo) -> Self {
+        PathListing { paths: &info.image_paths, metadata: &info.metadata, curated_order: &info.curated_order }
+    }
+}
+impl<'a> From<&'a PodDescription> for PathListing<'a> {
+    fn from(pod: &'a PodDescription) -> Self {
+        PathListing { paths: &pod.paths, metadata: &pod.metadata, curated_order: &pod.curated_order }
+    }
+}
+impl<'a> PathListing<'a> {
+    fn contains(&self, path: &GalleryPath) -> bool {
+        self.paths.binary_search(path).is_ok()
+    }
+
+    /// The metadata of those `paths` that have some
+    fn metadata_of<'p>(&self, paths: impl IntoIterator<Item = &'p GalleryPath>) -> PathMetadata {
+        paths.into_iter()
+            .filter_map(|path| Some((path.clone(), self.metadata.get(path)?.clone())))
+            .collect()
+    }
+}
 
+/// What clients are told when the paths of a pod go from `old` to `new`.
+/// Deltas unless they are as big as the full listing, every message bumps `version`.
+/// An image whose metadata changed is a different file, it is removed and added again.
+/// A new curated order is sent as the full listing.
+fn path_updates(
+    id: PodId,
+    old: PathListing,
+    new: PathListing,
+    version: &mut u64,
+    replace_images: bool,
+    last_modified: DateTime<Utc>,
+) -> Vec<ClientResponse> {
+    let changed = |path: &GalleryPath| old.metadata.get(path) != new.metadata.get(path);
+    let removed: Vec<GalleryPath> = old.paths.iter().filter(|path| !new.contains(path) || changed(path)).cloned().collect();
+    let added: Vec<GalleryPath> = new.paths.iter().filter(|path| !old.contains(path) || changed(path)).cloned().collect();
+    let reordered = old.curated_order != new.curated_order;
+    if !replace_images && !reordered && removed.is_empty() && added.is_empty() {
+        return vec![];
+    }
+    if replace_images || reordered || removed.len() + added.len() >= new.paths.len() {
+        *version += 1;
+        return vec![ClientResponse::PodUpdatePaths {
+            id,
+            paths: new.paths.to_vec(),
+            replace_images,
+            last_modified,
+            version: *version,
+            metadata: new.metadata.clone(),
+            // the paths are sorted by name
+            curated_order: if new.curated_order == new.paths { vec![] } else { new.curated_order.to_vec() },
+        }];
+    }
+    let mut updates = vec![];
+    if !removed.is_empty() {
+        *version += 1;
+        updates.push(ClientResponse::PathsRemoved { id, paths: removed, last_modified, version: *version });
+    }
+    if !added.is_empty() {
+        *version += 1;
+        let metadata = new.metadata_of(&added);
+        updates.push(ClientResponse::PathsAdded { id, paths: added, last_modified, version: *version, metadata });
+    }
+    updates
 }
+
 impl Default for Hub{
     fn default() -> Self {
         Hub{
             pods: HashMap::new(),
             clients: HashMap::new(),
+            share_signer: ShareLinkSigner::random(),
+            share_links: HashMap::new(),
+            next_share_link_id: 0,
+            allowed_image_types: ImageType::DEFAULT_ALLOWED.to_vec(),
+            node_id: 0,
+            cluster_hub: None,
+            cluster_sync_interval: Duration::from_secs(5),
+            peers: HashMap::new(),
+            remote_pods: HashMap::new(),
+            image_requests: HashMap::new(),
+            sessions: HashMap::new(),
+            search_index: None,
         }
     }
 }
+impl RemoteActor for Hub {
+    const REMOTE_ID: &'static str = "gallery::Hub";
+}
 impl Actor for Hub {
     type Args = Self;
     type Error = Infallible;
-    async fn on_start(_state: Self::Args, _actor_ref: ActorRef<Self>) -> Result<Self, Self::Error> {
-        println!("HubActor started");
-        Ok(Hub::new())
+    async fn on_start(mut state: Self::Args, actor_ref: ActorRef<Self>) -> Result<Self, Self::Error> {
+        info!(node_id = state.node_id, "hub started");
+        // unbounded, the Hub must never wait for it
+        let search_index = SearchIndex::spawn_link_with_mailbox(&actor_ref, SearchIndex::default(), mailbox::unbounded()).await;
+        state.search_index = Some(search_index);
+        Ok(state)
+    }
+
+    /// Everything lives in memory, anything persisted later has to be flushed here
+    async fn on_stop(&mut self, _actor_ref: WeakActorRef<Self>, reason: ActorStopReason) -> Result<(), Self::Error> {
+        info!(%reason, "hub stopped");
+        Ok(())
     }
 }
 
-impl Message<SubscribeClient> for Hub {
+impl Message<Shutdown> for Hub {
     type Reply = ();
     async fn handle(
         &mut self,
-        msg: SubscribeClient,
+        msg: Shutdown,
         ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        self.clients.insert(msg.id, msg.addr);
-        ctx.forward(&ctx.actor_ref().clone(), ClientRequest::ListAllPods).await;
-        // maybe do self request through ctx.handle(...)
+        info!(code = ?msg.0, clients = self.clients.len(), pods = self.pods.len(), "closing every session");
+        let reason = match msg.0 {
+            CloseCode::Restart => "server restarting",
+            _ => "server shutting down",
+        };
+        for client in self.clients.values() {
+            let _ = client.addr.tell(Disconnect((msg.0, reason).into())).try_send();
+        }
+        // the other nodes drop our pods now instead of waiting for them to time out
+        for id in self.pods.keys() {
+            for peer in self.peers.values() {
+                let _ = peer.hub.tell(&ClusterPodGone(*id)).send();
+            }
+        }
+        self.clients.clear();
+        self.pods.clear();
+        self.image_requests.clear();
+        self.sessions.clear();
+        self.update_connection_gauges();
+        ctx.stop();
+    }
+}
+
+impl Message<SubscribeClient> for Hub {
+    type Reply = Option<ActorRef<SearchIndex>>;
+    async fn handle(
+        &mut self,
+        msg: SubscribeClient,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        debug!(client_id = msg.id, share_link = msg.share_scope.map(|scope| scope.link_id), "client subscribed");
+        self.clients.insert(msg.id, ClientInfo {
+            addr: msg.addr,
+            share_scope: msg.share_scope,
+            connection: msg.connection,
+        });
+        self.update_connection_gauges();
+        self.attach_session(msg.id, msg.share_scope);
+        self.search_index.clone()
     }
 }
 
@@ -203,18 +769,10 @@ impl Message<UnsubscribeClient> for Hub {
         msg: UnsubscribeClient,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        self.clients.remove(&msg.0);
-
-        // and pod if it was serving data
-        if let Some(lost_pod) = self.pods.remove(&msg.0) {
-            let message = ClientResponse::PodGone(msg.0);
-            self.broadcast_client_response(message);
-            println!("removing pod {}: {:?}", msg.0, lost_pod);
-            println!("  > remaining: {:?}", self.pods);
+        // a socket whose session was resumed elsewhere has been unsubscribed already
+        if self.clients.get(&msg.id).is_some_and(|client| client.addr.id() == msg.actor_id) {
+            self.unsubscribe_client(msg.id);
         }
-
-        println!("UnsubscribeClient: {:?}", msg.0);
-
     }
 }
 
@@ -225,69 +783,50 @@ impl Message<SubscribePod> for Hub{
         msg: SubscribePod,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
+        info!(pod_id = msg.id, name = %msg.name, "pod registered");
         self.pods.insert(msg.id, PodInfo {
             addr: msg.addr,
             name: msg.name.clone(),
             image_paths: vec![],
+            curated_order: vec![],
+            metadata: PathMetadata::new(),
             last_modified: Utc::now(),
+            version: 0,
         });
-        self.broadcast_client_response(ClientResponse::NewPod { id: msg.id, name: msg.name, });
+        self.update_connection_gauges();
+        self.publish_pod_update(ClientResponse::NewPod { id: msg.id, name: msg.name, });
+        self.local_pod_changed(msg.id);
     }
 }
 
 impl Message<UnsubscribePod> for Hub{
-    type Reply = ();
+    type Reply = bool;
     async fn handle(
         &mut self,
         msg: UnsubscribePod,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        if let Some(lost_pod) = self.pods.remove(&msg.0) {
-            let message = ClientResponse::PodGone(msg.0);
-            self.broadcast_client_response(message);
-            println!("removing pod {}:{:?}", msg.0, lost_pod.name);
-        }
+        let Some(lost_pod) = self.remove_pod(msg.0) else {
+            return false;
+        };
+        info!(pod_id = msg.0, name = %lost_pod.name, "pod unsubscribed");
+        let _ = lost_pod.addr.tell(PodRemoved).try_send();
+        true
     }
 
 }
 
-impl Message<ClientRequest> for Hub {
+impl Message<HubRequest> for Hub {
     type Reply = ClientResponse;
     async fn handle(
         &mut self,
-        msg: ClientRequest,
+        msg: HubRequest,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        use crate::protocols::ClientRequest::*;
-        let r = match msg {
-            ListAllPods => {
-                let pods = self.pods.iter().map(|(&id, info)| {
-                    crate::protocols::PodDescription {
-                        id,
-                        name: info.name.clone(),
-                        paths: info.image_paths.clone(),
-                        last_modified: info.last_modified,
-                    }
-                }).collect();
-                ClientResponse::Pods(pods)
-            }
-            ListPodStructure(id) => {
-                match self.pods.get(&id) {
-                    Some(info) => {
-                        ClientResponse::PodUpdatePaths {
-                            id,
-                            paths: info.image_paths.clone(),
-                            replace_images: false,
-                            last_modified: info.last_modified,
-                        }
-                    }
-                    None => {
-                        ClientResponse::UnknownPod(id)
-                    }
-                }
-            }
-        };
-        r
+        match msg {
+            HubRequest::ListAllPods => ClientResponse::Pods(self.pod_descriptions()),
+            HubRequest::ListPodSummaries => ClientResponse::PodSummaries(self.pod_summaries()),
+        }
     }
 }
 
@@ -300,19 +839,14 @@ impl Message<ClientRequestAsync> for Hub {
     ) -> Self::Reply {
         match msg {
             ClientRequestAsync::RequestImage { gallery_id, path, client_id } => {
-                match self.pods.get(&gallery_id) {
-                    Some(pod) => {
-                        let _ = pod.addr.tell(PodResponse::RequestImage{
-                            path,
-                            client_id
-                        });
-                    }
-                    None => {
-                        if let Some(client) = self.clients.get(&client_id) {
-                            let _ = client.tell(
-                                ClientResponse::UnknownPod(gallery_id)
-                            );
+                debug!(pod_id = gallery_id, client_id, path = %path, "image requested");
+                match self.pods.contains_key(&gallery_id) {
+                    true => self.request_image(gallery_id, path, client_id),
+                    false => match self.peers.get(&node_of(gallery_id)) {
+                        Some(peer) if self.remote_pods.contains_key(&gallery_id) => {
+                            let _ = peer.hub.tell(&ClusterRequestImage { gallery_id, path, client_id }).send();
                         }
+                        _ => self.send_to_client(client_id, ClientResponse::UnknownPod(gallery_id)),
                     }
                 }
             }
@@ -325,58 +859,185 @@ impl Message<IdedPodRequest> for Hub {
     async fn handle(
         &mut self,
         msg: IdedPodRequest,
-        _ctx: &mut Context<Self, Self::Reply>,
+        ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
         use crate::protocols::PodRequest::*;
+        if !self.pods.contains_key(&msg.id) {
+            warn!(client_id = msg.id, "ignoring request of unregistered pod");
+            return;
+        }
         match msg.message {
-            RegisterSelf { proposed_id, name } => unreachable!("must be handles by WebClient"),
+            RegisterSelf { .. } => unreachable!("must be handles by WebClient"),
             UpdateTitle { name } => {
+                debug!(pod_id = msg.id, name = %name, "pod renamed");
                 self.pods.get_mut(&msg.id).expect("unable to find PodInfo").name = name.clone();
-                self.broadcast_client_response(ClientResponse::PodUpdateName{ id: msg.id, name, });
+                self.publish_pod_update(ClientResponse::PodUpdateName{ id: msg.id, name, });
+                self.local_pod_changed(msg.id);
+            }
+            UpdatePaths { mut paths, replace_images, curated, metadata } => {
+                let curated_order = if curated { paths.clone() } else { vec![] };
+                paths.sort();
+                paths.dedup_by(|a, b| a == b);
+                debug!(pod_id = msg.id, paths = paths.len(), replace_images, curated, "pod updated its paths");
+                self.set_pod_paths(msg.id, paths, metadata, curated_order, replace_images);
             }
-            UpdatePaths { mut paths, replace_images } => {
+            PathsAdded { paths: added, metadata: added_metadata } => {
+                debug!(pod_id = msg.id, added = added.len(), "pod added paths");
+                let pod_info = &self.pods[&msg.id];
+                let mut paths = pod_info.image_paths.clone();
+                let mut metadata = pod_info.metadata.clone();
+                let mut curated_order = pod_info.curated_order.clone();
+                if !curated_order.is_empty() {
+                    // after the curated ones, those already there keep their place
+                    curated_order.extend(added.iter().cloned());
+                }
+                paths.extend(added);
                 paths.sort();
                 paths.dedup_by(|a, b| a == b);
-                let now = Utc::now();
-                let pod_info = self.pods.get_mut(&msg.id).expect("unable to find PodInfo");
-                pod_info.image_paths = paths.clone();
-                pod_info.last_modified = now;
-                self.broadcast_client_response(ClientResponse::PodUpdatePaths{
-                    id: msg.id,
-                    paths,
-                    replace_images,
-                    last_modified: now,
-                });
+                metadata.extend(added_metadata);
+                self.set_pod_paths(msg.id, paths, metadata, curated_order, false);
+            }
+            PathsRemoved { paths: mut removed } => {
+                debug!(pod_id = msg.id, removed = removed.len(), "pod removed paths");
+                removed.sort();
+                let pod_info = &self.pods[&msg.id];
+                let mut paths = pod_info.image_paths.clone();
+                paths.retain(|path| removed.binary_search(path).is_err());
+                self.set_pod_paths(msg.id, paths, pod_info.metadata.clone(), pod_info.curated_order.clone(), false);
             }
             DeliverImage { client_id, path, blob } => {
-                if let Some(client) = self.clients.get(&client_id) {
-                    let _ = client.tell(ClientResponse::DeliverImage {
-                        gallery_id: msg.id,
-                        path,
-                        blob,
-                    });
+                if let Some(requested) = self.image_requests.remove(&(client_id, msg.id, path.clone())) {
+                    histogram!(monitoring::IMAGE_DELIVERY_SECONDS).record(requested.elapsed());
                 }
+                let response = match self.validate_image(&blob) {
+                    Ok((image_type, blob)) => {
+                        counter!(monitoring::IMAGES_DELIVERED).increment(1);
+                        debug!(pod_id = msg.id, client_id, path = %path, mime = image_type.mime(), "image delivered");
+                        ClientResponse::DeliverImage {
+                            gallery_id: msg.id,
+                            path,
+                            mime: image_type.mime().into(),
+                            blob,
+                        }
+                    }
+                    Err(rejection) => {
+                        counter!(monitoring::IMAGES_REJECTED).increment(1);
+                        warn!(pod_id = msg.id, client_id, path = %path, %rejection, "pod delivered an invalid image");
+                        self.respond_to_pod(msg.id, PodResponse::ImageRejected {
+                            client_id,
+                            path: path.clone(),
+                            reason: rejection.to_string(),
+                        });
+                        ClientResponse::ImageRejected {
+                            gallery_id: msg.id,
+                            path,
+                            reason: rejection.to_string(),
+                        }
+                    }
+                };
+                self.send_to_client(client_id, response);
+            }
+            CreateShareLink { expires_at, max_views } => {
+                let response = self.create_share_link(msg.id, expires_at, max_views, ctx.actor_ref().downgrade());
+                info!(pod_id = msg.id, %expires_at, max_views, granted = matches!(response, PodResponse::ShareLink { .. }), "share link requested");
+                self.respond_to_pod(msg.id, response);
+            }
+            RevokeShareLink { token } => {
+                let response = match self.share_signer.verify(&token) {
+                    Ok(claims) if claims.pod_id == msg.id && self.share_links.contains_key(&claims.link_id) => {
+                        info!(pod_id = msg.id, link_id = claims.link_id, "share link revoked");
+                        self.revoke_share_links(|link_id, _| link_id == claims.link_id);
+                        PodResponse::ShareLinkRevoked { token }
+                    }
+                    Ok(_) => PodResponse::ShareLinkDenied { reason: ShareLinkError::Revoked.to_string() },
+                    Err(error) => PodResponse::ShareLinkDenied { reason: error.to_string() },
+                };
+                self.respond_to_pod(msg.id, response);
             }
         }
     }
 }
 
+impl Message<RedeemShareLink> for Hub {
+    type Reply = Result<ShareScope, ShareLinkError>;
+    async fn handle(
+        &mut self,
+        msg: RedeemShareLink,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let claims = self.share_signer.verify(&msg.token)
+            .inspect_err(|error| info!(%error, "refused share token"))?;
+        let link = self.share_links.get_mut(&claims.link_id).ok_or(ShareLinkError::Revoked)?;
+        if link.pod_id != claims.pod_id {
+            return Err(ShareLinkError::Revoked);
+        }
+        if link.max_views.is_some_and(|max_views| link.views >= max_views) {
+            info!(pod_id = claims.pod_id, link_id = claims.link_id, "share link has no views left");
+            return Err(ShareLinkError::Exhausted);
+        }
+        link.views += 1;
+        info!(pod_id = claims.pod_id, link_id = claims.link_id, views = link.views, "share link redeemed");
+        Ok(ShareScope {
+            pod_id: claims.pod_id,
+            link_id: claims.link_id,
+        })
+    }
+}
+
+/// Closes the sessions of share links that expired meanwhile
+pub struct ExpireShareLinks;
+
+impl Message<ExpireShareLinks> for Hub {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        _msg: ExpireShareLinks,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.revoke_share_links(|_, _| false);
+    }
+}
+
 pub struct SubscribePod {
     id: PodId,
     addr: ActorRef<WebClient>,
     name: String,
 }
 
-pub struct UnsubscribePod(PodId);
+/// Closes every session with the given code, `Restart` or `Away`, then stops the Hub
+pub struct Shutdown(pub CloseCode);
+
+/// Forgets a pod while its session stays connected, replies whether it was registered
+pub struct UnsubscribePod(pub PodId);
+
+/// The answer to a request of the client itself, see [`OutboundQueue::push_reply`]
+struct Reply(ClientResponse);
 
+/// The requests of [`ClientRequest`] about every pod, the Hub answers those only
+enum HubRequest {
+    ListAllPods,
+    ListPodSummaries,
+}
+
+/// Answered with the search index the client sends its searches and listings to
 pub struct SubscribeClient {
     id: PodId,
     addr: ActorRef<WebClient>,
+    share_scope: Option<ShareScope>,
+    connection: Arc<ConnectionInfo>,
 }
 
-pub struct UnsubscribeClient(PodId);
+pub struct UnsubscribeClient {
+    id: PodId,
+    actor_id: ActorId,
+}
 
 pub struct IdedPodRequest {
     id: PodId,
     message: PodRequest,
 }
+
+/// Exchange a share token for the scope of a limited client session
+pub struct RedeemShareLink {
+    pub token: String,
+}
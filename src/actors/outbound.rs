@@ -0,0 +1,290 @@
+//! Frames a [`WebClient`](super::WebClient) queued for its websocket. The queue is bounded by bytes,
+//! a slow socket first loses what can be recomputed or requested again and is closed if that is not enough.
+
+use std::{collections::VecDeque, sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}}, time::{Duration, Instant}};
+
+use metrics::counter;
+use tokio::sync::Notify;
+
+use crate::monitoring;
+use crate::protocols::{ClientResponse, JsonProtocol, PodId};
+use super::websocket::{CloseReason, Message};
+
+/// Sent instead of images a client was too slow to receive, so it does not wait for them forever
+pub const TOO_SLOW: &str = "client too slow, request again";
+
+/// Queued bytes stayed above the limit for longer than the grace period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow {
+    pub queued_bytes: usize,
+}
+
+/// Frames that describe the same state, only the latest of them is worth sending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Name(PodId),
+    Paths(PodId),
+}
+
+struct Frame {
+    message: Message,
+    bytes: usize,
+    /// Replaced in place by a newer frame with the same key
+    key: Option<Key>,
+    /// `replace_images` of a `PodUpdatePaths`, which a replacement must not lose
+    replace_images: bool,
+    /// `PathsAdded` or `PathsRemoved`, obsolete once a `PodUpdatePaths` of the same pod is queued
+    paths_delta: bool,
+    /// The pod this frame is about, obsolete once `PodGone` for it is queued
+    pod: Option<PodId>,
+    /// Set for `PodGone`, which is never dropped
+    pod_gone: bool,
+    /// Sent instead when the queue is over its limit, only images have one
+    fallback: Option<Message>,
+}
+impl Frame {
+    fn new(message: Message) -> Self {
+        Frame { bytes: message_bytes(&message), message, key: None, replace_images: false, paths_delta: false, pod: None, pod_gone: false, fallback: None }
+    }
+
+    fn response(response: &ClientResponse) -> Self {
+        let mut frame = Frame::new(text(&JsonProtocol::ClientResponse(response.clone())));
+        frame.pod = response.pod_id();
+        match response {
+            ClientResponse::PodUpdateName { id, .. } => frame.key = Some(Key::Name(*id)),
+            ClientResponse::PodUpdatePaths { id, replace_images, .. } => {
+                frame.key = Some(Key::Paths(*id));
+                frame.replace_images = *replace_images;
+            }
+            ClientResponse::PathsAdded { .. } | ClientResponse::PathsRemoved { .. } => frame.paths_delta = true,
+            ClientResponse::PodGone(_) => frame.pod_gone = true,
+            ClientResponse::DeliverImage { gallery_id, path, .. } => {
+                frame.fallback = Some(text(&JsonProtocol::ClientResponse(ClientResponse::ImageRejected {
+                    gallery_id: *gallery_id,
+                    path: path.clone(),
+                    reason: TOO_SLOW.into(),
+                })));
+            }
+            _ => {}
+        }
+        frame
+    }
+}
+
+fn text(message: &JsonProtocol) -> Message {
+    Message::Text(serde_json::to_string(message).expect("unable to serialize internal state"))
+}
+
+fn message_bytes(message: &Message) -> usize {
+    match message {
+        Message::Text(text) | Message::Ping(text) | Message::Pong(text) => text.len(),
+        Message::Binary(bytes) => bytes.len(),
+        Message::Close(_) | Message::Nop => 0,
+    }
+}
+
+struct State {
+    frames: VecDeque<Frame>,
+    bytes: usize,
+    over_limit_since: Option<Instant>,
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    frame_queued: Notify,
+    senders: AtomicUsize,
+    max_bytes: usize,
+    grace: Duration,
+}
+
+/// Sending half, the queue closes once every clone is dropped
+pub struct OutboundQueue {
+    shared: Arc<Shared>,
+}
+
+/// Receiving half, owned by the task writing into the websocket
+pub struct OutboundReceiver {
+    shared: Arc<Shared>,
+}
+
+impl OutboundQueue {
+    /// A queue holding up to `max_bytes`, a socket may stay above that for `grace` before it overflows.
+    /// Twice the limit overflows right away.
+    pub fn new(max_bytes: usize, grace: Duration) -> (Self, OutboundReceiver) {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State { frames: VecDeque::new(), bytes: 0, over_limit_since: None, closed: false }),
+            frame_queued: Notify::new(),
+            senders: AtomicUsize::new(1),
+            max_bytes,
+            grace,
+        });
+        (OutboundQueue { shared: shared.clone() }, OutboundReceiver { shared })
+    }
+
+    /// Queues a frame that is never dropped nor replaced
+    pub fn push(&self, message: Message) -> Result<(), Overflow> {
+        self.enqueue(Frame::new(message))
+    }
+
+    /// Queues a response, stale updates of the same pod are replaced and images may be dropped
+    pub fn push_response(&self, response: &ClientResponse) -> Result<(), Overflow> {
+        let frame = match response {
+            // the update it replaces may have asked to drop the images, so it has to ask as well
+            ClientResponse::PodUpdatePaths { id, paths, replace_images: false, last_modified, version, metadata, curated_order } if self.queued_replace_images(*id) => {
+                Frame::response(&ClientResponse::PodUpdatePaths {
+                    id: *id,
+                    paths: paths.clone(),
+                    replace_images: true,
+                    last_modified: *last_modified,
+                    version: *version,
+                    metadata: metadata.clone(),
+                    curated_order: curated_order.clone(),
+                })
+            }
+            _ => Frame::response(response),
+        };
+        self.enqueue(frame)
+    }
+
+    /// Queues the answer to a request of the client, which updates queued later must not replace
+    /// even if it looks like one of them, e.g. the `PodUpdatePaths` answering a bare `ListPodStructure`
+    pub fn push_reply(&self, response: &ClientResponse) -> Result<(), Overflow> {
+        let mut frame = Frame::response(response);
+        frame.key = None;
+        frame.replace_images = false;
+        self.enqueue(frame)
+    }
+
+    /// Drops everything still queued and closes the socket as soon as possible
+    pub fn close_now(&self, reason: CloseReason) {
+        let mut state = self.shared.state.lock().expect("outbound queue poisoned");
+        state.frames.clear();
+        state.bytes = 0;
+        state.frames.push_back(Frame::new(Message::Close(Some(reason))));
+        drop(state);
+        self.shared.frame_queued.notify_one();
+    }
+
+    fn queued_replace_images(&self, id: PodId) -> bool {
+        let state = self.shared.state.lock().expect("outbound queue poisoned");
+        state.frames.iter().any(|queued| queued.key == Some(Key::Paths(id)) && queued.replace_images)
+    }
+
+    fn enqueue(&self, frame: Frame) -> Result<(), Overflow> {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().expect("outbound queue poisoned");
+        if frame.pod_gone {
+            // updates and images of a gone pod are not worth sending anymore
+            state.drop_about(frame.pod);
+        }
+        if let Some(Key::Paths(id)) = frame.key {
+            // the full listing includes whatever the deltas before it changed
+            state.drop_deltas_of(id);
+        }
+        match frame.key.and_then(|key| state.frames.iter().position(|queued| queued.key == Some(key))) {
+            Some(index) => {
+                let bytes = frame.bytes;
+                let replaced = std::mem::replace(&mut state.frames[index], frame);
+                state.bytes = state.bytes - replaced.bytes + bytes;
+                counter!(monitoring::DROPPED_MESSAGES, "reason" => "superseded").increment(1);
+            }
+            None => {
+                state.bytes += frame.bytes;
+                state.frames.push_back(frame);
+            }
+        }
+
+        if state.bytes > shared.max_bytes {
+            state.replace_expendable(shared.max_bytes);
+        }
+        let result = if state.bytes <= shared.max_bytes {
+            state.over_limit_since = None;
+            Ok(())
+        } else {
+            let since = *state.over_limit_since.get_or_insert_with(Instant::now);
+            match state.bytes > shared.max_bytes.saturating_mul(2) || since.elapsed() > shared.grace {
+                true => Err(Overflow { queued_bytes: state.bytes }),
+                false => Ok(()),
+            }
+        };
+        drop(state);
+        shared.frame_queued.notify_one();
+        result
+    }
+}
+
+impl State {
+    fn drop_about(&mut self, pod: Option<PodId>) {
+        let before = self.frames.len();
+        self.frames.retain(|queued| queued.pod != pod || (queued.key.is_none() && queued.fallback.is_none() && !queued.paths_delta));
+        self.bytes = self.frames.iter().map(|frame| frame.bytes).sum();
+        let dropped = before - self.frames.len();
+        if dropped > 0 {
+            counter!(monitoring::DROPPED_MESSAGES, "reason" => "pod_gone").increment(dropped as u64);
+        }
+    }
+
+    fn drop_deltas_of(&mut self, pod: PodId) {
+        let before = self.frames.len();
+        self.frames.retain(|queued| !(queued.paths_delta && queued.pod == Some(pod)));
+        self.bytes = self.frames.iter().map(|frame| frame.bytes).sum();
+        let dropped = before - self.frames.len();
+        if dropped > 0 {
+            counter!(monitoring::DROPPED_MESSAGES, "reason" => "superseded").increment(dropped as u64);
+        }
+    }
+
+    /// Swaps the oldest images for their small fallback until the queue fits again
+    fn replace_expendable(&mut self, max_bytes: usize) {
+        for frame in self.frames.iter_mut() {
+            if self.bytes <= max_bytes {
+                break;
+            }
+            let Some(fallback) = frame.fallback.take() else { continue };
+            let bytes = message_bytes(&fallback);
+            self.bytes = self.bytes - frame.bytes + bytes;
+            frame.message = fallback;
+            frame.bytes = bytes;
+            counter!(monitoring::DROPPED_MESSAGES, "reason" => "slow_client").increment(1);
+        }
+    }
+}
+
+impl Clone for OutboundQueue {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        OutboundQueue { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for OutboundQueue {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.state.lock().expect("outbound queue poisoned").closed = true;
+            self.shared.frame_queued.notify_one();
+        }
+    }
+}
+
+impl OutboundReceiver {
+    /// The next frame to write, `None` once the queue is empty and closed
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().expect("outbound queue poisoned");
+                if let Some(frame) = state.frames.pop_front() {
+                    state.bytes -= frame.bytes;
+                    if state.bytes <= self.shared.max_bytes {
+                        state.over_limit_since = None;
+                    }
+                    return Some(frame.message);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.shared.frame_queued.notified().await;
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use std::{borrow::Cow, cmp::Ordering, collections::{BTreeMap, HashMap}, ops::Range};
+
+use chrono::{DateTime, Utc};
+use kameo::{error::Infallible, prelude::*};
+use tracing::debug;
+
+use crate::protocols::{ClientResponse, GalleryPath, Glob, MAX_PAGE_SIZE, PathMetadata, PodId, SearchFilters, SearchHit, SortItem, SortKey, SortOrder, StructureQuery};
+use super::WebClient;
+
+/// Answers searches and path listings across the galleries of the whole cluster, so the Hub never walks every path itself.
+/// The Hub forwards it every change of a pod it tells clients about, clients send it their requests directly.
+#[derive(Default)]
+pub struct SearchIndex {
+    pods: BTreeMap<PodId, IndexedPod>,
+}
+
+struct IndexedPod {
+    /// Lowercase, for [`SearchFilters::gallery`]
+    name: String,
+    /// Sorted, each with its lowercase file name the query is matched against
+    paths: Vec<(GalleryPath, String)>,
+    metadata: PathMetadata,
+    /// Empty if the pod declared none or it is the order of `paths`
+    curated_order: Vec<GalleryPath>,
+    /// Positions in `curated_order`
+    ranks: HashMap<GalleryPath, usize>,
+    version: u64,
+    last_modified: DateTime<Utc>,
+    /// The orders listed so far, forgotten with every change of the paths
+    sorted: HashMap<SortOrder, Sorted>,
+}
+
+/// The paths of a pod in one [`SortOrder`], by their index in [`IndexedPod::paths`]
+struct Sorted {
+    order: Vec<usize>,
+    /// Where each path is in `order`
+    position: Vec<usize>,
+}
+
+impl IndexedPod {
+    fn new(name: &str) -> Self {
+        IndexedPod {
+            name: name.to_lowercase(),
+            paths: vec![],
+            metadata: PathMetadata::new(),
+            curated_order: vec![],
+            ranks: HashMap::new(),
+            version: 0,
+            last_modified: Utc::now(),
+            sorted: HashMap::new(),
+        }
+    }
+
+    /// `paths` are sorted like in every `PodUpdatePaths`, only those new to the pod get their name lowercased
+    fn set_paths(&mut self, paths: Vec<GalleryPath>) {
+        let mut known = std::mem::take(&mut self.paths).into_iter().peekable();
+        self.paths = paths.into_iter().map(|path| {
+            while known.next_if(|(old, _)| *old < path).is_some() {}
+            let name = match known.next_if(|(old, _)| *old == path) {
+                Some((_, name)) => name,
+                None => lowercase_name(&path),
+            };
+            (path, name)
+        }).collect();
+    }
+
+    fn add_paths(&mut self, paths: Vec<GalleryPath>) {
+        let mut added: Vec<(GalleryPath, String)> = paths.into_iter().map(|path| {
+            let name = lowercase_name(&path);
+            (path, name)
+        }).collect();
+        added.sort_by(|(a, _), (b, _)| a.cmp(b));
+        // two sorted runs, merged rather than sorted again
+        let mut known = std::mem::take(&mut self.paths).into_iter().peekable();
+        let mut added = added.into_iter().peekable();
+        self.paths.reserve(known.len() + added.len());
+        loop {
+            let next = match (known.peek(), added.peek()) {
+                (Some((old, _)), Some((new, _))) => match old.cmp(new) {
+                    Ordering::Less => known.next(),
+                    Ordering::Equal => {
+                        added.next();
+                        known.next()
+                    }
+                    Ordering::Greater => added.next(),
+                },
+                (Some(_), None) => known.next(),
+                (None, Some(_)) => added.next(),
+                (None, None) => break,
+            };
+            self.paths.extend(next);
+        }
+        self.paths.dedup_by(|(a, _), (b, _)| a == b);
+    }
+
+    fn remove_paths(&mut self, mut paths: Vec<GalleryPath>) {
+        paths.sort();
+        self.paths.retain(|(path, _)| paths.binary_search(path).is_err());
+        for path in &paths {
+            self.metadata.remove(path);
+        }
+    }
+
+    fn set_curated_order(&mut self, curated_order: Vec<GalleryPath>) {
+        self.ranks = curated_order.iter().enumerate().map(|(rank, path)| (path.clone(), rank)).collect();
+        self.curated_order = curated_order;
+    }
+
+    fn sort_item<'a>(&'a self, path: &'a GalleryPath) -> SortItem<'a> {
+        SortItem { path, metadata: self.metadata.get(path), rank: self.ranks.get(path).copied() }
+    }
+
+    /// Sorts the paths in `sort` order unless they were already since the last change
+    fn sort(&mut self, sort: SortOrder) {
+        if self.sorted.contains_key(&sort) {
+            return;
+        }
+        let mut order: Vec<usize> = (0..self.paths.len()).collect();
+        order.sort_by(|&a, &b| sort.compare(self.sort_item(&self.paths[a].0), self.sort_item(&self.paths[b].0)));
+        let mut position = vec![0; order.len()];
+        for (at, &index) in order.iter().enumerate() {
+            position[index] = at;
+        }
+        self.sorted.insert(sort, Sorted { order, position });
+    }
+
+    /// Indices of the paths starting with `prefix`, they are next to each other
+    fn range(&self, prefix: &str) -> Range<usize> {
+        let start = self.paths.partition_point(|(path, _)| path.as_str() < prefix);
+        let end = start + self.paths[start..].partition_point(|(path, _)| path.as_str().starts_with(prefix));
+        start..end
+    }
+
+    /// The page of the `matching` paths following `after` in `sort` order, which has to be sorted already.
+    /// At most `limit` up to [`MAX_PAGE_SIZE`], with the `next` to continue from while more follow.
+    fn page(&self, sort: SortOrder, mut matching: Vec<usize>, after: Option<&GalleryPath>, limit: Option<u32>) -> (Vec<GalleryPath>, Option<GalleryPath>) {
+        let sorted = &self.sorted[&sort];
+        let matching: Cow<[usize]> = match matching.len() == self.paths.len() {
+            true => Cow::Borrowed(&sorted.order),
+            false => {
+                matching.sort_unstable_by_key(|&index| sorted.position[index]);
+                Cow::Owned(matching)
+            }
+        };
+        let limit = limit.map_or(MAX_PAGE_SIZE, |limit| (limit as usize).clamp(1, MAX_PAGE_SIZE));
+        // `after` may be gone since, the page starts where it would be
+        let start = after.map_or(0, |after| {
+            matching.partition_point(|&index| sort.compare(self.sort_item(&self.paths[index].0), self.sort_item(after)).is_le())
+        });
+        let page: Vec<GalleryPath> = matching[start..].iter().take(limit).map(|&index| self.paths[index].0.clone()).collect();
+        let next = match matching.len() - start > limit {
+            true => page.last().cloned(),
+            false => None,
+        };
+        (page, next)
+    }
+
+    /// The metadata of those `paths` that have some
+    fn metadata_of(&self, paths: &[GalleryPath]) -> PathMetadata {
+        paths.iter()
+            .filter_map(|path| Some((path.clone(), self.metadata.get(path)?.clone())))
+            .collect()
+    }
+
+    /// Every path sorted by name like clients are told about them, see [`StructureQuery::Pod`]
+    fn full_listing(&self, id: PodId) -> ClientResponse {
+        ClientResponse::PodUpdatePaths {
+            id,
+            paths: self.paths.iter().map(|(path, _)| path.clone()).collect(),
+            replace_images: false,
+            last_modified: self.last_modified,
+            version: self.version,
+            metadata: self.metadata.clone(),
+            curated_order: self.curated_order.clone(),
+        }
+    }
+
+    /// Every path in `sort` order, see [`StructureQuery::Sorted`]
+    fn structure(&mut self, id: PodId, sort: SortOrder) -> ClientResponse {
+        self.sort(sort);
+        let paths = self.sorted[&sort].order.iter().map(|&index| self.paths[index].0.clone()).collect();
+        ClientResponse::PodStructure {
+            id,
+            sort,
+            paths,
+            last_modified: self.last_modified,
+            version: self.version,
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// See [`crate::protocols::ClientRequest::ListPodPaths`]
+    fn list_paths(&mut self, id: PodId, prefix: Option<String>, glob: Option<String>, after: Option<GalleryPath>, limit: Option<u32>, sort: SortOrder) -> ClientResponse {
+        let version = self.version;
+        // compiled once, a glob too long for that matches nothing
+        let glob = match glob.as_deref().map(Glob::new) {
+            Some(None) => return ClientResponse::PodPaths { id, paths: vec![], next: None, total: 0, version, metadata: PathMetadata::new() },
+            glob => glob.flatten(),
+        };
+        let matching: Vec<usize> = self.range(prefix.as_deref().unwrap_or_default())
+            .filter(|&index| glob.as_ref().is_none_or(|glob| glob.matches(&self.paths[index].0)))
+            .collect();
+        let total = matching.len() as u64;
+        self.sort(sort);
+        let (paths, next) = self.page(sort, matching, after.as_ref(), limit);
+        let metadata = self.metadata_of(&paths);
+        ClientResponse::PodPaths { id, paths, next, total, version, metadata }
+    }
+
+    /// See [`crate::protocols::ClientRequest::ListFolder`], the first page comes with the subfolders
+    fn list_folder(&mut self, gallery_id: PodId, folder: String, sort: SortOrder, after: Option<GalleryPath>, limit: Option<u32>) -> ClientResponse {
+        let folder = folder.trim_matches('/').to_string();
+        let prefix = match folder.as_str() {
+            "" => String::new(),
+            folder => format!("{folder}/"),
+        };
+        let mut folders: Vec<String> = vec![];
+        let mut images = vec![];
+        for index in self.range(&prefix) {
+            match self.paths[index].0.as_str()[prefix.len()..].split_once('/') {
+                // the paths below a subfolder share its prefix and are next to each other
+                Some((name, _)) if folders.last().is_some_and(|last| last == name) => (),
+                Some((name, _)) => folders.push(name.to_string()),
+                None => images.push(index),
+            }
+        }
+        // `a-b/` comes before `a/` in the paths
+        folders.sort();
+        if after.is_some() {
+            folders.clear();
+        }
+        let total = images.len() as u64;
+        self.sort(sort);
+        let (images, next) = self.page(sort, images, after.as_ref(), limit);
+        let metadata = self.metadata_of(&images);
+        ClientResponse::FolderListing { gallery_id, folder, folders, images, sort, next, total, version: self.version, metadata }
+    }
+}
+
+fn lowercase_name(path: &GalleryPath) -> String {
+    path.components().last().unwrap_or_default().to_lowercase()
+}
+
+/// Orders hits of different galleries, the curated order only means something within one of them
+fn compare_hits(sort: SortOrder, (a_id, a): (PodId, SortItem), (b_id, b): (PodId, SortItem)) -> Ordering {
+    match sort.key {
+        SortKey::Curated => a_id.cmp(&b_id).then_with(|| sort.compare(a, b)),
+        _ => sort.compare_keys(a, b).then_with(|| (a_id, a.path).cmp(&(b_id, b.path))),
+    }
+}
+
+/// What clients are told about a pod
+pub(super) struct IndexUpdate(pub ClientResponse);
+
+/// The [`crate::protocols::ClientRequest`]s listing the paths of one pod
+pub(super) enum Listing {
+    Structure(StructureQuery),
+    Paths { id: PodId, prefix: Option<String>, glob: Option<String>, after: Option<GalleryPath>, limit: Option<u32>, sort: SortOrder },
+    Folder { gallery_id: PodId, folder: String, sort: SortOrder, after: Option<GalleryPath>, limit: Option<u32> },
+}
+impl Listing {
+    pub fn id(&self) -> PodId {
+        match *self {
+            Listing::Structure(query) => query.id(),
+            Listing::Paths { id, .. } | Listing::Folder { gallery_id: id, .. } => id,
+        }
+    }
+}
+
+/// A [`crate::protocols::ClientRequest::Search`], the results are sent to `client`
+pub(super) struct RunSearch {
+    pub client: ActorRef<WebClient>,
+    pub query: String,
+    pub filters: SearchFilters,
+    pub sort: SortOrder,
+    pub after: Option<SearchHit>,
+    pub limit: Option<u32>,
+    /// Share sessions only search their own gallery
+    pub scope: Option<PodId>,
+}
+
+impl SearchIndex {
+    fn search(&self, msg: RunSearch) -> ClientResponse {
+        let query = msg.query.to_lowercase();
+        let gallery = msg.filters.gallery.map(|gallery| gallery.to_lowercase());
+        let folder = msg.filters.folder.as_deref()
+            .map(|folder| folder.trim_matches('/'))
+            .filter(|folder| !folder.is_empty())
+            .map(|folder| format!("{folder}/"));
+        let (taken_after, taken_before) = (msg.filters.taken_after, msg.filters.taken_before);
+        let dated = taken_after.is_some() || taken_before.is_some();
+
+        let mut matching: Vec<(PodId, SortItem)> = self.pods.iter()
+            .filter(|&(&id, pod)| msg.scope.is_none_or(|scope| scope == id) && gallery.as_ref().is_none_or(|gallery| pod.name.contains(gallery)))
+            .flat_map(|(&id, pod)| pod.paths.iter()
+                .filter(|(path, name)| {
+                    if !name.contains(&query) || folder.as_ref().is_some_and(|folder| !path.as_str().starts_with(folder)) {
+                        return false;
+                    }
+                    if !dated {
+                        return true;
+                    }
+                    pod.metadata.get(path).and_then(|metadata| metadata.taken_at).is_some_and(|taken_at| {
+                        taken_after.is_none_or(|after| taken_at >= after) && taken_before.is_none_or(|before| taken_at < before)
+                    })
+                })
+                .map(move |(path, _)| (id, pod.sort_item(path))))
+            .collect();
+        matching.sort_by(|&a, &b| compare_hits(msg.sort, a, b));
+        let total = matching.len() as u64;
+        let limit = msg.limit.map_or(MAX_PAGE_SIZE, |limit| (limit as usize).clamp(1, MAX_PAGE_SIZE));
+        // the image `after` may be gone since, without metadata it sorts last
+        let after = msg.after.as_ref().map(|after| {
+            let item = match self.pods.get(&after.gallery_id) {
+                Some(pod) => pod.sort_item(&after.path),
+                None => SortItem { path: &after.path, metadata: None, rank: None },
+            };
+            (after.gallery_id, item)
+        });
+        let mut rest = matching.into_iter().filter(|&hit| after.is_none_or(|after| compare_hits(msg.sort, hit, after).is_gt()));
+        let hits: Vec<SearchHit> = rest.by_ref().take(limit).map(|(gallery_id, item)| SearchHit { gallery_id, path: item.path.clone() }).collect();
+        let next = match rest.next() {
+            Some(_) => hits.last().cloned(),
+            None => None,
+        };
+        ClientResponse::SearchResults { query: msg.query, hits, next, total }
+    }
+}
+
+impl Actor for SearchIndex {
+    type Args = Self;
+    type Error = Infallible;
+    async fn on_start(state: Self::Args, _actor_ref: ActorRef<Self>) -> Result<Self, Self::Error> {
+        debug!("search index started");
+        Ok(state)
+    }
+}
+
+impl Message<IndexUpdate> for SearchIndex {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: IndexUpdate,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let update = match msg.0 {
+            ClientResponse::NewPod { id, name } => {
+                self.pods.insert(id, IndexedPod::new(&name));
+                return;
+            }
+            ClientResponse::PodGone(id) => {
+                self.pods.remove(&id);
+                return;
+            }
+            update => update,
+        };
+        let Some(id) = update.pod_id() else { return };
+        let Some(pod) = self.pods.get_mut(&id) else {
+            debug!(pod_id = id, "update of a pod never announced");
+            return;
+        };
+        let (version, last_modified) = match update {
+            ClientResponse::PodUpdateName { name, .. } => {
+                pod.name = name.to_lowercase();
+                return;
+            }
+            ClientResponse::PodUpdatePaths { paths, metadata, curated_order, version, last_modified, .. } => {
+                pod.set_paths(paths);
+                pod.metadata = metadata;
+                pod.set_curated_order(curated_order);
+                (version, last_modified)
+            }
+            ClientResponse::PathsAdded { paths, metadata, version, last_modified, .. } => {
+                pod.add_paths(paths);
+                pod.metadata.extend(metadata);
+                (version, last_modified)
+            }
+            ClientResponse::PathsRemoved { paths, version, last_modified, .. } => {
+                pod.remove_paths(paths);
+                (version, last_modified)
+            }
+            _ => return,
+        };
+        pod.version = version;
+        pod.last_modified = last_modified;
+        pod.sorted.clear();
+    }
+}
+
+/// Listings of the paths of one pod, answered right away
+impl Message<Listing> for SearchIndex {
+    type Reply = ClientResponse;
+    async fn handle(
+        &mut self,
+        msg: Listing,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let id = msg.id();
+        let Some(pod) = self.pods.get_mut(&id) else {
+            return ClientResponse::UnknownPod(id);
+        };
+        match msg {
+            Listing::Structure(StructureQuery::Pod(id)) => pod.full_listing(id),
+            Listing::Structure(StructureQuery::Sorted { id, sort }) => pod.structure(id, sort),
+            Listing::Paths { id, prefix, glob, after, limit, sort } => pod.list_paths(id, prefix, glob, after, limit, sort),
+            Listing::Folder { gallery_id, folder, sort, after, limit } => pod.list_folder(gallery_id, folder, sort, after, limit),
+        }
+    }
+}
+
+impl Message<RunSearch> for SearchIndex {
+    type Reply = ();
+    async fn handle(
+        &mut self,
+        msg: RunSearch,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let client = msg.client.clone();
+        let _ = client.tell(self.search(msg)).try_send();
+    }
+}
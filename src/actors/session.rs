@@ -0,0 +1,191 @@
+//! Sessions outliving their socket. Every socket is told a token first, a client reconnecting with
+//! `?resume=<token>` within [`RESUME_WINDOW`] gets its id back and what it missed meanwhile replayed.
+
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+use chrono::Utc;
+use kameo::prelude::*;
+use metrics::counter;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::monitoring;
+use crate::protocols::{ClientResponse, PodId};
+use super::{Disconnect, Hub, ShareScope, websocket::CloseCode};
+
+/// How long a session can be resumed after its socket closed
+pub const RESUME_WINDOW: Duration = Duration::from_secs(60);
+/// Events kept for a detached session, one missing more than that has to resync
+pub const MAX_MISSED_EVENTS: usize = 256;
+
+pub(super) struct Session {
+    secret: String,
+    share_scope: Option<ShareScope>,
+    /// Set while no socket is attached, events are kept for a resume meanwhile
+    detached_at: Option<Instant>,
+    missed: VecDeque<ClientResponse>,
+}
+impl Session {
+    /// `id.secret`, the id in hex like in share tokens
+    fn token(&self, id: PodId) -> String {
+        format!("{id:x}.{}", self.secret)
+    }
+}
+
+fn new_secret() -> String {
+    let mut secret = [0u8; 16];
+    rand::rng().fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+/// Checks whether a session token can be resumed, `None` if not. Nothing changes yet,
+/// the session is only taken over with [`TakeOverSession`] once the new socket is upgraded.
+pub struct ResumeSession {
+    pub token: String,
+}
+
+/// Takes over the session of a token checked by [`ResumeSession`], `None` if it can no longer be resumed.
+/// The token is used up, the resuming socket is told a new one and the old socket is closed.
+pub struct TakeOverSession {
+    pub token: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResumedSession {
+    pub id: PodId,
+    pub share_scope: Option<ShareScope>,
+    /// To take the session over with
+    pub token: String,
+}
+
+impl Hub {
+    /// Tells a freshly subscribed socket its token and replays what it missed if it resumed a session
+    pub(super) fn attach_session(&mut self, id: PodId, share_scope: Option<ShareScope>) {
+        let Some(client) = self.clients.get(&id) else { return };
+        let session = self.sessions.entry(id).or_insert_with(|| Session {
+            secret: new_secret(),
+            share_scope,
+            detached_at: None,
+            missed: VecDeque::new(),
+        });
+        let resumed = session.detached_at.take().is_some();
+        let missed = std::mem::take(&mut session.missed);
+        if resumed {
+            info!(client_id = id, replayed = missed.len(), "session resumed");
+        }
+        let _ = client.addr.tell(ClientResponse::Session { client_id: id, token: session.token(id), resumed }).try_send();
+        for response in missed {
+            let _ = client.addr.tell(response).try_send();
+        }
+    }
+
+    /// Starts keeping events for a session whose socket is gone
+    pub(super) fn detach_session(&mut self, id: PodId) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.detached_at = Some(Instant::now());
+        }
+        self.prune_sessions();
+    }
+
+    fn prune_sessions(&mut self) {
+        self.sessions.retain(|_, session| session.detached_at.is_none_or(|since| since.elapsed() < RESUME_WINDOW));
+    }
+
+    /// Keeps a response for a detached session, `false` if there is none.
+    /// Images are not kept, resumed clients request again what did not arrive.
+    pub(super) fn record_missed(&mut self, id: PodId, response: &ClientResponse) -> bool {
+        let Some(session) = self.sessions.get_mut(&id).filter(|session| session.detached_at.is_some()) else {
+            return false;
+        };
+        if matches!(response, ClientResponse::DeliverImage { .. }) {
+            counter!(monitoring::DROPPED_MESSAGES, "reason" => "session_detached").increment(1);
+        } else if session.missed.len() < MAX_MISSED_EVENTS {
+            session.missed.push_back(response.clone());
+        } else {
+            debug!(client_id = id, "session missed too much, it has to resync");
+            self.sessions.remove(&id);
+        }
+        true
+    }
+
+    /// Keeps a broadcast for every detached session
+    pub(super) fn record_missed_by_all(&mut self, response: &ClientResponse) {
+        let detached: Vec<PodId> = self.sessions.iter()
+            .filter(|(_, session)| session.detached_at.is_some())
+            .map(|(&id, _)| id)
+            .collect();
+        for id in detached {
+            self.record_missed(id, response);
+        }
+    }
+
+    /// Sessions closed on purpose must not come back through a resume
+    pub(super) fn end_sessions(&mut self, ended: impl Fn(PodId, Option<ShareScope>) -> bool) {
+        self.sessions.retain(|&id, session| !ended(id, session.share_scope));
+    }
+
+    /// The session of a token, if it may still be resumed. Sessions of share links need the link to still be valid.
+    fn resumable_session(&self, token: &str) -> Option<ResumedSession> {
+        let (id, secret) = token.split_once('.')?;
+        let id = PodId::from_str_radix(id, 16).ok()?;
+        let session = self.sessions.get(&id)?;
+        // digests have the same length, so comparing them does not tell how much of the secret was right
+        if Sha256::digest(secret) != Sha256::digest(&session.secret) {
+            return None;
+        }
+        if let Some(scope) = session.share_scope {
+            let link = self.share_links.get(&scope.link_id)?;
+            if link.expires_at <= Utc::now() {
+                return None;
+            }
+        }
+        Some(ResumedSession { id, share_scope: session.share_scope, token: token.into() })
+    }
+
+    fn take_over_session(&mut self, token: &str) -> Option<ResumedSession> {
+        let resumed = self.resumable_session(token)?;
+        let id = resumed.id;
+        self.sessions.get_mut(&id)?.secret = new_secret();
+        if let Some(client) = self.clients.get(&id) {
+            // the old socket has not noticed yet that it is gone
+            info!(client_id = id, "session resumed on another socket, closing the old one");
+            let _ = client.addr.tell(Disconnect((CloseCode::Policy, "session resumed elsewhere").into())).try_send();
+            self.unsubscribe_client(id);
+        }
+        Some(resumed)
+    }
+}
+
+impl Message<ResumeSession> for Hub {
+    type Reply = Option<ResumedSession>;
+    async fn handle(
+        &mut self,
+        msg: ResumeSession,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.prune_sessions();
+        let resumable = self.resumable_session(&msg.token);
+        if resumable.is_none() {
+            counter!(monitoring::SESSION_RESUMES, "result" => "refused").increment(1);
+        }
+        debug!(resumable = resumable.is_some(), "session resume requested");
+        resumable
+    }
+}
+
+impl Message<TakeOverSession> for Hub {
+    type Reply = Option<ResumedSession>;
+    async fn handle(
+        &mut self,
+        msg: TakeOverSession,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.prune_sessions();
+        let resumed = self.take_over_session(&msg.token);
+        let result = if resumed.is_some() { "resumed" } else { "refused" };
+        counter!(monitoring::SESSION_RESUMES, "result" => result).increment(1);
+        debug!(result, "session taken over");
+        resumed
+    }
+}
@@ -1,4 +1,4 @@
-use std::convert::{From, Into};
+use std::convert::From;
 use bytes::Bytes;
 use self::CloseCode::*;
 
@@ -66,9 +66,9 @@ pub enum CloseCode {
     Other(u16),
 }
 
-impl Into<u16> for CloseCode {
-    fn into(self) -> u16 {
-        match self {
+impl From<CloseCode> for u16 {
+    fn from(code: CloseCode) -> u16 {
+        match code {
             Normal => 1000,
             Away => 1001,
             Protocol => 1002,
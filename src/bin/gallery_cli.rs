@@ -0,0 +1,203 @@
+use std::{error::Error, path::{Path, PathBuf}, time::Duration};
+
+use clap::{Parser, Subcommand};
+use futures_util::StreamExt;
+use infra::{client::GalleryClient, protocols::{GalleryPath, PodId, SearchFilters, SortDirection, SortKey, SortOrder}};
+
+#[derive(Parser)]
+#[command(about = "Browse and download the galleries shared on an image gallery server")]
+struct Cli {
+    /// Websocket endpoint of the server
+    #[arg(long, default_value = "ws://127.0.0.1:3000/ws")]
+    server: String,
+    /// Seconds to wait for a single response
+    #[arg(long, default_value_t = 30, global = true)]
+    timeout: u64,
+    /// Order of listed images: curated, name, natural, modified, taken or size
+    #[arg(long, default_value = "curated", value_parser = parse_sort_key, global = true)]
+    sort: SortKey,
+    /// Lists the images in reverse order
+    #[arg(long, global = true)]
+    descending: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List all shared galleries
+    Pods,
+    /// List the images of a gallery
+    Structure { pod_id: PodId },
+    /// List the subfolders and images of one folder of a gallery
+    Ls {
+        pod_id: PodId,
+        #[arg(default_value = "")]
+        folder: String,
+    },
+    /// Find images in all galleries whose file name contains the query
+    Search {
+        query: String,
+        /// Only images below this folder
+        #[arg(long)]
+        folder: Option<String>,
+        /// Only galleries whose name contains this
+        #[arg(long)]
+        gallery: Option<String>,
+    },
+    /// Download images of a gallery, all of them if no path is given.
+    /// Files already present are skipped, so an interrupted download can simply be started again.
+    Download {
+        pod_id: PodId,
+        #[arg(value_parser = parse_gallery_path)]
+        paths: Vec<GalleryPath>,
+        /// Folder the gallery is stored in
+        #[arg(short, long, default_value = ".")]
+        out: PathBuf,
+        /// Images requested at the same time
+        #[arg(long, default_value_t = 8)]
+        parallel: usize,
+    },
+}
+
+fn parse_sort_key(key: &str) -> Result<SortKey, String> {
+    match key {
+        "curated" => Ok(SortKey::Curated),
+        "name" => Ok(SortKey::Name),
+        "natural" => Ok(SortKey::Natural),
+        "modified" => Ok(SortKey::Modified),
+        "taken" => Ok(SortKey::Taken),
+        "size" => Ok(SortKey::Size),
+        _ => Err(format!("unknown sort key {key}")),
+    }
+}
+
+fn parse_gallery_path(path: &str) -> Result<GalleryPath, String> {
+    GalleryPath::new(path).map_err(|error| error.to_string())
+}
+
+/// Stores a downloaded image, a partly written file never looks like a finished one
+async fn store_image(target: &Path, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(".part");
+    tokio::fs::write(&partial, bytes).await?;
+    tokio::fs::rename(&partial, target).await?;
+    Ok(())
+}
+
+async fn download(
+    client: &GalleryClient,
+    pod_id: PodId,
+    paths: Vec<GalleryPath>,
+    out: &Path,
+    parallel: usize,
+) -> Result<(), Box<dyn Error>> {
+    let available = client.pod_structure(pod_id, SortOrder::default()).await?;
+    let wanted = if paths.is_empty() {
+        available
+    } else {
+        if let Some(missing) = paths.iter().find(|path| !available.contains(path)) {
+            return Err(format!("pod {pod_id} has no image {missing}").into());
+        }
+        paths
+    };
+
+    let total = wanted.len();
+    let queue: Vec<GalleryPath> = wanted.into_iter().filter(|path| !path.to_path(out).is_some_and(|local| local.exists())).collect();
+    let mut done = total - queue.len();
+    let mut failed = 0;
+    if done > 0 {
+        println!("[{done}/{total}] skipping images already downloaded");
+    }
+
+    let mut downloads = futures_util::stream::iter(queue)
+        .map(|path| async move {
+            let result = match (path.to_path(out), client.fetch_image(pod_id, &path).await) {
+                (None, _) => Err("not a valid file name on this system".into()),
+                (Some(local), Ok(image)) => store_image(&local, &image.bytes).await,
+                (_, Err(error)) => Err(error.into()),
+            };
+            (path, result)
+        })
+        .buffer_unordered(parallel.max(1));
+    while let Some((path, result)) = downloads.next().await {
+        match result {
+            Ok(()) => {
+                done += 1;
+                println!("[{done}/{total}] {path}");
+            }
+            Err(error) => {
+                failed += 1;
+                println!("[{done}/{total}] {path} failed: {error}");
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{failed} of {total} images failed, run again to retry them").into());
+    }
+    println!("downloaded {total} images to {}", out.display());
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    // connection trouble is logged, results go to stdout
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()))
+        .init();
+    let client = GalleryClient::connect(&cli.server).await?.with_timeout(Duration::from_secs(cli.timeout));
+    let direction = if cli.descending { SortDirection::Descending } else { SortDirection::Ascending };
+    let sort = SortOrder { key: cli.sort, direction };
+    match cli.command {
+        Command::Pods => {
+            for pod in client.list_pods().await? {
+                println!("{}\t{}\t{} images\t{}", pod.id, pod.name, pod.paths.len(), pod.last_modified);
+            }
+        }
+        Command::Structure { pod_id } => {
+            for path in client.pod_structure(pod_id, sort).await? {
+                println!("{path}");
+            }
+        }
+        Command::Ls { pod_id, folder } => {
+            let mut after = None;
+            loop {
+                let page = client.list_folder(pod_id, &folder, sort, after, None).await?;
+                for folder in page.folders {
+                    println!("{folder}/");
+                }
+                for image in page.images {
+                    println!("{image}");
+                }
+                after = page.next;
+                if after.is_none() {
+                    break;
+                }
+            }
+        }
+        Command::Search { query, folder, gallery } => {
+            let filters = SearchFilters { folder, gallery, ..SearchFilters::default() };
+            let mut after = None;
+            loop {
+                let page = client.search(&query, &filters, sort, after, None).await?;
+                for hit in page.hits {
+                    println!("{}\t{}", hit.gallery_id, hit.path);
+                }
+                after = page.next;
+                if after.is_none() {
+                    break;
+                }
+            }
+        }
+        Command::Download { pod_id, paths, out, parallel } => {
+            download(&client, pod_id, paths, &out, parallel).await?;
+        }
+    }
+    Ok(())
+}
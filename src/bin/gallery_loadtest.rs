@@ -0,0 +1,220 @@
+use std::{error::Error, net::SocketAddr, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use clap::Parser;
+use futures_util::{StreamExt, future::join_all};
+use infra::{
+    actors::Hub,
+    client::{Event, GalleryClient, PodClient, PodOptions},
+    config::{ConnectionLimits, RateLimit},
+    protocols::{GalleryPath, PathMetadata, PodResponse},
+    webserver::{self, AppState},
+};
+use kameo::actor::ActorRef;
+use rand::Rng;
+
+#[derive(Parser)]
+#[command(about = "Puts an image gallery server under load and reports latencies")]
+struct Cli {
+    /// Websocket endpoint of a running server. Without it a server is started in process,
+    /// only then the Hub mailbox can be watched.
+    #[arg(long)]
+    server: Option<String>,
+    /// Pods sharing generated images
+    #[arg(long, default_value_t = 10)]
+    pods: usize,
+    /// Browsing clients
+    #[arg(long, default_value_t = 100)]
+    clients: usize,
+    #[arg(long, default_value_t = 50)]
+    images_per_pod: usize,
+    /// Bytes per delivered image
+    #[arg(long, default_value_t = 16 * 1024)]
+    image_size: usize,
+    /// Images a client requests at once when it opens a gallery
+    #[arg(long, default_value_t = 10)]
+    burst: usize,
+    /// Milliseconds a client looks at a gallery before opening the next one
+    #[arg(long, default_value_t = 1000)]
+    think_time: u64,
+    /// Seconds the clients keep browsing
+    #[arg(long, default_value_t = 30)]
+    duration: u64,
+    /// Seconds to wait for a single response
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+}
+
+/// Latencies of one kind of request
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+impl Samples {
+    fn record<T, E>(samples: &Mutex<Samples>, started: Instant, result: &Result<T, E>) {
+        let mut samples = samples.lock().expect("samples poisoned");
+        match result {
+            Ok(_) => samples.latencies.push(started.elapsed()),
+            Err(_) => samples.errors += 1,
+        }
+    }
+
+    fn report(&mut self, name: &str, duration: Duration) {
+        self.latencies.sort();
+        let percentile = |p: f64| {
+            let index = ((self.latencies.len() as f64 * p).ceil() as usize).saturating_sub(1);
+            self.latencies.get(index).map_or("-".into(), |latency| format!("{latency:.1?}"))
+        };
+        println!(
+            "{name:<14} {:>8} ok {:>6} failed {:>8.1}/s   p50 {:>9} p90 {:>9} p99 {:>9} max {:>9}",
+            self.latencies.len(),
+            self.errors,
+            self.latencies.len() as f64 / duration.as_secs_f64(),
+            percentile(0.5),
+            percentile(0.9),
+            percentile(0.99),
+            percentile(1.0),
+        );
+    }
+}
+
+/// A PNG header padded with zeros, the server only looks at the first bytes
+fn synthetic_image(size: usize) -> Vec<u8> {
+    let mut image = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13, b'I', b'H', b'D', b'R'];
+    image.resize(size.max(image.len()), 0);
+    image
+}
+
+/// Every socket comes from the same address, so the server started here does not limit them
+async fn start_server() -> Result<(String, ActorRef<Hub>), Box<dyn Error>> {
+    let hub = Hub::default().start();
+    let unlimited = RateLimit { per_second: f64::MAX, burst: f64::MAX };
+    let limits = ConnectionLimits {
+        per_connection: unlimited,
+        per_ip: unlimited,
+        max_message_size: 64 * 1024 * 1024,
+        max_pod_message_size: 64 * 1024 * 1024,
+        max_queued_bytes: usize::MAX,
+        slow_consumer_grace: Duration::MAX,
+    };
+    let app = webserver::router(AppState::new(hub.clone(), 0, limits), "./static/");
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("ws://{}/ws", listener.local_addr()?);
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+    });
+    Ok((url, hub))
+}
+
+async fn run_pod(url: String, index: usize, paths: Vec<GalleryPath>, image: Arc<Vec<u8>>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let pod = PodClient::connect(&url, format!("loadtest pod {index}"), paths, PathMetadata::new(), PodOptions::default()).await?;
+    tokio::spawn(async move {
+        let mut events = pod.events();
+        while let Some(event) = events.next().await {
+            if let Event::Message(PodResponse::RequestImage { client_id, path }) = event {
+                let _ = pod.deliver_image(client_id, path, &image);
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn run_client(client: GalleryClient, cli: Arc<Cli>, deadline: Instant, listing: Arc<Mutex<Samples>>, fetching: Arc<Mutex<Samples>>) {
+    while Instant::now() < deadline {
+        let started = Instant::now();
+        let pods = client.list_pods().await;
+        Samples::record(&listing, started, &pods);
+        let Some(pod) = pods.ok().and_then(|pods| {
+            (!pods.is_empty()).then(|| pods[rand::rng().random_range(0..pods.len())].clone())
+        }) else {
+            tokio::time::sleep(Duration::from_millis(cli.think_time)).await;
+            continue;
+        };
+
+        // like a browser opening a gallery, which requests the visible images at once
+        let fetches = (0..cli.burst.min(pod.paths.len())).map(|_| {
+            let path = pod.paths[rand::rng().random_range(0..pod.paths.len())].clone();
+            let (client, fetching) = (&client, &fetching);
+            async move {
+                let started = Instant::now();
+                let image = client.fetch_image(pod.id, &path).await;
+                Samples::record(fetching, started, &image);
+            }
+        });
+        join_all(fetches).await;
+        tokio::time::sleep(Duration::from_millis(cli.think_time)).await;
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Arc::new(Cli::parse());
+    // connection trouble is logged, results go to stdout
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()))
+        .init();
+    let (url, hub) = match &cli.server {
+        Some(url) => {
+            println!("all sockets come from this host, the per ip rate limit of {url} applies to them together");
+            (url.clone(), None)
+        }
+        None => {
+            let (url, hub) = start_server().await?;
+            (url, Some(hub))
+        }
+    };
+
+    let image = Arc::new(synthetic_image(cli.image_size));
+    let paths: Vec<GalleryPath> = (0..cli.images_per_pod)
+        .map(|i| GalleryPath::new(format!("img-{i:05}.png")).unwrap())
+        .collect();
+    for result in join_all((0..cli.pods).map(|index| run_pod(url.clone(), index, paths.clone(), image.clone()))).await {
+        result.map_err(|error| format!("unable to connect pod: {error}"))?;
+    }
+    let timeout = Duration::from_secs(cli.timeout);
+    let clients = join_all((0..cli.clients).map(|_| GalleryClient::connect(&url))).await
+        .into_iter()
+        .map(|client| client.map(|client| client.with_timeout(timeout)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| format!("unable to connect client: {error}"))?;
+    println!("{} pods and {} clients connected to {url}", cli.pods, cli.clients);
+
+    let depths = Arc::new(Mutex::new(vec![]));
+    if let Some(hub) = hub.clone() {
+        let depths = depths.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_millis(50));
+            loop {
+                ticker.tick().await;
+                depths.lock().expect("depths poisoned").push(Hub::mailbox_depth(&hub));
+            }
+        });
+    }
+
+    let listing = Arc::new(Mutex::new(Samples::default()));
+    let fetching = Arc::new(Mutex::new(Samples::default()));
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(cli.duration);
+    join_all(clients.into_iter().map(|client| {
+        tokio::spawn(run_client(client, cli.clone(), deadline, listing.clone(), fetching.clone()))
+    })).await;
+    let elapsed = started.elapsed();
+
+    println!("after {elapsed:.1?}:");
+    listing.lock().expect("samples poisoned").report("ListAllPods", elapsed);
+    fetching.lock().expect("samples poisoned").report("RequestImage", elapsed);
+    if hub.is_some() {
+        let mut depths = depths.lock().expect("depths poisoned").clone();
+        depths.sort();
+        let percentile = |p: f64| depths.get(((depths.len() as f64 * p).ceil() as usize).saturating_sub(1)).copied().unwrap_or(0);
+        println!(
+            "Hub mailbox    p50 {} p99 {} max {} of {}",
+            percentile(0.5),
+            percentile(0.99),
+            percentile(1.0),
+            infra::actors::HUB_MAILBOX_CAPACITY,
+        );
+    }
+    Ok(())
+}
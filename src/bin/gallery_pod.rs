@@ -0,0 +1,99 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use futures_util::StreamExt;
+use infra::{
+    client::{Event, PodClient, PodOptions, scan_images},
+    protocols::{GalleryPath, PodResponse},
+};
+
+const USAGE: &str = "usage: gallery_pod [--share-location] <directory> [name] [server url, default ws://127.0.0.1:3000/ws]";
+/// Announces the EXIF positions of the images, left out otherwise
+const SHARE_LOCATION: &str = "--share-location";
+/// Lists paths of the directory one per line in the order clients should see them
+const ORDER_FILE: &str = ".gallery-order";
+
+/// `paths` in the order of the [`ORDER_FILE`] of `root` if there is one, the images it leaves out follow by name
+fn curated_order(root: &Path, paths: &[GalleryPath]) -> std::io::Result<Option<Vec<GalleryPath>>> {
+    let order = match std::fs::read_to_string(root.join(ORDER_FILE)) {
+        Ok(order) => order,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let available: HashSet<&GalleryPath> = paths.iter().collect();
+    let mut curated: Vec<GalleryPath> = order.lines()
+        .filter_map(|line| GalleryPath::new(line.trim()).ok())
+        .filter(|path| available.contains(path))
+        .collect();
+    let listed: HashSet<GalleryPath> = curated.iter().cloned().collect();
+    let mut rest: Vec<GalleryPath> = paths.iter().filter(|path| !listed.contains(*path)).cloned().collect();
+    rest.sort();
+    curated.extend(rest);
+    Ok(Some(curated))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // skipped files are logged
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+    let (flags, args): (Vec<String>, Vec<String>) = std::env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let share_location = match flags.as_slice() {
+        [] => false,
+        [flag] if flag == SHARE_LOCATION => true,
+        _ => return Err(USAGE.into()),
+    };
+    let mut args = args.into_iter();
+    let root = PathBuf::from(args.next().ok_or(USAGE)?).canonicalize()?;
+    let name = args.next().unwrap_or_else(|| {
+        root.file_name().map_or("gallery".into(), |name| name.to_string_lossy().into_owned())
+    });
+    let url = args.next().unwrap_or_else(|| "ws://127.0.0.1:3000/ws".into());
+
+    let (paths, metadata) = scan_images(&root)?;
+    let (paths, curated) = match curated_order(&root, &paths)? {
+        Some(curated) => (curated, true),
+        None => (paths, false),
+    };
+    println!("sharing {} images of {} as {}{}", paths.len(), root.display(), name, if curated { " in curated order" } else { "" });
+    // only announced files are ever read
+    let shared: HashSet<GalleryPath> = paths.iter().cloned().collect();
+
+    let pod = PodClient::connect(&url, name, paths, metadata, PodOptions { curated, share_location }).await?;
+    println!("connected to {url}");
+    let mut events = pod.events();
+    while let Some(event) = events.next().await {
+        match event {
+            Event::Reconnected => println!("reconnected to {url}"),
+            Event::Message(PodResponse::Registered { global_id } | PodResponse::AlreadyRegistered { global_id }) => {
+                println!("registered as pod {global_id}");
+            }
+            Event::Message(PodResponse::RequestImage { client_id, path }) => {
+                if !shared.contains(&path) {
+                    println!("client {client_id} requested unknown image {path}");
+                    continue;
+                }
+                let Some(local) = path.to_path(&root) else {
+                    println!("client {client_id} requested {path}, which is no file name here");
+                    continue;
+                };
+                match tokio::fs::read(local).await {
+                    Ok(bytes) => if let Err(error) = pod.deliver_image(client_id, path.clone(), &bytes) {
+                        println!("unable to deliver {path}: {error}");
+                    },
+                    Err(error) => println!("unable to read {path}: {error}"),
+                }
+            }
+            Event::Message(PodResponse::ImageRejected { path, reason, .. }) => {
+                println!("server rejected {path}: {reason}");
+            }
+            Event::Message(PodResponse::PathsRejected { paths }) => {
+                for rejected in paths {
+                    println!("server left out {}: {}", rejected.path, rejected.reason);
+                }
+            }
+            Event::Message(_) => {}
+        }
+    }
+    Ok(())
+}
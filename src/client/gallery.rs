@@ -0,0 +1,189 @@
+use std::{sync::Arc, time::Duration};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::stream::BoxStream;
+
+use crate::image_type::ImageType;
+use crate::protocols::{ClientRequest, ClientRequestAsync, ClientResponse, GalleryPath, JsonProtocol, PathMetadata, PodDescription, PodId, PodSummary, SearchFilters, SearchHit, SortOrder, StructureQuery};
+use super::{ClientError, Connection, DEFAULT_TIMEOUT, Event};
+
+/// An image as delivered by a pod
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub image_type: ImageType,
+    pub bytes: Vec<u8>,
+}
+
+/// Which paths [`GalleryClient::pod_paths`] lists, see [`ClientRequest::ListPodPaths`]
+#[derive(Debug, Clone, Default)]
+pub struct PathQuery {
+    pub prefix: Option<String>,
+    pub glob: Option<String>,
+    pub limit: Option<u32>,
+    pub sort: SortOrder,
+}
+
+/// One page of [`GalleryClient::pod_paths`]
+#[derive(Debug, Clone)]
+pub struct PathsPage {
+    pub paths: Vec<GalleryPath>,
+    pub metadata: PathMetadata,
+    /// Continues the listing when passed as `after`
+    pub next: Option<GalleryPath>,
+    pub total: u64,
+    pub version: u64,
+}
+
+/// One page of [`GalleryClient::list_folder`]
+#[derive(Debug, Clone)]
+pub struct FolderPage {
+    /// Only on the first page
+    pub folders: Vec<String>,
+    pub images: Vec<GalleryPath>,
+    pub metadata: PathMetadata,
+    /// Continues the listing when passed as `after`
+    pub next: Option<GalleryPath>,
+    pub total: u64,
+    pub version: u64,
+}
+
+/// One page of [`GalleryClient::search`]
+#[derive(Debug, Clone)]
+pub struct SearchPage {
+    pub hits: Vec<SearchHit>,
+    /// Continues the search when passed as `after`
+    pub next: Option<SearchHit>,
+    pub total: u64,
+}
+
+/// Browses the galleries of a server like `gallery.js` does
+#[derive(Clone)]
+pub struct GalleryClient {
+    connection: Connection<ClientResponse>,
+    timeout: Duration,
+}
+impl GalleryClient {
+    /// Connects to the websocket endpoint of a server, e.g. `ws://127.0.0.1:3000/ws`
+    pub async fn connect(url: &str) -> Result<Self, ClientError> {
+        let connection = Connection::open(url, Arc::new(Vec::new), |message| match message {
+            JsonProtocol::ClientResponse(response) => Some(response),
+            _ => None,
+        }).await?;
+        Ok(GalleryClient { connection, timeout: DEFAULT_TIMEOUT })
+    }
+
+    /// How long requests wait for their response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Everything the server tells this client, including pods coming, changing and going
+    pub fn events(&self) -> BoxStream<'static, Event<ClientResponse>> {
+        self.connection.stream()
+    }
+
+    pub async fn list_pods(&self) -> Result<Vec<PodDescription>, ClientError> {
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::ClientRequest(ClientRequest::ListAllPods),
+            |response| match response {
+                ClientResponse::Pods(pods) => Some(pods),
+                _ => None,
+            },
+        ).await
+    }
+
+    /// Pods with their path counts instead of the paths
+    pub async fn list_pod_summaries(&self) -> Result<Vec<PodSummary>, ClientError> {
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::ClientRequest(ClientRequest::ListPodSummaries),
+            |response| match response {
+                ClientResponse::PodSummaries(pods) => Some(pods),
+                _ => None,
+            },
+        ).await
+    }
+
+    /// The page of matching paths of a pod following `after`
+    pub async fn pod_paths(&self, pod_id: PodId, query: &PathQuery, after: Option<GalleryPath>) -> Result<PathsPage, ClientError> {
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::ClientRequest(ClientRequest::ListPodPaths {
+                id: pod_id,
+                prefix: query.prefix.clone(),
+                glob: query.glob.clone(),
+                after: after.clone(),
+                limit: query.limit,
+                sort: query.sort,
+            }),
+            |response| match response {
+                ClientResponse::PodPaths { id, paths, next, total, version, metadata } if id == pod_id => Some(Ok(PathsPage { paths, metadata, next, total, version })),
+                ClientResponse::UnknownPod(id) if id == pod_id => Some(Err(ClientError::UnknownPod(id))),
+                _ => None,
+            },
+        ).await?
+    }
+
+    /// Subfolder names and the page of images directly inside `folder` of a pod following `after`, `""` is the root
+    pub async fn list_folder(&self, pod_id: PodId, folder: &str, sort: SortOrder, after: Option<GalleryPath>, limit: Option<u32>) -> Result<FolderPage, ClientError> {
+        let folder = folder.trim_matches('/');
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: pod_id, folder: folder.to_string(), sort, after: after.clone(), limit }),
+            |response| match response {
+                ClientResponse::FolderListing { gallery_id, folder: listed, folders, images, sort: sorted, next, total, version, metadata }
+                    if gallery_id == pod_id && listed == folder && sorted == sort => Some(Ok(FolderPage { folders, images, metadata, next, total, version })),
+                ClientResponse::UnknownPod(id) if id == pod_id => Some(Err(ClientError::UnknownPod(id))),
+                _ => None,
+            },
+        ).await?
+    }
+
+    /// The page of images of all galleries matching `query` and `filters` following `after`
+    pub async fn search(&self, query: &str, filters: &SearchFilters, sort: SortOrder, after: Option<SearchHit>, limit: Option<u32>) -> Result<SearchPage, ClientError> {
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::ClientRequest(ClientRequest::Search { query: query.to_string(), filters: filters.clone(), sort, after: after.clone(), limit }),
+            |response| match response {
+                ClientResponse::SearchResults { query: searched, hits, next, total } if searched == query => Some(SearchPage { hits, next, total }),
+                _ => None,
+            },
+        ).await
+    }
+
+    /// Image paths of a pod in `sort` order
+    pub async fn pod_structure(&self, pod_id: PodId, sort: SortOrder) -> Result<Vec<GalleryPath>, ClientError> {
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(StructureQuery::Sorted { id: pod_id, sort })),
+            |response| match response {
+                ClientResponse::PodStructure { id, sort: listed, paths, .. } if id == pod_id && listed == sort => Some(Ok(paths)),
+                ClientResponse::UnknownPod(id) if id == pod_id => Some(Err(ClientError::UnknownPod(id))),
+                _ => None,
+            },
+        ).await?
+    }
+
+    pub async fn fetch_image(&self, pod_id: PodId, path: &GalleryPath) -> Result<Image, ClientError> {
+        let blob = self.connection.request(
+            self.timeout,
+            || JsonProtocol::ClientRequestAsync(ClientRequestAsync::RequestImage {
+                gallery_id: pod_id,
+                path: path.clone(),
+                client_id: 0,
+            }),
+            |response| match response {
+                ClientResponse::DeliverImage { gallery_id, path: delivered, blob, .. } if gallery_id == pod_id && delivered == *path => Some(Ok(blob)),
+                ClientResponse::ImageRejected { gallery_id, path: rejected, reason } if gallery_id == pod_id && rejected == *path => Some(Err(ClientError::Rejected(reason))),
+                ClientResponse::UnknownPod(id) if id == pod_id => Some(Err(ClientError::UnknownPod(id))),
+                ClientResponse::PodGone(id) if id == pod_id => Some(Err(ClientError::PodGone(id))),
+                _ => None,
+            },
+        ).await??;
+        let (image_type, payload) = ImageType::sniff_data_url(&blob).map_err(|_| ClientError::InvalidImage)?;
+        let bytes = STANDARD.decode(payload).map_err(|_| ClientError::InvalidImage)?;
+        Ok(Image { image_type, bytes })
+    }
+}
@@ -0,0 +1,231 @@
+//! Async clients for the websocket protocol, the Rust counterpart of `gallery.js` and `self_host.js`.
+//! Both keep reconnecting in the background until they are dropped.
+
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use futures_util::{SinkExt, StreamExt, stream::BoxStream};
+use tokio::{sync::{broadcast, mpsc}, time::Instant};
+use tokio_tungstenite::{connect_async, tungstenite::{self, Message}};
+
+use crate::protocols::{ClientRequest, ClientResponse, JsonProtocol, PodId};
+
+mod gallery;
+mod pod;
+mod scan;
+
+pub use gallery::{FolderPage, GalleryClient, Image, PathQuery, PathsPage, SearchPage};
+pub use pod::{PodClient, PodOptions};
+pub use scan::scan_images;
+
+/// How long a request waits for its response unless configured otherwise
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// A server silent for longer than the timeout is given up on, like the server does with its sockets
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(45);
+/// Events kept for slow subscribers before they miss some
+const EVENT_CAPACITY: usize = 1024;
+
+/// What a client observes on its connection
+#[derive(Debug, Clone)]
+pub enum Event<T> {
+    /// Connected again after the connection was lost, state fetched earlier may be outdated
+    Reconnected,
+    Message(T),
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The first connection attempt failed
+    Connect(tungstenite::Error),
+    /// The client was dropped or its connection task ended
+    Closed,
+    Timeout,
+    UnknownPod(PodId),
+    PodGone(PodId),
+    /// The server refused a delivered image or a share link request
+    Rejected(String),
+    /// The delivered image could not be decoded
+    InvalidImage,
+}
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ClientError::Connect(error) => write!(f, "unable to connect: {error}"),
+            ClientError::Closed => f.write_str("connection closed"),
+            ClientError::Timeout => f.write_str("no response in time"),
+            ClientError::UnknownPod(id) => write!(f, "unknown pod {id}"),
+            ClientError::PodGone(id) => write!(f, "pod {id} is gone"),
+            ClientError::Rejected(reason) => write!(f, "rejected: {reason}"),
+            ClientError::InvalidImage => f.write_str("invalid image data"),
+        }
+    }
+}
+impl std::error::Error for ClientError {}
+
+/// Messages to send once a connection is up, before anything else
+type Greeting = Arc<dyn Fn() -> Vec<JsonProtocol> + Send + Sync>;
+
+/// Handle of the background task owning the websocket
+#[derive(Clone)]
+struct Connection<T> {
+    outbound: mpsc::UnboundedSender<JsonProtocol>,
+    events: broadcast::Sender<Event<T>>,
+    /// Subscribed before connecting, so the first stream sees the answers to the greeting
+    first_events: Arc<Mutex<Option<broadcast::Receiver<Event<T>>>>>,
+}
+impl<T: Clone + Send + 'static> Connection<T> {
+    /// Connects once, so a wrong url is reported right away, then keeps the connection alive.
+    /// `pick` selects the messages this kind of client cares about.
+    async fn open(url: &str, greeting: Greeting, pick: fn(JsonProtocol) -> Option<T>) -> Result<Self, ClientError> {
+        let (socket, _) = connect_async(url).await.map_err(ClientError::Connect)?;
+        let (outbound, outbound_rx) = mpsc::unbounded_channel();
+        let (events, first_events) = broadcast::channel(EVENT_CAPACITY);
+        tokio::spawn(run(url.to_owned(), socket, greeting, pick, outbound_rx, events.clone()));
+        Ok(Connection { outbound, events, first_events: Arc::new(Mutex::new(Some(first_events))) })
+    }
+
+    fn send(&self, message: JsonProtocol) -> Result<(), ClientError> {
+        self.outbound.send(message).map_err(|_| ClientError::Closed)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Event<T>> {
+        self.events.subscribe()
+    }
+
+    fn stream(&self) -> BoxStream<'static, Event<T>> {
+        let events = self.first_events.lock().expect("first_events poisoned").take()
+            .unwrap_or_else(|| self.subscribe());
+        futures_util::stream::unfold(events, |mut events| async move {
+            let event = next_event(&mut events).await.ok()?;
+            Some((event, events))
+        }).boxed()
+    }
+
+    /// Sends a request and waits for the first message `matches` accepts,
+    /// sending the request again whenever the connection was lost meanwhile
+    async fn request<R>(
+        &self,
+        timeout: Duration,
+        request: impl Fn() -> JsonProtocol,
+        mut matches: impl FnMut(T) -> Option<R>,
+    ) -> Result<R, ClientError> {
+        let mut events = self.subscribe();
+        self.send(request())?;
+        tokio::time::timeout(timeout, async {
+            loop {
+                match next_event(&mut events).await? {
+                    Event::Reconnected => self.send(request())?,
+                    Event::Message(message) => if let Some(response) = matches(message) {
+                        return Ok(response);
+                    }
+                }
+            }
+        }).await.map_err(|_| ClientError::Timeout)?
+    }
+}
+
+async fn next_event<T: Clone>(events: &mut broadcast::Receiver<Event<T>>) -> Result<Event<T>, ClientError> {
+    loop {
+        match events.recv().await {
+            Ok(event) => return Ok(event),
+            Err(broadcast::error::RecvError::Lagged(missed)) => tracing::warn!(missed, "client fell behind, events were dropped"),
+            Err(broadcast::error::RecvError::Closed) => return Err(ClientError::Closed),
+        }
+    }
+}
+
+type Socket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn reconnect(url: &str) -> Socket {
+    let mut delay = MIN_RECONNECT_DELAY;
+    loop {
+        tokio::time::sleep(delay).await;
+        match connect_async(url).await {
+            Ok((socket, _)) => return socket,
+            Err(error) => tracing::debug!(%url, %error, "reconnecting failed"),
+        }
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+fn frame(message: &JsonProtocol) -> Message {
+    Message::Text(serde_json::to_string(message).expect("unable to serialize JsonProtocol").into())
+}
+
+async fn run<T: Clone>(
+    url: String,
+    mut socket: Socket,
+    greeting: Greeting,
+    pick: fn(JsonProtocol) -> Option<T>,
+    mut outbound: mpsc::UnboundedReceiver<JsonProtocol>,
+    events: broadcast::Sender<Event<T>>,
+) {
+    loop {
+        let (mut sender, mut receiver) = socket.split();
+        'session: {
+            for message in greeting() {
+                if sender.send(frame(&message)).await.is_err() {
+                    break 'session;
+                }
+            }
+            let mut last_seen = Instant::now();
+            let mut heartbeats = tokio::time::interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = heartbeats.tick() => {
+                        if last_seen.elapsed() > HEARTBEAT_TIMEOUT {
+                            tracing::warn!(%url, silent_for = ?last_seen.elapsed(), "server stayed silent, reconnecting");
+                            break;
+                        }
+                        if sender.send(frame(&JsonProtocol::ClientRequest(ClientRequest::Heartbeat))).await.is_err() {
+                            break;
+                        }
+                    }
+                    message = outbound.recv() => {
+                        // every handle is gone
+                        let Some(message) = message else { return };
+                        if sender.send(frame(&message)).await.is_err() {
+                            break;
+                        }
+                    }
+                    frame = receiver.next() => match frame.inspect(|_| last_seen = Instant::now()) {
+                        Some(Ok(Message::Text(text))) => {
+                            // both only concern the connection, sessions are not resumed here
+                            let message = serde_json::from_str(&text).ok()
+                                .filter(|message| !matches!(message, JsonProtocol::ClientResponse(ClientResponse::Heartbeat | ClientResponse::Session { .. })));
+                            if let Some(message) = message.and_then(pick) {
+                                let _ = events.send(Event::Message(message));
+                            }
+                        }
+                        Some(Ok(Message::Close(frame))) => {
+                            tracing::warn!(%url, ?frame, "server closed the connection");
+                            break;
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(error)) => {
+                            tracing::warn!(%url, %error, "connection lost");
+                            break;
+                        }
+                        None => break,
+                    },
+                }
+            }
+        }
+
+        let reconnecting = reconnect(&url);
+        tokio::pin!(reconnecting);
+        socket = loop {
+            tokio::select! {
+                socket = &mut reconnecting => break socket,
+                // whatever is sent meanwhile belongs to the old session, requests are repeated on `Reconnected`
+                message = outbound.recv() => if message.is_none() {
+                    return;
+                },
+            }
+        };
+        let _ = events.send(Event::Reconnected);
+    }
+}
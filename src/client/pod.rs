@@ -0,0 +1,146 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+
+use crate::image_type::ImageType;
+use crate::protocols::{GalleryPath, JsonProtocol, PathMetadata, PodId, PodRequest, PodResponse};
+use super::{ClientError, Connection, DEFAULT_TIMEOUT, Event};
+
+/// What the server learns again after every reconnect
+struct Announcement {
+    name: String,
+    paths: Vec<GalleryPath>,
+    metadata: PathMetadata,
+    /// Whether `paths` are in the order clients should see them
+    curated: bool,
+}
+
+/// `metadata` as clients may see it, without positions unless `share_location`
+fn published(mut metadata: PathMetadata, share_location: bool) -> PathMetadata {
+    if !share_location {
+        metadata.values_mut().for_each(|image| image.gps = None);
+    }
+    metadata
+}
+
+/// How a [`PodClient`] publishes its gallery
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PodOptions {
+    /// Unless set clients see the paths in whatever order they ask for, by name by default
+    pub curated: bool,
+    /// Whether clients, share sessions included, learn where images were taken.
+    /// The position is left out of the metadata unless set.
+    pub share_location: bool,
+}
+
+/// Publishes a gallery like `self_host.js` does.
+/// After a reconnect the pod registers again under a new id and announces its current paths.
+#[derive(Clone)]
+pub struct PodClient {
+    connection: Connection<PodResponse>,
+    announcement: Arc<Mutex<Announcement>>,
+    timeout: Duration,
+    share_location: bool,
+}
+impl PodClient {
+    /// `metadata` may leave out images or be empty, clients then only know the paths of those.
+    pub async fn connect(url: &str, name: String, paths: Vec<GalleryPath>, metadata: PathMetadata, options: PodOptions) -> Result<Self, ClientError> {
+        let metadata = published(metadata, options.share_location);
+        let announcement = Arc::new(Mutex::new(Announcement { name, paths, metadata, curated: options.curated }));
+        let greeting = {
+            let announcement = announcement.clone();
+            Arc::new(move || {
+                let announcement = announcement.lock().expect("announcement poisoned");
+                vec![
+                    JsonProtocol::PodRequest(PodRequest::RegisterSelf { proposed_id: None, name: announcement.name.clone() }),
+                    JsonProtocol::PodRequest(PodRequest::UpdatePaths {
+                        paths: announcement.paths.clone(),
+                        replace_images: true,
+                        curated: announcement.curated,
+                        metadata: announcement.metadata.clone(),
+                    }),
+                ]
+            })
+        };
+        let connection = Connection::open(url, greeting, |message| match message {
+            JsonProtocol::PodResponse(response) => Some(response),
+            _ => None,
+        }).await?;
+        Ok(PodClient { connection, announcement, timeout: DEFAULT_TIMEOUT, share_location: options.share_location })
+    }
+
+    /// How long requests wait for their response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Registrations, image requests of clients and answers to share link requests
+    pub fn events(&self) -> BoxStream<'static, Event<PodResponse>> {
+        self.connection.stream()
+    }
+
+    pub fn update_title(&self, name: String) -> Result<(), ClientError> {
+        self.announcement.lock().expect("announcement poisoned").name = name.clone();
+        self.connection.send(JsonProtocol::PodRequest(PodRequest::UpdateTitle { name }))
+    }
+
+    pub fn update_paths(&self, paths: Vec<GalleryPath>, metadata: PathMetadata, replace_images: bool, curated: bool) -> Result<(), ClientError> {
+        let metadata = published(metadata, self.share_location);
+        let mut announcement = self.announcement.lock().expect("announcement poisoned");
+        announcement.paths = paths.clone();
+        announcement.metadata = metadata.clone();
+        announcement.curated = curated;
+        self.connection.send(JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths, replace_images, curated, metadata }))
+    }
+
+    /// Publishes more paths without sending the ones already published
+    pub fn add_paths(&self, paths: Vec<GalleryPath>, metadata: PathMetadata) -> Result<(), ClientError> {
+        let metadata = published(metadata, self.share_location);
+        let mut announcement = self.announcement.lock().expect("announcement poisoned");
+        announcement.paths.extend(paths.iter().cloned());
+        announcement.metadata.extend(metadata.clone());
+        self.connection.send(JsonProtocol::PodRequest(PodRequest::PathsAdded { paths, metadata }))
+    }
+
+    pub fn remove_paths(&self, paths: Vec<GalleryPath>) -> Result<(), ClientError> {
+        let mut announcement = self.announcement.lock().expect("announcement poisoned");
+        announcement.paths.retain(|path| !paths.contains(path));
+        announcement.metadata.retain(|path, _| !paths.contains(path));
+        self.connection.send(JsonProtocol::PodRequest(PodRequest::PathsRemoved { paths }))
+    }
+
+    /// Answers a `PodResponse::RequestImage`, the type is taken from the content
+    pub fn deliver_image(&self, client_id: PodId, path: GalleryPath, bytes: &[u8]) -> Result<(), ClientError> {
+        let image_type = ImageType::sniff(bytes).ok_or(ClientError::InvalidImage)?;
+        let blob = image_type.data_url(&STANDARD.encode(bytes));
+        self.connection.send(JsonProtocol::PodRequest(PodRequest::DeliverImage { client_id, path, blob }))
+    }
+
+    /// Returns the token of a new share link for this gallery
+    pub async fn create_share_link(&self, expires_at: DateTime<Utc>, max_views: Option<u32>) -> Result<String, ClientError> {
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::PodRequest(PodRequest::CreateShareLink { expires_at, max_views }),
+            |response| match response {
+                PodResponse::ShareLink { token, expires_at: expires, max_views: views } if expires == expires_at && views == max_views => Some(Ok(token)),
+                PodResponse::ShareLinkDenied { reason } => Some(Err(ClientError::Rejected(reason))),
+                _ => None,
+            },
+        ).await?
+    }
+
+    pub async fn revoke_share_link(&self, token: String) -> Result<(), ClientError> {
+        self.connection.request(
+            self.timeout,
+            || JsonProtocol::PodRequest(PodRequest::RevokeShareLink { token: token.clone() }),
+            |response| match response {
+                PodResponse::ShareLinkRevoked { token: revoked } if revoked == token => Some(Ok(())),
+                PodResponse::ShareLinkDenied { reason } => Some(Err(ClientError::Rejected(reason))),
+                _ => None,
+            },
+        ).await?
+    }
+}
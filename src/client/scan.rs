@@ -0,0 +1,61 @@
+use std::{io::Read, path::Path};
+
+use chrono::{DateTime, Utc};
+
+use crate::image_metadata::{METADATA_HEAD_LENGTH, read_metadata};
+use crate::image_type::ImageType;
+use crate::protocols::{GalleryPath, PathMetadata};
+
+/// Finds every image below `root` whose path the server accepts, along with what can be told about it.
+/// Symbolic links are not followed, they could loop or lead out of `root`.
+/// Names that are not UTF-8 have no gallery path and are skipped.
+pub fn scan_images(root: &Path) -> std::io::Result<(Vec<GalleryPath>, PathMetadata)> {
+    let mut images = vec![];
+    let mut metadata = PathMetadata::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                tracing::debug!(path = %path.display(), "skipping symbolic link");
+                continue;
+            }
+            if file_type.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let relative = path.strip_prefix(root).expect("scanned path outside of root");
+            let Some(name) = relative.components()
+                .map(|component| component.as_os_str().to_str())
+                .collect::<Option<Vec<_>>>()
+            else {
+                tracing::warn!(path = %path.display(), "skipping file whose name is not UTF-8");
+                continue;
+            };
+            let mut head = vec![];
+            let file_metadata = std::fs::File::open(&path).and_then(|file| {
+                let file_metadata = file.metadata()?;
+                file.take(METADATA_HEAD_LENGTH as u64).read_to_end(&mut head)?;
+                Ok(file_metadata)
+            });
+            let Ok(file_metadata) = file_metadata else {
+                continue;
+            };
+            if ImageType::sniff(&head).is_none() {
+                continue;
+            }
+            match GalleryPath::new(name.join("/")) {
+                Ok(gallery_path) => {
+                    let mut image_metadata = read_metadata(&head, file_metadata.len());
+                    image_metadata.modified = file_metadata.modified().ok().map(DateTime::<Utc>::from);
+                    metadata.insert(gallery_path.clone(), image_metadata);
+                    images.push(gallery_path);
+                }
+                Err(error) => tracing::warn!(path = %path.display(), %error, "skipping file"),
+            }
+        }
+    }
+    Ok((images, metadata))
+}
@@ -0,0 +1,144 @@
+use std::{collections::HashMap, error::Error, time::Duration};
+
+use futures_util::StreamExt;
+use kameo::{actor::RemoteActorRef, prelude::*, remote};
+use libp2p::{Multiaddr, PeerId, allow_block_list, core::ConnectedPoint, identity::Keypair, noise, swarm::{NetworkBehaviour, SwarmEvent}, tcp, yamux};
+use tracing::{info, warn};
+
+use crate::actors::{ClusterJoined, ClusterTick, Hub, PeerFound};
+use crate::protocols::PodId;
+
+/// Name every Hub registers under in the swarm
+pub const HUB_NAME: &str = "gallery-hub";
+
+/// Ids carry the node that issued them in the bits above this,
+/// small enough to stay exact in javascript numbers
+pub const NODE_ID_SHIFT: u32 = 40;
+pub const MAX_NODE_ID: NodeId = (1 << (53 - NODE_ID_SHIFT)) - 1;
+
+pub type NodeId = u16;
+
+/// First client or pod id handed out by `node_id`
+pub fn first_id_of_node(node_id: NodeId) -> PodId {
+    assert!(node_id <= MAX_NODE_ID, "node id {node_id} is bigger than {MAX_NODE_ID}");
+    (node_id as PodId) << NODE_ID_SHIFT
+}
+
+/// Node a client or pod is connected to
+pub fn node_of(id: PodId) -> NodeId {
+    (id >> NODE_ID_SHIFT) as NodeId
+}
+
+/// How this process takes part in the cluster
+#[derive(Clone, Debug)]
+pub struct ClusterConfig {
+    pub listen: Multiaddr,
+    pub peers: Vec<Multiaddr>,
+    pub sync_interval: Duration,
+    /// Identity of this node, its peer id is what the other nodes allow
+    pub keypair: Keypair,
+    /// The only nodes connections are accepted from or kept to
+    pub allowed_peers: Vec<PeerId>,
+}
+
+/// Ed25519 identity of a node from the 32 bytes of its secret key, hex encoded
+pub fn keypair_from_hex(secret_key: &str) -> Option<Keypair> {
+    let mut bytes = hex::decode(secret_key.trim()).ok()?;
+    Keypair::ed25519_from_bytes(&mut bytes).ok()
+}
+
+#[derive(NetworkBehaviour)]
+struct ClusterBehaviour {
+    /// Denies every connection of peers not allowed, before any message is exchanged
+    allowed: allow_block_list::Behaviour<allow_block_list::AllowedPeers>,
+    kameo: remote::Behaviour,
+}
+
+/// Starts the libp2p swarm, registers the Hub under [`HUB_NAME`]
+/// and keeps looking for the Hubs of the other nodes.
+pub async fn join(cluster_config: ClusterConfig, hub: ActorRef<Hub>) -> Result<(), Box<dyn Error>> {
+    let mut swarm = libp2p::SwarmBuilder::with_existing_identity(cluster_config.keypair.clone())
+        .with_tokio()
+        .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+        .with_behaviour(|key| {
+            let mut allowed = allow_block_list::Behaviour::default();
+            for peer_id in &cluster_config.allowed_peers {
+                allowed.allow_peer(*peer_id);
+            }
+            ClusterBehaviour {
+                allowed,
+                kameo: remote::Behaviour::new(key.public().to_peer_id(), remote::messaging::Config::default()),
+            }
+        })?
+        .with_swarm_config(|swarm_config| swarm_config.with_idle_connection_timeout(Duration::from_secs(60 * 60 * 24)))
+        .build();
+    swarm.behaviour().kameo.try_init_global()?;
+    swarm.listen_on(cluster_config.listen.clone())?;
+    let local_peer_id = *swarm.local_peer_id();
+    info!(peer_id = %local_peer_id, listen = %cluster_config.listen, allowed_peers = cluster_config.allowed_peers.len(), "cluster swarm listening");
+
+    let sync_interval = cluster_config.sync_interval;
+    tokio::spawn(async move {
+        let mut connected: HashMap<Multiaddr, usize> = HashMap::new();
+        let mut redial = tokio::time::interval(sync_interval);
+        loop {
+            tokio::select! {
+                _ = redial.tick() => {
+                    for peer in cluster_config.peers.iter().filter(|peer| !connected.contains_key(*peer)) {
+                        if let Err(error) = swarm.dial(peer.clone()) {
+                            warn!(%peer, %error, "unable to dial cluster peer");
+                        }
+                    }
+                }
+                event = swarm.select_next_some() => match event {
+                    SwarmEvent::ConnectionEstablished { peer_id, endpoint: ConnectedPoint::Dialer { address, .. }, .. } => {
+                        info!(%peer_id, %address, "connected to cluster peer");
+                        // kademlia only learns addresses it is told about
+                        swarm.add_peer_address(peer_id, address.clone());
+                        *connected.entry(address).or_default() += 1;
+                    }
+                    SwarmEvent::IncomingConnectionError { send_back_addr, error, .. } => {
+                        warn!(address = %send_back_addr, %error, "refused cluster connection");
+                    }
+                    SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                        warn!(?peer_id, %error, "unable to connect to cluster peer");
+                    }
+                    SwarmEvent::ConnectionClosed { peer_id, endpoint: ConnectedPoint::Dialer { address, .. }, .. } => {
+                        info!(%peer_id, %address, "lost cluster peer");
+                        if let Some(count) = connected.get_mut(&address) {
+                            *count -= 1;
+                            if *count == 0 {
+                                connected.remove(&address);
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+            }
+        }
+    });
+
+    hub.register(HUB_NAME).await?;
+    let _ = hub.tell(ClusterJoined { hub: hub.into_remote_ref().await }).await;
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(sync_interval);
+        loop {
+            ticker.tick().await;
+            let mut hubs = RemoteActorRef::<Hub>::lookup_all(HUB_NAME);
+            while let Some(found) = hubs.next().await {
+                match found {
+                    Ok(remote_hub) if remote_hub.id().peer_id() != Some(&local_peer_id) => {
+                        let _ = hub.tell(PeerFound(remote_hub)).await;
+                    }
+                    Ok(_) => {}
+                    Err(error) => warn!(%error, "looking up cluster hubs failed"),
+                }
+            }
+            if hub.tell(ClusterTick).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
@@ -1,6 +1,12 @@
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::net::SocketAddr;
+use std::time::Duration;
+
+use libp2p::{Multiaddr, PeerId, identity::Keypair};
+
+use crate::cluster::{self, ClusterConfig, MAX_NODE_ID, NodeId};
+use crate::image_type::ImageType;
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -9,6 +15,40 @@ pub struct Config {
     pub frontend_dir_path: PathBuf,
     pub file_store_dir_path: PathBuf,
     pub rust_log: String,
+    pub log_format: LogFormat,
+    /// Secret for signing share links, random per process if unset
+    pub share_link_secret: Option<String>,
+    /// Bearer token for the admin interface, disabled if unset
+    pub admin_token: Option<String>,
+    pub rate_limit_per_second: f64,
+    pub rate_limit_burst: f64,
+    pub ip_rate_limit_per_second: f64,
+    pub ip_rate_limit_burst: f64,
+    pub max_message_size: usize,
+    pub max_pod_message_size: usize,
+    /// Bytes queued for a slow socket before images are dropped, and seconds it may stay above that
+    pub max_queued_bytes: usize,
+    pub slow_consumer_grace: u64,
+    /// Seconds between pings and seconds of silence after which a socket is closed
+    pub heartbeat_interval: u64,
+    pub heartbeat_timeout: u64,
+    /// Image formats pods may deliver to clients
+    pub allowed_image_types: Vec<ImageType>,
+    /// PEM certificate chain and private key, serves https and wss if both are set
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    /// Seconds between checks of the certificate files for changes
+    pub tls_reload_interval: u64,
+    /// Unique per server of a cluster, part of every id it hands out
+    pub cluster_node_id: NodeId,
+    /// Address the cluster swarm listens on, no cluster if unset
+    pub cluster_listen: Option<Multiaddr>,
+    pub cluster_peers: Vec<Multiaddr>,
+    /// Identity of this node in the cluster, and the nodes it talks to
+    pub cluster_key: Option<Keypair>,
+    pub cluster_allowed_peers: Vec<PeerId>,
+    /// Seconds between pod snapshots sent to the other nodes
+    pub cluster_sync_interval: u64,
 }
 
 impl Config {
@@ -30,13 +70,81 @@ impl Config {
         let file_store_dir = std::env::var("FILE_STORE_DIR").expect("FILE_STORE_DIR not set");
         // todo set folder where executed
         let rust_log =
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "todo_axum=debug,tower_http=debug".into());
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "infra=info,image_gallery_server=info,tower_http=debug".into());
+        let log_format = parse_env("LOG_FORMAT", LogFormat::Text);
+        let share_link_secret = std::env::var("SHARE_LINK_SECRET").ok();
+        let admin_token = std::env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty());
+        // opening a gallery requests every image at once, so bursts are generous
+        let rate_limit_per_second = parse_env("RATE_LIMIT_PER_SECOND", 100.0);
+        let rate_limit_burst = parse_env("RATE_LIMIT_BURST", 1000.0);
+        let ip_rate_limit_per_second = parse_env("IP_RATE_LIMIT_PER_SECOND", 200.0);
+        let ip_rate_limit_burst = parse_env("IP_RATE_LIMIT_BURST", 2000.0);
+        let max_message_size = parse_env("MAX_MESSAGE_SIZE", 1024 * 1024);
+        // pods deliver whole photos, base64 encoded
+        let max_pod_message_size = parse_env("MAX_POD_MESSAGE_SIZE", 32 * 1024 * 1024);
+        let max_queued_bytes = parse_env("MAX_QUEUED_BYTES", 8 * 1024 * 1024);
+        let slow_consumer_grace = parse_env("SLOW_CONSUMER_GRACE", 10);
+        let heartbeat_interval = parse_env("HEARTBEAT_INTERVAL", 15);
+        let heartbeat_timeout = parse_env("HEARTBEAT_TIMEOUT", 45);
+        assert!(heartbeat_interval > 0, "HEARTBEAT_INTERVAL must be positive");
+        let allowed_image_types = std::env::var("ALLOWED_IMAGE_TYPES").map_or(ImageType::DEFAULT_ALLOWED.to_vec(), |types| {
+            types.split(',')
+                .map(|mime| mime.parse::<ImageType>().expect("ALLOWED_IMAGE_TYPES not parsable"))
+                .collect()
+        });
+        let tls_cert_path = std::env::var("TLS_CERT_PATH").ok().map(PathBuf::from);
+        let tls_key_path = std::env::var("TLS_KEY_PATH").ok().map(PathBuf::from);
+        assert_eq!(tls_cert_path.is_some(), tls_key_path.is_some(), "TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+        let tls_reload_interval = parse_env("TLS_RELOAD_INTERVAL", 30);
+        assert!(tls_reload_interval > 0, "TLS_RELOAD_INTERVAL must be positive");
+        let cluster_listen: Option<Multiaddr> = std::env::var("CLUSTER_LISTEN").ok()
+            .map(|address| address.parse().expect("CLUSTER_LISTEN not parsable"));
+        // two nodes with the same id would hand out the same ids
+        let cluster_node_id = match cluster_listen {
+            Some(_) => std::env::var("CLUSTER_NODE_ID").expect("CLUSTER_NODE_ID must be set along with CLUSTER_LISTEN")
+                .parse().expect("CLUSTER_NODE_ID not parsable"),
+            None => parse_env("CLUSTER_NODE_ID", 0),
+        };
+        assert!(cluster_node_id <= MAX_NODE_ID, "CLUSTER_NODE_ID must not be bigger than {MAX_NODE_ID}");
+        let cluster_peers = parse_list_env("CLUSTER_PEERS");
+        let cluster_key = std::env::var("CLUSTER_KEY").ok()
+            .map(|key| cluster::keypair_from_hex(&key).expect("CLUSTER_KEY must be 32 hex encoded bytes"));
+        let cluster_allowed_peers = parse_list_env("CLUSTER_ALLOWED_PEERS");
+        if cluster_listen.is_some() {
+            assert!(cluster_key.is_some(), "CLUSTER_KEY must be set along with CLUSTER_LISTEN");
+            assert!(!cluster_allowed_peers.is_empty(), "CLUSTER_ALLOWED_PEERS must be set along with CLUSTER_LISTEN");
+        }
+        let cluster_sync_interval = parse_env("CLUSTER_SYNC_INTERVAL", 5);
+        assert!(cluster_sync_interval > 0, "CLUSTER_SYNC_INTERVAL must be positive");
         Config {
             host_ip,
             port,
             frontend_dir_path,
             file_store_dir_path: file_store_dir.into(),
             rust_log,
+            log_format,
+            share_link_secret,
+            admin_token,
+            rate_limit_per_second,
+            rate_limit_burst,
+            ip_rate_limit_per_second,
+            ip_rate_limit_burst,
+            max_message_size,
+            max_pod_message_size,
+            max_queued_bytes,
+            slow_consumer_grace,
+            heartbeat_interval,
+            heartbeat_timeout,
+            allowed_image_types,
+            tls_cert_path,
+            tls_key_path,
+            tls_reload_interval,
+            cluster_node_id,
+            cluster_listen,
+            cluster_peers,
+            cluster_key,
+            cluster_allowed_peers,
+            cluster_sync_interval,
         }
     }
 
@@ -54,6 +162,148 @@ impl Config {
     pub fn get_rust_log(&self) -> &str {
         &self.rust_log
     }
+
+    pub fn get_log_format(&self) -> LogFormat {
+        self.log_format
+    }
+
+    pub fn get_share_link_secret(&self) -> Option<&str> {
+        self.share_link_secret.as_deref()
+    }
+
+    pub fn get_allowed_image_types(&self) -> &[ImageType] {
+        &self.allowed_image_types
+    }
+
+    /// Certificate and key path, `None` for plain http
+    pub fn get_tls_paths(&self) -> Option<(&Path, &Path)> {
+        Some((self.tls_cert_path.as_deref()?, self.tls_key_path.as_deref()?))
+    }
+
+    pub fn get_tls_reload_interval(&self) -> Duration {
+        Duration::from_secs(self.tls_reload_interval)
+    }
+
+    pub fn get_cluster_node_id(&self) -> NodeId {
+        self.cluster_node_id
+    }
+
+    pub fn get_cluster_sync_interval(&self) -> Duration {
+        Duration::from_secs(self.cluster_sync_interval)
+    }
+
+    /// How to join the cluster, `None` when running standalone
+    pub fn get_cluster_config(&self) -> Option<ClusterConfig> {
+        Some(ClusterConfig {
+            listen: self.cluster_listen.clone()?,
+            peers: self.cluster_peers.clone(),
+            sync_interval: self.get_cluster_sync_interval(),
+            keypair: self.cluster_key.clone()?,
+            allowed_peers: self.cluster_allowed_peers.clone(),
+        })
+    }
+
+    pub fn get_heartbeat(&self) -> Heartbeat {
+        Heartbeat {
+            interval: Duration::from_secs(self.heartbeat_interval),
+            timeout: Duration::from_secs(self.heartbeat_timeout),
+        }
+    }
+
+    pub fn get_admin_token(&self) -> Option<&str> {
+        self.admin_token.as_deref()
+    }
+
+    pub fn get_connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits {
+            per_connection: RateLimit {
+                per_second: self.rate_limit_per_second,
+                burst: self.rate_limit_burst,
+            },
+            per_ip: RateLimit {
+                per_second: self.ip_rate_limit_per_second,
+                burst: self.ip_rate_limit_burst,
+            },
+            max_message_size: self.max_message_size,
+            max_pod_message_size: self.max_pod_message_size,
+            max_queued_bytes: self.max_queued_bytes,
+            slow_consumer_grace: Duration::from_secs(self.slow_consumer_grace),
+        }
+    }
+}
+
+/// Refill rate and burst size of a token bucket
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: f64,
+}
+
+/// Limits applied to every websocket connection
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionLimits {
+    pub per_connection: RateLimit,
+    pub per_ip: RateLimit,
+    /// Largest text message accepted, bigger ones close the socket with `CloseCode::Size`
+    pub max_message_size: usize,
+    /// Largest text message accepted from a registered pod, which delivers whole images
+    pub max_pod_message_size: usize,
+    /// Bytes queued for a socket before images are dropped, see [`crate::actors::outbound`]
+    pub max_queued_bytes: usize,
+    /// How long a socket may stay over `max_queued_bytes` before it is closed
+    pub slow_consumer_grace: Duration,
+}
+
+/// How often sockets are pinged and how long they may stay silent before they are closed.
+/// Anything received counts, pongs as well as `ClientRequest::Heartbeat` of clients not answering pings.
+#[derive(Debug, Clone, Copy)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(45),
+        }
+    }
+}
+
+/// How log lines are written to stdout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable, the default
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+impl FromStr for LogFormat {
+    type Err = String;
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format {other}")),
+        }
+    }
+}
+
+/// Reads an optional env variable, panics if it is set but not parsable
+fn parse_env<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).map_or(default, |value| {
+        value.parse::<T>().unwrap_or_else(|_| panic!("{name} not parsable"))
+    })
+}
+
+/// Reads an optional comma separated env variable, panics if an entry is not parsable
+fn parse_list_env<T: FromStr>(name: &str) -> Vec<T> {
+    std::env::var(name).map_or(vec![], |list| {
+        list.split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| entry.trim().parse::<T>().unwrap_or_else(|_| panic!("{name} not parsable")))
+            .collect()
+    })
 }
 
 impl Default for Config {
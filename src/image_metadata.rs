@@ -0,0 +1,79 @@
+use std::io::Cursor;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use exif::{Exif, In, Tag, Value};
+
+use crate::image_type::ImageType;
+use crate::protocols::{GpsPosition, ImageMetadata};
+
+/// Enough of a file to find the dimensions and the EXIF data camera images start with
+pub const METADATA_HEAD_LENGTH: usize = 256 * 1024;
+
+/// Reads what can be told about an image of `size` bytes from `head`, its beginning or the whole file.
+/// Whatever is missing or unreadable is left out, `modified` is up to the caller knowing the file.
+pub fn read_metadata(head: &[u8], size: u64) -> ImageMetadata {
+    let dimensions = imagesize::blob_size(head).ok();
+    let exif = exif::Reader::new().read_from_container(&mut Cursor::new(head)).ok();
+    let field = |tag| exif.as_ref().and_then(|exif| field(exif, tag));
+    ImageMetadata {
+        width: dimensions.and_then(|dimensions| u32::try_from(dimensions.width).ok()),
+        height: dimensions.and_then(|dimensions| u32::try_from(dimensions.height).ok()),
+        size: Some(size),
+        mime: ImageType::sniff(head).map(|image_type| image_type.mime().to_string()),
+        modified: None,
+        taken_at: field(Tag::DateTimeOriginal).or_else(|| field(Tag::DateTime)).and_then(date_time),
+        camera: field(Tag::Model).and_then(ascii),
+        orientation: field(Tag::Orientation)
+            .and_then(|value| value.get_uint(0))
+            .and_then(|orientation| u8::try_from(orientation).ok())
+            .filter(|orientation| (1..=8).contains(orientation)),
+        gps: exif.as_ref().and_then(gps_position),
+    }
+}
+
+fn field(exif: &Exif, tag: Tag) -> Option<&Value> {
+    exif.get_field(tag, In::PRIMARY).map(|field| &field.value)
+}
+
+fn ascii(value: &Value) -> Option<String> {
+    let Value::Ascii(strings) = value else {
+        return None;
+    };
+    let text = String::from_utf8_lossy(strings.first()?);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn date_time(value: &Value) -> Option<NaiveDateTime> {
+    let Value::Ascii(strings) = value else {
+        return None;
+    };
+    let date_time = exif::DateTime::from_ascii(strings.first()?).ok()?;
+    NaiveDate::from_ymd_opt(date_time.year.into(), date_time.month.into(), date_time.day.into())?
+        .and_hms_opt(date_time.hour.into(), date_time.minute.into(), date_time.second.into())
+}
+
+fn gps_position(exif: &Exif) -> Option<GpsPosition> {
+    Some(GpsPosition {
+        latitude: coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, "S")?,
+        longitude: coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, "W")?,
+    })
+}
+
+/// Degrees, minutes and seconds as degrees, negative if the reference is `negative`
+fn coordinate(exif: &Exif, tag: Tag, reference: Tag, negative: &str) -> Option<f64> {
+    let Value::Rational(parts) = field(exif, tag)? else {
+        return None;
+    };
+    let [degrees, minutes, seconds] = parts.as_slice() else {
+        return None;
+    };
+    let degrees = degrees.to_f64() + minutes.to_f64() / 60.0 + seconds.to_f64() / 3600.0;
+    if !degrees.is_finite() {
+        return None;
+    }
+    match field(exif, reference).and_then(ascii) {
+        Some(reference) if reference == negative => Some(-degrees),
+        _ => Some(degrees),
+    }
+}
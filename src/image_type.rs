@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+/// Image formats the server can recognize by their magic bytes.
+/// SVG is deliberately missing, it may carry scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageType {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+    Bmp,
+    Avif,
+}
+
+/// Enough bytes to tell every supported format apart
+const SNIFF_LENGTH: usize = 12;
+
+impl ImageType {
+    pub const ALL: [ImageType; 6] = [ImageType::Jpeg, ImageType::Png, ImageType::Gif, ImageType::Webp, ImageType::Bmp, ImageType::Avif];
+    /// Allowed unless configured otherwise, what every browser displays
+    pub const DEFAULT_ALLOWED: [ImageType; 4] = [ImageType::Jpeg, ImageType::Png, ImageType::Gif, ImageType::Webp];
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageType::Jpeg => "image/jpeg",
+            ImageType::Png => "image/png",
+            ImageType::Gif => "image/gif",
+            ImageType::Webp => "image/webp",
+            ImageType::Bmp => "image/bmp",
+            ImageType::Avif => "image/avif",
+        }
+    }
+
+    /// Detects the format from the first bytes of a file
+    pub fn sniff(bytes: &[u8]) -> Option<ImageType> {
+        match bytes {
+            [0xFF, 0xD8, 0xFF, ..] => Some(ImageType::Jpeg),
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(ImageType::Png),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(ImageType::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(ImageType::Webp),
+            [b'B', b'M', ..] => Some(ImageType::Bmp),
+            [_, _, _, _, b'f', b't', b'y', b'p', b'a', b'v', b'i', b'f' | b's', ..] => Some(ImageType::Avif),
+            _ => None,
+        }
+    }
+
+    /// Detects the format of a base64 `data:` URL as produced by `FileReader.readAsDataURL`
+    /// and returns it together with the base64 payload. The declared type is ignored.
+    pub fn sniff_data_url(blob: &str) -> Result<(ImageType, &str), ImageRejection> {
+        let (header, payload) = blob
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(','))
+            .ok_or(ImageRejection::NotADataUrl)?;
+        if !header.ends_with(";base64") {
+            return Err(ImageRejection::NotADataUrl);
+        }
+        // whole groups of 4 characters decode on their own, padding may leave fewer bytes
+        let head = payload.get(..SNIFF_LENGTH / 3 * 4).ok_or(ImageRejection::UnknownFormat)?;
+        let bytes = STANDARD.decode(head).map_err(|_| ImageRejection::NotADataUrl)?;
+        let image_type = ImageType::sniff(&bytes).ok_or(ImageRejection::UnknownFormat)?;
+        if !is_base64(payload) {
+            return Err(ImageRejection::NotADataUrl);
+        }
+        Ok((image_type, payload))
+    }
+
+    /// `data:` URL declaring this type for a base64 payload
+    pub fn data_url(&self, payload: &str) -> String {
+        format!("data:{};base64,{}", self.mime(), payload)
+    }
+}
+
+/// Whether all of `payload` is padded base64, checked piecewise instead of decoding it as a whole
+fn is_base64(payload: &str) -> bool {
+    // whole groups of 4 characters, so only the last chunk may end in padding
+    const CHUNK: usize = 4 * 1024;
+    let mut decoded = [0u8; CHUNK / 4 * 3];
+    let mut chunks = payload.as_bytes().chunks(CHUNK).peekable();
+    while let Some(chunk) = chunks.next() {
+        let is_last = chunks.peek().is_none();
+        if (!is_last && chunk.contains(&b'=')) || STANDARD.decode_slice(chunk, &mut decoded).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+impl FromStr for ImageType {
+    type Err = ImageRejection;
+    fn from_str(mime: &str) -> Result<Self, Self::Err> {
+        ImageType::ALL
+            .into_iter()
+            .find(|image_type| image_type.mime().eq_ignore_ascii_case(mime.trim()))
+            .ok_or(ImageRejection::UnknownFormat)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageRejection {
+    NotADataUrl,
+    UnknownFormat,
+    NotAllowed(ImageType),
+}
+impl std::fmt::Display for ImageRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImageRejection::NotADataUrl => f.write_str("not a base64 data url"),
+            ImageRejection::UnknownFormat => f.write_str("not a known image format"),
+            ImageRejection::NotAllowed(image_type) => write!(f, "{} is not allowed", image_type.mime()),
+        }
+    }
+}
+impl std::error::Error for ImageRejection {}
@@ -1,4 +1,10 @@
 pub mod actors;
+pub mod client;
+pub mod cluster;
 pub mod config;
+pub mod image_metadata;
+pub mod image_type;
+pub mod monitoring;
 pub mod protocols;
+pub mod share_link;
 pub mod webserver;
@@ -1,42 +1,99 @@
-use std::{net::SocketAddr, path::PathBuf, sync::Mutex};
+use std::{net::SocketAddr, time::Duration};
 
-use kameo::prelude::*;
-use infra::{actors::{self, Hub, WebClient}, config::Config, webserver::{AppState, Incrementor, websocket_handler}};
-use axum::{extract::State, Router, routing::any};
-use tower_http::{services::ServeDir,trace::{DefaultMakeSpan, TraceLayer}};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use infra::{actors::{Hub, Shutdown, websocket::CloseCode}, cluster, config::{Config, LogFormat}, monitoring, webserver::{self, AppState, tls}};
+use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
+/// How long closing sockets may take before the process exits anyway
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Start only one instance of our central Hub
-    let hub = Hub::spawn(Hub::default());
-
-    let web_state = AppState{
-        incrementor: std::sync::Arc::new(Mutex::new(Incrementor::new())),
-        //next_id: std::sync::Arc::new(AtomicU64::new(1)),
-        actor_ref: hub.clone(),
+/// Resolves on the first SIGINT or SIGTERM with the close code every socket gets.
+/// SIGTERM usually comes from a supervisor replacing the process, so clients are told to come back.
+async fn shutdown_signal() -> CloseCode {
+    #[cfg(unix)]
+    let terminate = async {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("unable to listen for SIGTERM");
+        terminate.recv().await;
     };
-    println!("Hub created!");
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => CloseCode::Away,
+        _ = terminate => CloseCode::Restart,
+    }
+}
 
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::new();
+    let log_output = match config.get_log_format() {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().with_current_span(true).with_span_list(false).boxed(),
+    };
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(config.get_rust_log()))
-        .with(tracing_subscriber::fmt::layer())
+        .with(log_output)
         .init();
 
-    let assets_dir = PathBuf::from("./static/");
-    let app = Router::new().fallback_service(ServeDir::new(assets_dir).append_index_html_on_directories(true))
-        .route("/ws", any(websocket_handler))
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().include_headers(true)),
-        ).with_state(web_state);
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    println!("ws-Webserver created!");
-    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+    // Start only one instance of our central Hub
+    let hub = Hub::new(&config).start();
+
+    let first_id = cluster::first_id_of_node(config.get_cluster_node_id());
+    let mut web_state = AppState::new(hub.clone(), first_id, config.get_connection_limits())
+        .with_heartbeat(config.get_heartbeat())
+        .with_metrics(monitoring::install()?);
+    if let Some(token) = config.get_admin_token() {
+        web_state = web_state.with_admin_token(token);
+    }
+
+    if let Some(cluster_config) = config.get_cluster_config() {
+        cluster::join(cluster_config, hub.clone()).await?;
+        tracing::info!(node_id = config.get_cluster_node_id(), "joined cluster");
+    }
+
+    let open_sockets = web_state.open_sockets.clone();
+    // new connections are refused once this resolves, the Hub closes the existing ones
+    let shutdown = {
+        let hub = hub.clone();
+        async move {
+            let code = shutdown_signal().await;
+            tracing::info!(?code, "shutting down");
+            let _ = hub.ask(Shutdown(code)).await;
+        }
+    };
+
+    let app = webserver::router(web_state, "./static/");
+    let addr = config.get_host_socket_addr();
+    match config.get_tls_paths() {
+        Some((cert_path, key_path)) => {
+            let tls_config = tls::load_tls_config(cert_path, key_path).await?;
+            tls::spawn_certificate_reloader(tls_config.clone(), cert_path.into(), key_path.into(), config.get_tls_reload_interval());
+            tracing::info!(%addr, "serving https and wss");
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    shutdown.await;
+                    handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+                }
+            });
+            axum_server::bind_rustls(addr, tls_config)
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::info!(addr = %listener.local_addr()?, "serving http and ws");
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(shutdown)
+                .await?;
+        }
+    }
 
     hub.wait_for_shutdown().await;
+    // the close frames are written by the socket tasks, which must not be cut off by returning
+    if tokio::time::timeout(SHUTDOWN_GRACE, open_sockets.closed()).await.is_err() {
+        tracing::warn!(open = open_sockets.receiver_count(), "sockets still open after the grace period");
+    }
     Ok(())
 }
@@ -0,0 +1,47 @@
+//! Names of the metrics served on `/metrics`. They are recorded with the `metrics` macros
+//! throughout the server, without an installed recorder (e.g. in tests) that costs nothing.
+
+use metrics::{Unit, describe_counter, describe_gauge, describe_histogram};
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
+
+pub const CONNECTED_CLIENTS: &str = "gallery_connected_clients";
+pub const REGISTERED_PODS: &str = "gallery_registered_pods";
+pub const IMAGES_DELIVERED: &str = "gallery_images_delivered_total";
+pub const IMAGES_REJECTED: &str = "gallery_images_rejected_total";
+pub const IMAGE_DELIVERY_SECONDS: &str = "gallery_image_delivery_seconds";
+/// Labeled with `direction`, `received` or `sent`
+pub const WEBSOCKET_BYTES: &str = "gallery_websocket_bytes_total";
+/// Labeled with `kind`, the `JsonProtocol` variant, and `request`, the variant inside.
+/// Measures how long reading from the socket is held up by a message: until the answer is ready for
+/// a `ClientRequest`, until it is handed on otherwise. Searches, pod requests and image requests
+/// are handled after that, image delivery is measured by [`IMAGE_DELIVERY_SECONDS`].
+pub const REQUEST_SECONDS: &str = "gallery_request_duration_seconds";
+pub const INVALID_MESSAGES: &str = "gallery_invalid_messages_total";
+/// Labeled with `reason`
+pub const DROPPED_MESSAGES: &str = "gallery_dropped_messages_total";
+pub const HUB_MAILBOX_BACKLOG: &str = "gallery_hub_mailbox_backlog";
+pub const HEARTBEAT_TIMEOUTS: &str = "gallery_heartbeat_timeouts_total";
+/// Labeled with `result`, `resumed` or `refused`
+pub const SESSION_RESUMES: &str = "gallery_session_resumes_total";
+
+const LATENCY_BUCKETS: [f64; 12] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+/// Installs the global recorder, the handle renders everything recorded so far
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(&LATENCY_BUCKETS)?
+        .install_recorder()?;
+    describe_gauge!(CONNECTED_CLIENTS, "Websocket sessions subscribed at the Hub, pods included");
+    describe_gauge!(REGISTERED_PODS, "Pods connected to this server");
+    describe_counter!(IMAGES_DELIVERED, "Images delivered by pods and forwarded to clients");
+    describe_counter!(IMAGES_REJECTED, "Images refused because of their format");
+    describe_histogram!(IMAGE_DELIVERY_SECONDS, Unit::Seconds, "Time from a client requesting an image to the pod delivering it");
+    describe_counter!(WEBSOCKET_BYTES, Unit::Bytes, "Payload of websocket text messages");
+    describe_histogram!(REQUEST_SECONDS, Unit::Seconds, "Time a WebClient spends on an incoming message before reading the next one");
+    describe_counter!(INVALID_MESSAGES, "Incoming messages that are not valid JsonProtocol or not allowed for the session");
+    describe_counter!(DROPPED_MESSAGES, "Messages dropped instead of handled");
+    describe_gauge!(HUB_MAILBOX_BACKLOG, "Messages waiting in the Hub mailbox when scraped");
+    describe_counter!(HEARTBEAT_TIMEOUTS, "Sockets closed because they stayed silent too long");
+    describe_counter!(SESSION_RESUMES, "Reconnecting clients asking to resume their session");
+    Ok(handle)
+}
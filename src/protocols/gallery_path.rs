@@ -0,0 +1,240 @@
+use std::{borrow::Cow, path::{Component, Path, PathBuf}};
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde_derive::{Deserialize, Serialize};
+
+/// Longest path accepted from a pod, in bytes
+pub const MAX_PATH_LENGTH: usize = 1024;
+/// Longest single file or folder name, in bytes
+pub const MAX_NAME_LENGTH: usize = 255;
+/// Longest glob [`Glob`] accepts, in characters with `**` counting as one
+pub const MAX_GLOB_LENGTH: usize = 128;
+
+/// A `/` separated, relative image path announced by a pod.
+/// Construction rejects everything that could escape a gallery directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct GalleryPath(String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidGalleryPath {
+    Empty,
+    TooLong,
+    NameTooLong,
+    Absolute,
+    Traversal,
+    EmptyComponent,
+    ControlCharacter,
+    Backslash,
+}
+impl std::fmt::Display for InvalidGalleryPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = match self {
+            InvalidGalleryPath::Empty => "path is empty",
+            InvalidGalleryPath::TooLong => "path is too long",
+            InvalidGalleryPath::NameTooLong => "file or folder name is too long",
+            InvalidGalleryPath::Absolute => "path must be relative",
+            InvalidGalleryPath::Traversal => "path must not contain `.` or `..`",
+            InvalidGalleryPath::EmptyComponent => "path must not contain empty names",
+            InvalidGalleryPath::ControlCharacter => "path must not contain control characters",
+            InvalidGalleryPath::Backslash => "path must use `/` as separator",
+        };
+        f.write_str(text)
+    }
+}
+impl std::error::Error for InvalidGalleryPath {}
+
+impl GalleryPath {
+    pub fn new(path: impl Into<String>) -> Result<Self, InvalidGalleryPath> {
+        let path = path.into();
+        if path.is_empty() {
+            return Err(InvalidGalleryPath::Empty);
+        }
+        if path.len() > MAX_PATH_LENGTH {
+            return Err(InvalidGalleryPath::TooLong);
+        }
+        if path.chars().any(char::is_control) {
+            return Err(InvalidGalleryPath::ControlCharacter);
+        }
+        if path.contains('\\') {
+            return Err(InvalidGalleryPath::Backslash);
+        }
+        if path.starts_with('/') {
+            return Err(InvalidGalleryPath::Absolute);
+        }
+        // a leading `C:` leaves the gallery on windows, `to_path` refuses such names further down
+        if path.as_bytes()[0].is_ascii_alphabetic() && path.as_bytes().get(1) == Some(&b':') {
+            return Err(InvalidGalleryPath::Absolute);
+        }
+        for name in path.split('/') {
+            match name {
+                "" => return Err(InvalidGalleryPath::EmptyComponent),
+                "." | ".." => return Err(InvalidGalleryPath::Traversal),
+                name if name.len() > MAX_NAME_LENGTH => return Err(InvalidGalleryPath::NameTooLong),
+                _ => (),
+            }
+        }
+        Ok(GalleryPath(path))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Folder and file names from the gallery root downwards
+    pub fn components(&self) -> impl Iterator<Item = &str> {
+        self.0.split('/')
+    }
+
+    /// Whether the path matches `glob`, see [`Glob`]. Compile the glob once to match many paths.
+    pub fn matches_glob(&self, glob: &str) -> bool {
+        Glob::new(glob).is_some_and(|glob| glob.matches(self))
+    }
+
+    /// Location of this image below `root`, never outside of it.
+    /// `None` if a name means more than a name on this platform, like `c:d.png` on windows.
+    pub fn to_path(&self, root: &Path) -> Option<PathBuf> {
+        let mut path = root.to_path_buf();
+        for name in self.components() {
+            let mut parts = Path::new(name).components();
+            let (Some(Component::Normal(_)), None) = (parts.next(), parts.next()) else {
+                return None;
+            };
+            path.push(name);
+        }
+        Some(path)
+    }
+}
+
+/// A compiled glob: `?` is one character and `*` any number of them within a name, `**` spans folders too.
+/// Matching takes time proportional to the length of the path only, every token is a bit of a state set.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    tokens: usize,
+    /// Bit `i` set if token `i` is the character, for ASCII characters
+    ascii: Box<[u128; 128]>,
+    other: Vec<(char, u128)>,
+    /// `?`
+    any_char: u128,
+    /// `*`
+    any_name: u128,
+    /// `**`
+    any_path: u128,
+}
+impl Glob {
+    /// `None` if the glob is longer than [`MAX_GLOB_LENGTH`]
+    pub fn new(glob: &str) -> Option<Self> {
+        let mut compiled = Glob { tokens: 0, ascii: Box::new([0; 128]), other: vec![], any_char: 0, any_name: 0, any_path: 0 };
+        let mut chars = glob.chars().peekable();
+        while let Some(c) = chars.next() {
+            if compiled.tokens == MAX_GLOB_LENGTH {
+                return None;
+            }
+            let bit = 1u128 << compiled.tokens;
+            match c {
+                '*' if chars.next_if_eq(&'*').is_some() => compiled.any_path |= bit,
+                '*' => compiled.any_name |= bit,
+                '?' => compiled.any_char |= bit,
+                c if c.is_ascii() => compiled.ascii[c as usize] |= bit,
+                c => match compiled.other.iter_mut().find(|(other, _)| *other == c) {
+                    Some((_, mask)) => *mask |= bit,
+                    None => compiled.other.push((c, bit)),
+                },
+            }
+            compiled.tokens += 1;
+        }
+        Some(compiled)
+    }
+
+    pub fn matches(&self, path: &GalleryPath) -> bool {
+        // bit `i` of `states`: the first `i` tokens match what was read so far, `done`: all of them do
+        let (mut states, mut done) = self.skip_stars(if self.tokens > 0 { 1 } else { 0 }, self.tokens == 0);
+        for c in path.as_str().chars() {
+            if states == 0 {
+                return false;
+            }
+            let literal = match c {
+                c if c.is_ascii() => self.ascii[c as usize],
+                c => self.other.iter().find(|(other, _)| *other == c).map_or(0, |(_, mask)| *mask),
+            };
+            let (consumes, repeats) = match c {
+                '/' => (literal, self.any_path),
+                _ => (literal | self.any_char, self.any_path | self.any_name),
+            };
+            let advanced = states & consumes;
+            (states, done) = self.skip_stars(((advanced << 1) | (states & repeats)) & self.all(), advanced & self.last() != 0);
+        }
+        done
+    }
+
+    /// Stars may match nothing as well
+    fn skip_stars(&self, mut states: u128, mut done: bool) -> (u128, bool) {
+        loop {
+            let skipping = states & (self.any_name | self.any_path);
+            done |= skipping & self.last() != 0;
+            let next = (states | (skipping << 1)) & self.all();
+            if next == states {
+                return (states, done);
+            }
+            states = next;
+        }
+    }
+
+    /// The states before the last token, the state after it is `done`
+    fn all(&self) -> u128 {
+        match self.tokens {
+            128.. => u128::MAX,
+            tokens => (1 << tokens) - 1,
+        }
+    }
+
+    fn last(&self) -> u128 {
+        match self.tokens {
+            0 => 0,
+            tokens => 1 << (tokens - 1),
+        }
+    }
+}
+
+impl TryFrom<String> for GalleryPath {
+    type Error = InvalidGalleryPath;
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        GalleryPath::new(path)
+    }
+}
+impl TryFrom<&str> for GalleryPath {
+    type Error = InvalidGalleryPath;
+    fn try_from(path: &str) -> Result<Self, Self::Error> {
+        GalleryPath::new(path)
+    }
+}
+impl From<GalleryPath> for String {
+    fn from(path: GalleryPath) -> Self {
+        path.0
+    }
+}
+impl AsRef<str> for GalleryPath {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+impl JsonSchema for GalleryPath {
+    fn schema_name() -> Cow<'static, str> {
+        "GalleryPath".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "Relative image path with `/` separators, without `.`, `..`, empty names, backslashes or control characters",
+            "minLength": 1,
+            "maxLength": MAX_PATH_LENGTH,
+        })
+    }
+}
+
+impl std::fmt::Display for GalleryPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -1,70 +1,357 @@
+use std::collections::BTreeMap;
+
 use serde_json as json;
 use serde_derive::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use kameo::{Reply};
+use schemars::{JsonSchema, Schema, schema_for};
+
+mod gallery_path;
+pub use gallery_path::{GalleryPath, Glob, InvalidGalleryPath, MAX_GLOB_LENGTH, MAX_NAME_LENGTH, MAX_PATH_LENGTH};
+mod sort_order;
+pub use sort_order::{SortDirection, SortItem, SortKey, SortOrder};
 
 pub type PodId = u64; // <- danger zone
 
+/// Most paths a [`ClientResponse::PodPaths`] page holds, also the default
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Metadata of the images of a pod, images without any are left out
+pub type PathMetadata = BTreeMap<GalleryPath, ImageMetadata>;
+
 /// Master -> Browser
-#[derive(Serialize, Deserialize, Debug, Clone, Reply)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Reply)]
 pub enum ClientResponse {
     Pods(Vec<PodDescription>),
+    /// Answer to [`ClientRequest::ListPodSummaries`]
+    PodSummaries(Vec<PodSummary>),
     NewPod { id: PodId, name: String, },
     UnknownPod(PodId),
     PodGone(PodId),
     PodUpdateName { id: PodId, name: String, },
-    PodUpdatePaths { id: PodId, paths: Vec<String>, replace_images: bool, last_modified: DateTime<Utc>, },
-    DeliverImage { gallery_id: PodId, path: String, blob: String, },
+    /// Every path of the pod sorted by name, sent to everyone whenever they change and
+    /// to whoever asks with a bare [`StructureQuery::Pod`].
+    /// `version` counts the path updates of a pod, each of them bumps it by one.
+    PodUpdatePaths {
+        id: PodId,
+        paths: Vec<GalleryPath>,
+        replace_images: bool,
+        last_modified: DateTime<Utc>,
+        version: u64,
+        /// Of the images among `paths` the pod told about
+        #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+        metadata: PathMetadata,
+        /// The order the pod declared for its paths, empty if it did not or it is the order by name
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        curated_order: Vec<GalleryPath>,
+    },
+    /// Every path of the pod in the `sort` order a [`StructureQuery::Sorted`] asked for
+    PodStructure {
+        id: PodId,
+        sort: SortOrder,
+        paths: Vec<GalleryPath>,
+        last_modified: DateTime<Utc>,
+        version: u64,
+        /// Of the images among `paths` the pod told about
+        #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+        metadata: PathMetadata,
+    },
+    /// Paths new to the pod. A client whose version of the pod is not `version - 1` missed an update
+    /// and has to ask for the whole `ListPodStructure`.
+    /// Images whose metadata changed are sent as removed and added again.
+    PathsAdded {
+        id: PodId,
+        paths: Vec<GalleryPath>,
+        last_modified: DateTime<Utc>,
+        version: u64,
+        /// Of the images among `paths` the pod told about
+        #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+        metadata: PathMetadata,
+    },
+    /// Paths the pod no longer has, versioned like [`ClientResponse::PathsAdded`]
+    PathsRemoved { id: PodId, paths: Vec<GalleryPath>, last_modified: DateTime<Utc>, version: u64, },
+    /// A page of [`ClientRequest::ListPodPaths`]. `next` is set while more paths match, `total` counts all of them.
+    PodPaths {
+        id: PodId,
+        paths: Vec<GalleryPath>,
+        next: Option<GalleryPath>,
+        total: u64,
+        version: u64,
+        /// Of the images among `paths` the pod told about
+        #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+        metadata: PathMetadata,
+    },
+    /// A page of [`ClientRequest::Search`] in the order asked for, `next` is set while more images match
+    SearchResults { query: String, hits: Vec<SearchHit>, next: Option<SearchHit>, total: u64, },
+    /// A page of [`ClientRequest::ListFolder`]: names of the subfolders sorted by name, sent with the first page only,
+    /// and the images right inside `folder` in `sort` order, `next` is set while more follow
+    FolderListing {
+        gallery_id: PodId,
+        folder: String,
+        folders: Vec<String>,
+        images: Vec<GalleryPath>,
+        #[serde(default)]
+        sort: SortOrder,
+        next: Option<GalleryPath>,
+        /// Of the images right inside `folder`
+        total: u64,
+        version: u64,
+        /// Of the images among `images` the pod told about
+        #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+        metadata: PathMetadata,
+    },
+    DeliverImage { gallery_id: PodId, path: GalleryPath, mime: String, blob: String, },
+    ImageRejected { gallery_id: PodId, path: GalleryPath, reason: String, },
+    /// Announcement of the operators to everyone connected
+    ServerNotice { message: String, },
+    /// Answer to [`ClientRequest::Heartbeat`]
+    Heartbeat,
+    /// First message on every socket. Reconnecting with `?resume=<token>` keeps the id,
+    /// `resumed` tells whether the missed events follow or everything has to be listed again.
+    Session { client_id: PodId, token: String, resumed: bool, },
+}
+impl ClientResponse {
+    /// The gallery a response is about, `None` for listings spanning several pods
+    pub fn pod_id(&self) -> Option<PodId> {
+        use ClientResponse::*;
+        match self {
+            Pods(_) | PodSummaries(_) | SearchResults { .. } | ServerNotice { .. } | Heartbeat | Session { .. } => None,
+            NewPod { id, .. }
+            | UnknownPod(id)
+            | PodGone(id)
+            | PodUpdateName { id, .. }
+            | PodUpdatePaths { id, .. }
+            | PodStructure { id, .. }
+            | PathsAdded { id, .. }
+            | PathsRemoved { id, .. }
+            | PodPaths { id, .. } => Some(*id),
+            DeliverImage { gallery_id, .. }
+            | ImageRejected { gallery_id, .. }
+            | FolderListing { gallery_id, .. } => Some(*gallery_id),
+        }
+    }
 }
 
 /// Browser -> Master rpc style
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 //#[rtype(result = "ClientResponse")]
 pub enum ClientRequest{
     ListAllPods,
-    ListPodStructure(PodId),
+    /// Answered with a [`ClientResponse::PodUpdatePaths`] like those sent on every change,
+    /// or a [`ClientResponse::PodStructure`] if a sort order is asked for
+    ListPodStructure(StructureQuery),
+    /// Keeps the connection alive for clients unable to answer websocket pings,
+    /// the answer tells them the server is still there
+    Heartbeat,
+    /// Every pod without its paths, for clients fetching those only when needed
+    ListPodSummaries,
+    /// One page of the paths of a pod in `sort` order, at most `limit` up to [`MAX_PAGE_SIZE`].
+    /// Only paths starting with `prefix` and matching `glob` are listed: `?` is one character and `*` any
+    /// number of them within a name, `**` spans folders too. Globs longer than [`MAX_GLOB_LENGTH`] match nothing.
+    /// The next page starts `after` the `next` of the previous one, asked for in the same order.
+    ListPodPaths {
+        id: PodId,
+        prefix: Option<String>,
+        glob: Option<String>,
+        after: Option<GalleryPath>,
+        limit: Option<u32>,
+        #[serde(default)]
+        sort: SortOrder,
+    },
+    /// One level of the folder tree the `/` separated paths of a pod form, `""` is the root.
+    /// Images are paged like [`ClientRequest::ListPodPaths`], at most `limit` up to [`MAX_PAGE_SIZE`] following `after`.
+    ListFolder {
+        gallery_id: PodId,
+        folder: String,
+        #[serde(default)]
+        sort: SortOrder,
+        after: Option<GalleryPath>,
+        limit: Option<u32>,
+    },
+    /// Images of every gallery whose file name contains `query`, ignoring case, an empty one matches all.
+    /// At most `limit` hits up to [`MAX_PAGE_SIZE`] are sent, the next page starts `after` the `next` of the previous one.
+    /// Hits are in `sort` order, the curated one keeps the images of a gallery together.
+    Search {
+        query: String,
+        #[serde(default)]
+        filters: SearchFilters,
+        #[serde(default)]
+        sort: SortOrder,
+        after: Option<SearchHit>,
+        limit: Option<u32>,
+    },
+}
+
+/// Which pod a [`ClientRequest::ListPodStructure`] lists, a bare id lists it sorted by name along with the order the pod declared
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[serde(untagged)]
+pub enum StructureQuery {
+    Pod(PodId),
+    Sorted { id: PodId, sort: SortOrder, },
+}
+impl StructureQuery {
+    pub fn id(&self) -> PodId {
+        match *self {
+            StructureQuery::Pod(id) | StructureQuery::Sorted { id, .. } => id,
+        }
+    }
+}
+impl From<PodId> for StructureQuery {
+    fn from(id: PodId) -> Self {
+        StructureQuery::Pod(id)
+    }
+}
+
+/// Narrows a [`ClientRequest::Search`], every filter set has to match
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct SearchFilters {
+    /// Only images below this folder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// Only images taken at or after, those without a capture time are left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taken_after: Option<NaiveDateTime>,
+    /// Only images taken before, those without a capture time are left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taken_before: Option<NaiveDateTime>,
+    /// Part of the gallery name, ignoring case
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gallery: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SearchHit {
+    pub gallery_id: PodId,
+    pub path: GalleryPath,
 }
 
 /// Browser -> Master
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub enum ClientRequestAsync {
     RequestImage {
         gallery_id: PodId,
-        path: String,
+        path: GalleryPath,
         #[serde(skip)]
         client_id: PodId,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct PodDescription {
     pub id: PodId,
     pub name: String,
-    pub paths: Vec<String>,
+    pub paths: Vec<GalleryPath>,
     pub last_modified: DateTime<Utc>,
+    /// Of the last path update, see [`ClientResponse::PodUpdatePaths`]
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+    pub metadata: PathMetadata,
+    /// The order the pod declared for its paths, empty if it did not
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub curated_order: Vec<GalleryPath>,
+}
+
+/// What a pod knows about one of its images, so clients can lay out and sort them before downloading
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+pub struct ImageMetadata {
+    /// In pixels, as stored, before applying `orientation`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// File size in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    /// When the file was last changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+    /// Local time of the camera, EXIF does not reliably tell the time zone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taken_at: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<String>,
+    /// EXIF orientation from 1 to 8, 1 is upright
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gps: Option<GpsPosition>,
+}
+
+/// Where an image was taken, in degrees, south and west being negative
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// A [`PodDescription`] without the paths
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct PodSummary {
+    pub id: PodId,
+    pub name: String,
+    pub path_count: u64,
+    pub last_modified: DateTime<Utc>,
+    pub version: u64,
 }
 
 /// Slave -> Master
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub enum PodRequest {
     RegisterSelf {
         proposed_id: Option<PodId>,
         name: String,
     },
     UpdateTitle { name: String, },
-    UpdatePaths { paths: Vec<String>, replace_images: bool, },
-    DeliverImage { client_id: PodId, path: String, blob: String, },
+    /// Every path of the pod, clients are only sent what changed unless `replace_images` is set.
+    /// With `curated` listings keep the order of `paths` unless asked for another one,
+    /// paths added later go to the end.
+    UpdatePaths {
+        paths: Vec<GalleryPath>,
+        replace_images: bool,
+        #[serde(default)]
+        curated: bool,
+        /// Of the images among `paths` the pod told about
+        #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+        metadata: PathMetadata,
+    },
+    /// Paths to publish in addition to those already published
+    PathsAdded {
+        paths: Vec<GalleryPath>,
+        /// Of the images among `paths` the pod told about
+        #[serde(default, skip_serializing_if = "PathMetadata::is_empty")]
+        metadata: PathMetadata,
+    },
+    /// Paths no longer published
+    PathsRemoved { paths: Vec<GalleryPath>, },
+    DeliverImage { client_id: PodId, path: GalleryPath, blob: String, },
+    CreateShareLink { expires_at: DateTime<Utc>, max_views: Option<u32>, },
+    RevokeShareLink { token: String, },
 }
 /// Master -> Slave
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub enum PodResponse {
     Registered { global_id: PodId, },
     AlreadyRegistered { global_id: PodId, },
-    RequestImage { client_id: PodId, path: String },
+    RequestImage { client_id: PodId, path: GalleryPath },
+    ShareLink { token: String, expires_at: DateTime<Utc>, max_views: Option<u32>, },
+    ShareLinkRevoked { token: String, },
+    ShareLinkDenied { reason: String, },
+    ImageRejected { client_id: PodId, path: GalleryPath, reason: String, },
+    /// Paths of the last path update that are not valid and were left out, everything else was taken
+    PathsRejected { paths: Vec<RejectedPath>, },
+}
+
+/// A path a pod announced that is not a valid [`GalleryPath`]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct RejectedPath {
+    pub path: String,
+    pub reason: String,
 }
 
 /// Communicate with everything
-#[derive(Serialize, Deserialize, Debug, Reply)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Reply)]
 pub enum JsonProtocol {
     ClientRequest(ClientRequest),
     ClientRequestAsync(ClientRequestAsync),
@@ -73,44 +360,224 @@ pub enum JsonProtocol {
     PodResponse(PodResponse),
 }
 
+impl JsonProtocol {
+    /// Name of the variant, used as metrics label
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JsonProtocol::ClientRequest(_) => "ClientRequest",
+            JsonProtocol::ClientRequestAsync(_) => "ClientRequestAsync",
+            JsonProtocol::ClientResponse(_) => "ClientResponse",
+            JsonProtocol::PodRequest(_) => "PodRequest",
+            JsonProtocol::PodResponse(_) => "PodResponse",
+        }
+    }
+
+    /// Name of the variant inside, e.g. `ListPodPaths`, used as metrics label along with [`JsonProtocol::kind`]
+    pub fn variant(&self) -> &'static str {
+        match self {
+            JsonProtocol::ClientRequest(request) => match request {
+                ClientRequest::ListAllPods => "ListAllPods",
+                ClientRequest::ListPodStructure(_) => "ListPodStructure",
+                ClientRequest::Heartbeat => "Heartbeat",
+                ClientRequest::ListPodSummaries => "ListPodSummaries",
+                ClientRequest::ListPodPaths { .. } => "ListPodPaths",
+                ClientRequest::ListFolder { .. } => "ListFolder",
+                ClientRequest::Search { .. } => "Search",
+            },
+            JsonProtocol::ClientRequestAsync(request) => match request {
+                ClientRequestAsync::RequestImage { .. } => "RequestImage",
+            },
+            JsonProtocol::ClientResponse(response) => match response {
+                ClientResponse::Pods(_) => "Pods",
+                ClientResponse::PodSummaries(_) => "PodSummaries",
+                ClientResponse::NewPod { .. } => "NewPod",
+                ClientResponse::UnknownPod(_) => "UnknownPod",
+                ClientResponse::PodGone(_) => "PodGone",
+                ClientResponse::PodUpdateName { .. } => "PodUpdateName",
+                ClientResponse::PodUpdatePaths { .. } => "PodUpdatePaths",
+                ClientResponse::PodStructure { .. } => "PodStructure",
+                ClientResponse::PathsAdded { .. } => "PathsAdded",
+                ClientResponse::PathsRemoved { .. } => "PathsRemoved",
+                ClientResponse::PodPaths { .. } => "PodPaths",
+                ClientResponse::SearchResults { .. } => "SearchResults",
+                ClientResponse::FolderListing { .. } => "FolderListing",
+                ClientResponse::DeliverImage { .. } => "DeliverImage",
+                ClientResponse::ImageRejected { .. } => "ImageRejected",
+                ClientResponse::ServerNotice { .. } => "ServerNotice",
+                ClientResponse::Heartbeat => "Heartbeat",
+                ClientResponse::Session { .. } => "Session",
+            },
+            JsonProtocol::PodRequest(request) => match request {
+                PodRequest::RegisterSelf { .. } => "RegisterSelf",
+                PodRequest::UpdateTitle { .. } => "UpdateTitle",
+                PodRequest::UpdatePaths { .. } => "UpdatePaths",
+                PodRequest::PathsAdded { .. } => "PathsAdded",
+                PodRequest::PathsRemoved { .. } => "PathsRemoved",
+                PodRequest::DeliverImage { .. } => "DeliverImage",
+                PodRequest::CreateShareLink { .. } => "CreateShareLink",
+                PodRequest::RevokeShareLink { .. } => "RevokeShareLink",
+            },
+            JsonProtocol::PodResponse(response) => match response {
+                PodResponse::Registered { .. } => "Registered",
+                PodResponse::AlreadyRegistered { .. } => "AlreadyRegistered",
+                PodResponse::RequestImage { .. } => "RequestImage",
+                PodResponse::ShareLink { .. } => "ShareLink",
+                PodResponse::ShareLinkRevoked { .. } => "ShareLinkRevoked",
+                PodResponse::ShareLinkDenied { .. } => "ShareLinkDenied",
+                PodResponse::ImageRejected { .. } => "ImageRejected",
+                PodResponse::PathsRejected { .. } => "PathsRejected",
+            },
+        }
+    }
+
+    /// Parses a message like serde does, except that invalid paths of pod path updates are left out
+    /// and returned instead of failing the whole message
+    pub fn parse_lenient(json_raw: &str) -> Result<(JsonProtocol, Vec<RejectedPath>), json::Error> {
+        let error = match json::from_str(json_raw) {
+            Ok(message) => return Ok((message, vec![])),
+            Err(error) => error,
+        };
+        let Ok(mut value) = json::from_str::<json::Value>(json_raw) else {
+            return Err(error);
+        };
+        let rejected = reject_invalid_paths(&mut value);
+        if rejected.is_empty() {
+            return Err(error);
+        }
+        json::from_value(value).map(|message| (message, rejected)).map_err(|_| error)
+    }
+}
+
+/// Drops the paths and metadata keys of a `PathsAdded`, `PathsRemoved` or `UpdatePaths` that are not valid
+fn reject_invalid_paths(message: &mut json::Value) -> Vec<RejectedPath> {
+    let mut rejected: Vec<RejectedPath> = vec![];
+    let mut is_valid = |path: &str| match GalleryPath::new(path) {
+        Ok(_) => true,
+        Err(reason) => {
+            if !rejected.iter().any(|known| known.path == path) {
+                rejected.push(RejectedPath { path: path.into(), reason: reason.to_string() });
+            }
+            false
+        }
+    };
+    let Some(json::Value::Object(request)) = message.get_mut("PodRequest") else {
+        return rejected;
+    };
+    for (kind, fields) in request.iter_mut() {
+        if !matches!(kind.as_str(), "UpdatePaths" | "PathsAdded" | "PathsRemoved") {
+            continue;
+        }
+        if let Some(json::Value::Array(paths)) = fields.get_mut("paths") {
+            paths.retain(|path| path.as_str().is_none_or(&mut is_valid));
+        }
+        if let Some(json::Value::Object(metadata)) = fields.get_mut("metadata") {
+            metadata.retain(|path, _| is_valid(path));
+        }
+    }
+    rejected
+}
+
+/// JSON Schema of everything sent over the websocket, published as `/protocol.schema.json`
+pub fn protocol_schema() -> Schema {
+    schema_for!(JsonProtocol)
+}
+
+/// One message of every kind, the golden tests pin down how each of them looks on the wire
+pub fn example_messages() -> Vec<JsonProtocol> {
+    let last_modified = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let path = |path: &str| GalleryPath::new(path).unwrap();
+    let metadata = PathMetadata::from([(path("new"), ImageMetadata {
+        width: Some(4032),
+        height: Some(3024),
+        size: Some(2_345_678),
+        mime: Some("image/jpeg".into()),
+        modified: Some(last_modified),
+        taken_at: Some(last_modified.naive_utc()),
+        camera: Some("Pixel 8".into()),
+        orientation: Some(6),
+        gps: Some(GpsPosition { latitude: 52.52, longitude: -13.405 }),
+    })]);
+    vec![
+        JsonProtocol::ClientRequest(ClientRequest::ListAllPods),
+        JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(StructureQuery::Pod(42))),
+        JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(StructureQuery::Sorted { id: 42, sort: SortOrder { key: SortKey::Taken, direction: SortDirection::Descending }, })),
+        JsonProtocol::ClientRequest(ClientRequest::Heartbeat),
+        JsonProtocol::ClientRequest(ClientRequest::ListPodSummaries),
+        JsonProtocol::ClientRequest(ClientRequest::ListPodPaths { id: 42, prefix: Some("2024/".into()), glob: Some("**/*.png".into()), after: Some(path("2024/a.png")), limit: Some(100), sort: SortOrder { key: SortKey::Natural, direction: SortDirection::Ascending }, }),
+        JsonProtocol::ClientRequest(ClientRequest::ListPodPaths { id: 42, prefix: None, glob: None, after: None, limit: None, sort: SortOrder::default(), }),
+        JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: 42, folder: "2024/summer".into(), sort: SortOrder { key: SortKey::Size, direction: SortDirection::Descending }, after: None, limit: None, }),
+        JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: 42, folder: "".into(), sort: SortOrder::default(), after: Some(path("a.png")), limit: Some(100), }),
+        JsonProtocol::ClientRequest(ClientRequest::Search {
+            query: "beach".into(),
+            filters: SearchFilters {
+                folder: Some("2024".into()),
+                taken_after: Some(last_modified.naive_utc()),
+                taken_before: Some(last_modified.naive_utc()),
+                gallery: Some("holiday".into()),
+            },
+            sort: SortOrder { key: SortKey::Modified, direction: SortDirection::Descending },
+            after: Some(SearchHit { gallery_id: 42, path: path("2024/beach.png") }),
+            limit: Some(50),
+        }),
+        JsonProtocol::ClientRequest(ClientRequest::Search { query: "".into(), filters: SearchFilters::default(), sort: SortOrder::default(), after: None, limit: None, }),
+
+        JsonProtocol::ClientRequestAsync(ClientRequestAsync::RequestImage{gallery_id:42, path: path("bla"), client_id: 0, }),
+
+        JsonProtocol::ClientResponse(ClientResponse::Pods(
+            vec![PodDescription{id: 42, name: "bla".into(), paths: vec![], last_modified, version: 0, metadata: PathMetadata::new(), curated_order: vec![],}])),
+        JsonProtocol::ClientResponse(ClientResponse::PodSummaries(
+            vec![PodSummary{id: 42, name: "bla".into(), path_count: 1234, last_modified, version: 7,}])),
+        JsonProtocol::ClientResponse(ClientResponse::NewPod{id:23, name: "blubb".into()}),
+        JsonProtocol::ClientResponse(ClientResponse::UnknownPod(123)),
+        JsonProtocol::ClientResponse(ClientResponse::PodGone(1234)),
+        JsonProtocol::ClientResponse(ClientResponse::PodUpdateName{ id: 42, name: "String".into(), }),
+        JsonProtocol::ClientResponse(ClientResponse::PodUpdatePaths{ id: 42, paths: vec![path("String")], replace_images: false, last_modified, version: 3, metadata: PathMetadata::new(), curated_order: vec![], }),
+        JsonProtocol::ClientResponse(ClientResponse::PodUpdatePaths{ id: 42, paths: vec![path("a"), path("b")], replace_images: false, last_modified, version: 4, metadata: PathMetadata::new(), curated_order: vec![path("b"), path("a")], }),
+        JsonProtocol::ClientResponse(ClientResponse::PodStructure{ id: 42, sort: SortOrder { key: SortKey::Taken, direction: SortDirection::Descending }, paths: vec![path("b"), path("a")], last_modified, version: 4, metadata: PathMetadata::new(), }),
+        JsonProtocol::ClientResponse(ClientResponse::PathsAdded{ id: 42, paths: vec![path("new")], last_modified, version: 4, metadata: metadata.clone(), }),
+        JsonProtocol::ClientResponse(ClientResponse::PathsRemoved{ id: 42, paths: vec![path("String")], last_modified, version: 5, }),
+        JsonProtocol::ClientResponse(ClientResponse::PodPaths{ id: 42, paths: vec![path("2024/b.png")], next: Some(path("2024/b.png")), total: 3, version: 5, metadata: PathMetadata::new(), }),
+        JsonProtocol::ClientResponse(ClientResponse::PodPaths{ id: 42, paths: vec![], next: None, total: 0, version: 5, metadata: PathMetadata::new(), }),
+        JsonProtocol::ClientResponse(ClientResponse::SearchResults {
+            query: "beach".into(),
+            hits: vec![SearchHit { gallery_id: 42, path: path("2024/beach.png") }],
+            next: Some(SearchHit { gallery_id: 42, path: path("2024/beach.png") }),
+            total: 7,
+        }),
+        JsonProtocol::ClientResponse(ClientResponse::FolderListing{
+            gallery_id: 42, folder: "2024".into(), folders: vec!["summer".into()], images: vec![path("2024/a.png")], sort: SortOrder::default(), next: Some(path("2024/a.png")), total: 2, version: 5, metadata: PathMetadata::new(), }),
+        JsonProtocol::ClientResponse(ClientResponse::DeliverImage { gallery_id: 42, path: path("String"), mime: "image/png".into(), blob: "String".into(), },),
+        JsonProtocol::ClientResponse(ClientResponse::ImageRejected { gallery_id: 42, path: path("String"), reason: "not a known image format".into(), },),
+        JsonProtocol::ClientResponse(ClientResponse::ServerNotice { message: "restarting in 5 minutes".into(), }),
+        JsonProtocol::ClientResponse(ClientResponse::Heartbeat),
+        JsonProtocol::ClientResponse(ClientResponse::Session { client_id: 42, token: "2a.c0ffee".into(), resumed: false, }),
+
+        JsonProtocol::PodRequest(PodRequest::RegisterSelf{ proposed_id: Some(42), name: "bla".into(), }),
+        JsonProtocol::PodRequest(PodRequest::RegisterSelf{ proposed_id: None, name: "bla".into(), }),
+        JsonProtocol::PodRequest(PodRequest::UpdateTitle{ name: "bli".into(), }),
+        JsonProtocol::PodRequest(PodRequest::UpdatePaths{ paths: vec![path("bli")], replace_images: true, curated: false, metadata: PathMetadata::new(), }),
+        JsonProtocol::PodRequest(PodRequest::UpdatePaths{ paths: vec![path("b"), path("a")], replace_images: false, curated: true, metadata: PathMetadata::new(), }),
+        JsonProtocol::PodRequest(PodRequest::PathsAdded{ paths: vec![path("new")], metadata, }),
+        JsonProtocol::PodRequest(PodRequest::PathsRemoved{ paths: vec![path("bli")], }),
+        JsonProtocol::PodRequest(PodRequest::DeliverImage { client_id: 23, path: path("String"), blob: "String".into(), },),
+        JsonProtocol::PodRequest(PodRequest::CreateShareLink { expires_at: last_modified, max_views: Some(3), }),
+        JsonProtocol::PodRequest(PodRequest::RevokeShareLink { token: "2a.1.6553f100.c0ffee".into(), }),
+
+        JsonProtocol::PodResponse(PodResponse::Registered { global_id: 42, }),
+        JsonProtocol::PodResponse(PodResponse::AlreadyRegistered { global_id: 42, }),
+        JsonProtocol::PodResponse(PodResponse::RequestImage { client_id: 42, path: path("bli"), }),
+        JsonProtocol::PodResponse(PodResponse::ShareLink { token: "2a.1.6553f100.c0ffee".into(), expires_at: last_modified, max_views: None, }),
+        JsonProtocol::PodResponse(PodResponse::ShareLinkRevoked { token: "2a.1.6553f100.c0ffee".into(), }),
+        JsonProtocol::PodResponse(PodResponse::ShareLinkDenied { reason: "share link expired".into(), }),
+        JsonProtocol::PodResponse(PodResponse::ImageRejected { client_id: 42, path: path("bli"), reason: "not a known image format".into(), }),
+        JsonProtocol::PodResponse(PodResponse::PathsRejected { paths: vec![RejectedPath { path: "../bla".into(), reason: "path must not contain `.` or `..`".into() }], }),
+    ]
+}
+
 // kind of testfunction
+#[allow(dead_code)]
 pub(crate) fn print_all_messages() {
-    let t = |t| { println!("\n==== {} ====", t); };
-    let p = |obj| {
-        let s = json::to_string(&obj).unwrap();
-        println!("  {}", s);
-    };
-    let last_modified = Utc::now();
-
-    t("ClientRequest");
-    p(JsonProtocol::ClientRequest(ClientRequest::ListAllPods));
-    p(JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(42)));
-
-    t("ClientRequestAsync");
-    p(JsonProtocol::ClientRequestAsync(ClientRequestAsync::RequestImage{gallery_id:42, path: "bla".into(), client_id: 0, }));
-
-    t("ClientResponse");
-    p(JsonProtocol::ClientResponse(ClientResponse::Pods(
-        vec![PodDescription{id: 42, name: "bla".into(), paths: vec![], last_modified,}])));
-    p(JsonProtocol::ClientResponse(ClientResponse::NewPod{id:23, name: "blubb".into()}));
-    p(JsonProtocol::ClientResponse(ClientResponse::UnknownPod(123)));
-    p(JsonProtocol::ClientResponse(ClientResponse::PodGone(1234)));
-    p(JsonProtocol::ClientResponse(ClientResponse::PodUpdateName{ id: 42, name: "String".into(), }));
-    p(JsonProtocol::ClientResponse(ClientResponse::PodUpdatePaths{ id: 42, paths: vec!["String".into()], replace_images: false, last_modified, }));
-    p(JsonProtocol::ClientResponse(ClientResponse::DeliverImage { gallery_id: 42, path: "String".into(), blob: "String".into(), },));
-
-
-    t("PodRequest");
-    p(JsonProtocol::PodRequest(PodRequest::RegisterSelf{ proposed_id: Some(42), name: "bla".into(), }));
-    p(JsonProtocol::PodRequest(PodRequest::RegisterSelf{ proposed_id: None, name: "bla".into(), }));
-    p(JsonProtocol::PodRequest(PodRequest::UpdateTitle{ name: "bli".into(), }));
-    p(JsonProtocol::PodRequest(PodRequest::UpdatePaths{ paths: vec!["bli".into()], replace_images: true, }));
-    p(JsonProtocol::PodRequest(PodRequest::DeliverImage { client_id: 23, path: "String".into(), blob: "String".into(), },));
-
-    t("PodResponse");
-    p(JsonProtocol::PodResponse(PodResponse::Registered { global_id: 42, }));
-    p(JsonProtocol::PodResponse(PodResponse::AlreadyRegistered { global_id: 42, }));
-    p(JsonProtocol::PodResponse(PodResponse::RequestImage { client_id: 42, path: "bli".into(), }));
-
-    println!("\n");
+    for message in example_messages() {
+        println!("  {}", json::to_string(&message).unwrap());
+    }
 }
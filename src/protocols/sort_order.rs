@@ -0,0 +1,113 @@
+use std::cmp::Ordering;
+
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{GalleryPath, ImageMetadata};
+
+/// What listings of images are ordered by
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SortKey {
+    /// The order the pod declared, by name if it did not
+    #[default]
+    Curated,
+    /// Byte wise by path
+    Name,
+    /// By path ignoring case, numbers by their value so `img2` comes before `img10`
+    Natural,
+    /// By [`ImageMetadata::modified`]
+    Modified,
+    /// By [`ImageMetadata::taken_at`]
+    Taken,
+    /// By [`ImageMetadata::size`]
+    Size,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+impl SortDirection {
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortDirection::Ascending => ordering,
+            SortDirection::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// How a listing is ordered. Images lacking what they are sorted by come last in either direction,
+/// ties are broken by path.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SortOrder {
+    #[serde(default)]
+    pub key: SortKey,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+/// An image as [`SortOrder`] sees it
+#[derive(Debug, Clone, Copy)]
+pub struct SortItem<'a> {
+    pub path: &'a GalleryPath,
+    pub metadata: Option<&'a ImageMetadata>,
+    /// Position in the curated order of its pod, if it declared one
+    pub rank: Option<usize>,
+}
+
+impl SortOrder {
+    /// Compares images of the same pod
+    pub fn compare(&self, a: SortItem, b: SortItem) -> Ordering {
+        self.compare_keys(a, b).then_with(|| self.direction.apply(a.path.cmp(b.path)))
+    }
+
+    /// Compares only by the sort key, images it does not tell apart are equal
+    pub fn compare_keys(&self, a: SortItem, b: SortItem) -> Ordering {
+        match self.key {
+            SortKey::Curated if a.rank.is_some() || b.rank.is_some() => self.compare_present(a.rank, b.rank),
+            SortKey::Curated | SortKey::Name => self.direction.apply(a.path.cmp(b.path)),
+            SortKey::Natural => self.direction.apply(natural_cmp(a.path.as_str(), b.path.as_str())),
+            SortKey::Modified => self.compare_present(a.metadata.and_then(|m| m.modified), b.metadata.and_then(|m| m.modified)),
+            SortKey::Taken => self.compare_present(a.metadata.and_then(|m| m.taken_at), b.metadata.and_then(|m| m.taken_at)),
+            SortKey::Size => self.compare_present(a.metadata.and_then(|m| m.size), b.metadata.and_then(|m| m.size)),
+        }
+    }
+
+    fn compare_present<T: Ord>(&self, a: Option<T>, b: Option<T>) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => self.direction.apply(a.cmp(&b)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// Compares runs of digits by their value and everything else ignoring case
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(first_a), Some(first_b)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if first_a.is_ascii_digit() && first_b.is_ascii_digit() {
+            let (digits_a, rest_a) = split_digits(a);
+            let (digits_b, rest_b) = split_digits(b);
+            (a, b) = (rest_a, rest_b);
+            let (value_a, value_b) = (digits_a.trim_start_matches('0'), digits_b.trim_start_matches('0'));
+            value_a.len().cmp(&value_b.len()).then_with(|| value_a.cmp(value_b))
+        } else {
+            (a, b) = (&a[first_a.len_utf8()..], &b[first_b.len_utf8()..]);
+            first_a.to_lowercase().cmp(first_b.to_lowercase())
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+}
+
+fn split_digits(text: &str) -> (&str, &str) {
+    text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()))
+}
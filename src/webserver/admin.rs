@@ -0,0 +1,84 @@
+//! Operator interface, every route requires `Authorization: Bearer <ADMIN_TOKEN>`.
+//! The dashboard in `static/admin.html` is built on it.
+
+use axum::{
+    Json, Router, routing::{delete, get, post}, extract::{FromRequestParts, Path, State}, http::{StatusCode, request::Parts}, response::{IntoResponse, Response}
+};
+use axum_extra::{TypedHeader, headers::{Authorization, authorization::Bearer}};
+use serde_derive::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::actors::{BroadcastNotice, KickClient, ListConnections, UnsubscribePod};
+use crate::protocols::PodId;
+use super::AppState;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/connections", get(connections_handler))
+        .route("/clients/{id}", delete(kick_client_handler))
+        .route("/pods/{id}", delete(remove_pod_handler))
+        .route("/notice", post(notice_handler))
+}
+
+/// Extracting it checks the admin token, the routes do not exist without one configured
+pub struct Admin;
+
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(expected) = &state.admin_token else {
+            return Err(StatusCode::NOT_FOUND);
+        };
+        let TypedHeader(Authorization(bearer)) = TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, state).await
+            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+        // digests have the same length, so comparing them does not tell how much of the token was right
+        if Sha256::digest(bearer.token()) != Sha256::digest(expected.as_bytes()) {
+            warn!(uri = %parts.uri, "wrong admin token");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(Admin)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Notice {
+    pub message: String,
+}
+
+pub async fn connections_handler(_: Admin, State(state): State<AppState>) -> Response {
+    match state.actor_ref.ask(ListConnections).await {
+        Ok(overview) => Json(overview).into_response(),
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+/// Closes the session, pods are gone with it
+pub async fn kick_client_handler(_: Admin, State(state): State<AppState>, Path(id): Path<PodId>) -> StatusCode {
+    match state.actor_ref.ask(KickClient(id)).await {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Unlists the pod, its session stays connected and may register again
+pub async fn remove_pod_handler(_: Admin, State(state): State<AppState>, Path(id): Path<PodId>) -> StatusCode {
+    match state.actor_ref.ask(UnsubscribePod(id)).await {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Shows a message to every connected client, answers with how many were reached
+pub async fn notice_handler(_: Admin, State(state): State<AppState>, Json(notice): Json<Notice>) -> Response {
+    if notice.message.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "empty notice").into_response();
+    }
+    match state.actor_ref.ask(BroadcastNotice(notice.message)).await {
+        Ok(clients) => Json(serde_json::json!({ "clients": clients })).into_response(),
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
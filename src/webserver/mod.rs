@@ -1,20 +1,88 @@
-use std::{net::SocketAddr, sync::Mutex};
+use std::{net::SocketAddr, path::PathBuf, sync::{Arc, Mutex}};
 
 use axum::{
-    body::Bytes, extract::{ConnectInfo, State, ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade}}, response::IntoResponse
+    Json, Router, routing::{any, get}, body::Bytes, extract::{ConnectInfo, Path, Query, State, ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade}}, http::StatusCode, response::{IntoResponse, Response}
 };
 use axum_extra::{TypedHeader, headers};
-use kameo::actor::ActorRef;
+use futures_util::{SinkExt, StreamExt, stream::SplitSink};
+use kameo::{actor::{ActorRef, Spawn}, mailbox, message::StreamMessage};
+use serde_derive::Deserialize;
+use metrics::{counter, gauge};
+use metrics_exporter_prometheus::PrometheusHandle;
+use tokio::{sync::watch, time::{Instant, MissedTickBehavior}};
+use tokio_tungstenite::tungstenite;
+use tower_http::{services::ServeDir, trace::{DefaultMakeSpan, TraceLayer}};
+use tracing::{Instrument, debug, info, info_span, warn};
 
-use crate::{actors::{Hub, WebClient}, protocols::PodId};
+use crate::{actors::{self, ConnectionInfo, Hub, RedeemShareLink, ResumeSession, ResumedSession, ShareScope, TakeOverSession, WebClient, outbound::{OutboundQueue, OutboundReceiver}, websocket::CloseCode}, monitoring, protocols::{self, PodId}, share_link::ShareLinkError};
+use crate::config::{ConnectionLimits, Heartbeat};
+use self::rate_limit::{IpRateLimiter, TokenBucket};
+
+pub mod admin;
+pub mod rate_limit;
+pub mod tls;
 
 #[derive(Clone)]
 pub struct AppState {
     pub actor_ref: ActorRef<Hub>,
     // pub next_id: std::sync::Arc<AtomicU64>,
     pub incrementor: std::sync::Arc<Mutex<Incrementor>>,
+    pub limits: ConnectionLimits,
+    pub heartbeat: Heartbeat,
+    pub ip_limiter: Arc<IpRateLimiter>,
+    /// Renders `/metrics`, which answers 404 without it
+    pub metrics: Option<PrometheusHandle>,
+    /// Bearer token for `/admin`, which answers 404 without it
+    pub admin_token: Option<Arc<str>>,
+    /// Every websocket task holds a receiver, so `closed()` resolves once all sockets are gone
+    pub open_sockets: Arc<watch::Sender<()>>,
+}
+impl AppState {
+    /// State for a server handing out ids from `first_id` onwards
+    pub fn new(actor_ref: ActorRef<Hub>, first_id: PodId, limits: ConnectionLimits) -> Self {
+        AppState {
+            actor_ref,
+            incrementor: Arc::new(Mutex::new(Incrementor::starting_at(first_id))),
+            limits,
+            heartbeat: Heartbeat::default(),
+            ip_limiter: Arc::new(IpRateLimiter::new(limits.per_ip)),
+            metrics: None,
+            admin_token: None,
+            open_sockets: Arc::new(watch::Sender::new(())),
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: PrometheusHandle) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    pub fn with_admin_token(mut self, token: impl Into<Arc<str>>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
 }
 
+/// All routes of the gallery server, everything unknown is looked up in `assets_dir`
+pub fn router(state: AppState, assets_dir: impl Into<PathBuf>) -> Router {
+    Router::new().fallback_service(ServeDir::new(assets_dir.into()).append_index_html_on_directories(true))
+        .route("/ws", any(websocket_handler))
+        .route("/share/{token}", any(share_handler))
+        .route("/protocol.schema.json", get(schema_handler))
+        .route("/metrics", get(metrics_handler))
+        .nest("/admin", admin::routes())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::default().include_headers(true)),
+        ).with_state(state)
+}
+
+#[derive(Default)]
 pub struct Incrementor {
     i: PodId,
 }
@@ -24,6 +92,12 @@ impl Incrementor {
             i:0,
         }
     }
+    /// Ids of a cluster node start at its own offset so they never collide with other nodes
+    pub fn starting_at(first: PodId) -> Self {
+        Incrementor{
+            i: first,
+        }
+    }
     pub fn increment(&mut self) -> PodId {
         let id = self.i;
         self.i = self.i.checked_add(1).expect("we ran out of ids");
@@ -31,101 +105,289 @@ impl Incrementor {
     }
 }
 
+/// Translate the actor side websocket messages into axum frames, `Nop` has no frame
+fn to_axum_message(msg: actors::websocket::Message) -> Option<Message> {
+    use actors::websocket::Message::*;
+    match msg {
+        Text(text) => Some(Message::Text(text.into())),
+        Binary(bytes) => Some(Message::Binary(bytes)),
+        Ping(text) => Some(Message::Ping(Bytes::from(text))),
+        Pong(text) => Some(Message::Pong(Bytes::from(text))),
+        Close(reason) => Some(Message::Close(reason.map(|reason| CloseFrame {
+            code: reason.code.into(),
+            reason: reason.description.unwrap_or_default().into(),
+        }))),
+        Nop => None,
+    }
+}
+
+/// JSON Schema of the websocket messages, a copy is checked in as `documentation/protocol.schema.json`
+pub async fn schema_handler() -> Json<schemars::Schema> {
+    Json(protocols::protocol_schema())
+}
+
+/// Prometheus text format of everything recorded, see [`crate::monitoring`]
+pub async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let Some(metrics) = &state.metrics else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    gauge!(monitoring::HUB_MAILBOX_BACKLOG).set(Hub::mailbox_depth(&state.actor_ref) as f64);
+    metrics.render().into_response()
+}
+
+fn user_agent_name(user_agent: Option<TypedHeader<headers::UserAgent>>) -> String {
+    if let Some(TypedHeader(user_agent)) = user_agent {
+        user_agent.to_string()
+    } else {
+        String::from("Unknown browser")
+    }
+}
+
+/// `?resume=<token>` of a reconnecting client, the token of its last `ClientResponse::Session`
+#[derive(Deserialize)]
+pub struct Resume {
+    pub resume: Option<String>,
+}
+
+/// The session a reconnecting client may take over, anything wrong with the token means a new one
+async fn resume_session(state: &AppState, resume: Resume) -> Option<ResumedSession> {
+    let token = resume.resume?;
+    state.actor_ref.ask(ResumeSession { token }).await.ok().flatten()
+}
+
 pub async fn websocket_handler(State(state): State<AppState>,
+    Query(resume): Query<Resume>,
     ws: WebSocketUpgrade,
     user_agent: Option<TypedHeader<headers::UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>) -> impl IntoResponse {
+    let resumed = resume_session(&state, resume).await;
+    upgrade_web_client(state, ws, addr, user_agent_name(user_agent), resumed.as_ref().and_then(|session| session.share_scope), resumed)
+}
+
+/// Opens a client session limited to the gallery the share token was issued for.
+/// Resuming a session does not count as another view.
+pub async fn share_handler(State(state): State<AppState>,
+    Path(token): Path<String>,
+    Query(resume): Query<Resume>,
+    ws: WebSocketUpgrade,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>) -> Response {
+    if let Some(resumed) = resume_session(&state, resume).await {
+        return upgrade_web_client(state, ws, addr, user_agent_name(user_agent), resumed.share_scope, Some(resumed));
+    }
+    match state.actor_ref.ask(RedeemShareLink { token }).await {
+        Ok(scope) => upgrade_web_client(state, ws, addr, user_agent_name(user_agent), Some(scope), None),
+        Err(error) => {
+            let reason = error.to_string();
+            let status = match error.err() {
+                Some(ShareLinkError::Malformed | ShareLinkError::BadSignature) => StatusCode::FORBIDDEN,
+                Some(_) => StatusCode::GONE,
+                None => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (status, reason).into_response()
+        }
+    }
+}
+
+fn upgrade_web_client(state: AppState, ws: WebSocketUpgrade, addr: SocketAddr, user_agent: String, share_scope: Option<ShareScope>, resumed: Option<ResumedSession>) -> Response {
+    if !state.actor_ref.is_alive() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "shutting down").into_response();
+    }
     //let id = state.next_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    let id = state.incrementor.lock().unwrap().increment();
+    let id = match &resumed {
+        Some(session) => session.id,
+        None => state.incrementor.lock().unwrap().increment(),
+    };
+    // outlives the upgrade request, so it is not nested in its span
+    let span = info_span!(
+        parent: None,
+        "connection",
+        client_id = id,
+        remote = %addr,
+        user_agent = %user_agent,
+        share_link = share_scope.map(|scope| scope.link_id),
+        resumed = resumed.is_some(),
+    );
+    let connection = Arc::new(ConnectionInfo::new(addr, user_agent));
+    let (outbound, outbound_rx) = OutboundQueue::new(state.limits.max_queued_bytes, state.limits.slow_consumer_grace);
     let web_actor = WebClient{
         id,
         hub: state.actor_ref.clone(),
         is_pod: false,
+        share_scope,
+        outbound,
+        span: span.clone(),
+        connection: connection.clone(),
+        search_index: None,
     };
 
-    let user_agent = if let Some(TypedHeader(user_agent)) = user_agent {
-        user_agent.to_string()
-    } else {
-        String::from("Unknown browser")
-    };
-    println!("`{user_agent}` at {addr} connected.");
-    ws.on_failed_upgrade(|error| println!("Error upgrading websocket: {}", error))
-        .on_upgrade(move |socket| handle_web_socket(socket, addr, web_actor))
-}
-async fn send_close_message(mut socket: WebSocket, code: u16, reason: &str) {
-    _ = socket
-        .send(Message::Close(Some(CloseFrame {
-            code,
-            reason: reason.into(),
-        })))
-        .await;
-}
-async fn handle_web_socket(mut socket: WebSocket, who: SocketAddr, web_actor: WebClient) {
+    state.ip_limiter.prune();
+    let limits = state.limits;
+    let heartbeat = state.heartbeat;
+    let ip_limiter = state.ip_limiter.clone();
+    let socket_open = state.open_sockets.subscribe();
+    let hub = state.actor_ref.clone();
+
+    // hard backstop for what registered pods may send, others are held to the smaller limit in `handle_web_socket`.
+    // Bigger frames fail to be read and close the socket with `CloseCode::Size` as well.
+    let backstop = limits.max_message_size.max(limits.max_pod_message_size);
+    ws.max_message_size(backstop)
+        .max_frame_size(backstop)
+        .on_failed_upgrade({
+            let span = span.clone();
+            move |error| warn!(parent: &span, %error, "upgrading websocket failed")
+        })
+        .on_upgrade(move |mut socket| async move {
+            // only now the session is taken over, another socket may have been quicker
+            if let Some(session) = resumed
+                && hub.ask(TakeOverSession { token: session.token }).await.ok().flatten().is_none()
+            {
+                warn!(parent: &span, "session can no longer be resumed, closing");
+                let close = CloseFrame { code: CloseCode::Policy.into(), reason: "session can no longer be resumed".into() };
+                let _ = socket.send(Message::Close(Some(close))).await;
+                return;
+            }
+            handle_web_socket(socket, addr, web_actor, outbound_rx, limits, heartbeat, ip_limiter).instrument(span).await;
+            drop(socket_open);
+        })
+}
+
+/// Writes everything the WebClient actor queued up into the websocket
+async fn forward_outbound(mut sender: SplitSink<WebSocket, Message>, mut outbound_rx: OutboundReceiver, connection: Arc<ConnectionInfo>) {
+    while let Some(msg) = outbound_rx.recv().await {
+        let is_close = matches!(msg, actors::websocket::Message::Close(_));
+        let Some(msg) = to_axum_message(msg) else {
+            continue;
+        };
+        if let Message::Text(text) = &msg {
+            counter!(monitoring::WEBSOCKET_BYTES, "direction" => "sent").increment(text.len() as u64);
+            connection.count_sent(text.len());
+        }
+        if let Err(error) = sender.send(msg).await {
+            debug!(%error, "sending failed");
+            break;
+        }
+        if is_close {
+            return;
+        }
+    }
+    let _ = sender.close().await;
+}
+
+/// Messages or frames above the limits of the upgrade fail with a capacity error of tungstenite
+fn is_too_big(error: &axum::Error) -> bool {
+    let source = std::error::Error::source(error).and_then(|source| source.downcast_ref::<tungstenite::Error>());
+    matches!(source, Some(tungstenite::Error::Capacity(_)))
+}
+
+async fn handle_web_socket(mut socket: WebSocket,
+    who: SocketAddr,
+    web_actor: WebClient,
+    outbound_rx: OutboundReceiver,
+    limits: ConnectionLimits,
+    heartbeat: Heartbeat,
+    ip_limiter: Arc<IpRateLimiter>) {
     // send a ping (unsupported by some browsers) just to kick things off and get a response
     if socket
         .send(Message::Ping(Bytes::from_static(&[1, 2, 3])))
         .await
         .is_ok()
     {
-        println!("Pinged {who}...");
+        info!("connected");
     } else {
-        println!("Could not send ping {who}!");
+        debug!("could not send the first ping");
         // no Error here since the only thing we can do is to close the connection.
         // If we can not send messages, there is no way to salvage the statemachine anyway.
         return;
     }
-    // Returns `None` if the stream has closed.
-    // debugging websocket connection
-    while let Some(msg) = socket.recv().await {
-        // TODO: process messages in extra function
-        if let Ok(msg) = msg {
-            match msg {
-                Message::Text(utf8_bytes) => {
-                    println!("Text received: {}", utf8_bytes);
-                    let result = socket
-                        .send(Message::Text(
-                            format!("Echo back text: {}", utf8_bytes).into(),
-                        ))
-                        .await;
-                    if let Err(error) = result {
-                        println!("Error sending: {}", error);
-                        send_close_message(socket, 1011, &format!("Error occured: {}", error))
-                            .await;
-                        break;
-                    }
+
+    let mut bucket = TokenBucket::new(limits.per_connection);
+    let connection = web_actor.connection.clone();
+    let outbound = web_actor.outbound.clone();
+    let mut last_seen = Instant::now();
+    let mut pings = tokio::time::interval_at(Instant::now() + heartbeat.interval, heartbeat.interval);
+    pings.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let web_client = WebClient::spawn_with_mailbox(web_actor, mailbox::unbounded());
+    let (sender, mut receiver) = socket.split();
+    let _ = web_client.tell(StreamMessage::Started("websocket")).await;
+
+    // the outbound half belongs to the writer until the actor closes it
+    let writer = tokio::spawn(forward_outbound(sender, outbound_rx, connection.clone()).in_current_span());
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = pings.tick() => {
+                if last_seen.elapsed() > heartbeat.timeout {
+                    counter!(monitoring::HEARTBEAT_TIMEOUTS).increment(1);
+                    warn!(silent = ?last_seen.elapsed(), "heartbeat timed out, closing");
+                    let _ = web_client.tell(actors::Disconnect((CloseCode::Away, "heartbeat timeout").into())).await;
+                    break;
+                }
+                let _ = outbound.push(actors::websocket::Message::Ping(String::new()));
+                continue;
+            }
+            _ = web_client.wait_for_shutdown() => break,
+        };
+        // Returns `None` if the stream has closed.
+        let Some(msg) = msg else {
+            break;
+        };
+        last_seen = Instant::now();
+        match msg {
+            Ok(Message::Text(utf8_bytes)) => {
+                counter!(monitoring::WEBSOCKET_BYTES, "direction" => "received").increment(utf8_bytes.len() as u64);
+                connection.count_received(utf8_bytes.len());
+                let max_message_size = match connection.is_pod() {
+                    true => limits.max_pod_message_size,
+                    false => limits.max_message_size,
+                };
+                if utf8_bytes.len() > max_message_size {
+                    counter!(monitoring::DROPPED_MESSAGES, "reason" => "too_big").increment(1);
+                    warn!(bytes = utf8_bytes.len(), "message too big, closing");
+                    let _ = web_client.tell(actors::Disconnect((CloseCode::Size, "message too big").into())).await;
+                    break;
                 }
-                Message::Binary(bytes) => {
-                    println!("Received bytes of length: {}", bytes.len());
-                    let result = socket
-                        .send(Message::Text(
-                            format!("Received bytes of length: {}", bytes.len()).into(),
-                        ))
-                        .await;
-                    if let Err(error) = result {
-                        println!("Error sending: {}", error);
-                        send_close_message(socket, 1011, &format!("Error occured: {}", error))
-                            .await;
-                        break;
-                    }
+                if !bucket.try_take() || !ip_limiter.try_take(who.ip()) {
+                    counter!(monitoring::DROPPED_MESSAGES, "reason" => "rate_limited").increment(1);
+                    warn!("rate limit exceeded, closing");
+                    let _ = web_client.tell(actors::Disconnect((CloseCode::Policy, "rate limit exceeded").into())).await;
+                    break;
                 }
-                Message::Close(c) => {
-                    if let Some(cf) = c {
-                        println!(
-                            ">>> {who} sent close with code {} and reason `{}`",
-                            cf.code, cf.reason
-                        );
-                    } else {
-                        println!(">>> {who} somehow sent close message without CloseFrame");
-                    }
+                if web_client.tell(StreamMessage::Next(utf8_bytes.to_string())).await.is_err() {
                     break;
                 }
-                _ => {}
             }
-        } else {
-            let error = msg.err().unwrap();
-            println!("Error receiving message: {:?}", error);
-            send_close_message(socket, 1011, &format!("Error occured: {}", error)).await;
-            break;
+            Ok(Message::Binary(bytes)) => {
+                warn!(bytes = bytes.len(), "binary message, closing");
+                counter!(monitoring::DROPPED_MESSAGES, "reason" => "binary").increment(1);
+                let _ = web_client.tell(actors::Disconnect((CloseCode::Unsupported, "binary messages are not supported").into())).await;
+                break;
+            }
+            Ok(Message::Close(c)) => {
+                match c {
+                    Some(cf) => debug!(code = cf.code, reason = %cf.reason, "closed by remote"),
+                    None => debug!("closed by remote without close frame"),
+                }
+                break;
+            }
+            Ok(_) => {}
+            Err(error) if is_too_big(&error) => {
+                counter!(monitoring::DROPPED_MESSAGES, "reason" => "too_big").increment(1);
+                warn!(%error, "message too big, closing");
+                let _ = web_client.tell(actors::Disconnect((CloseCode::Size, "message too big").into())).await;
+                break;
+            }
+            Err(error) => {
+                warn!(%error, "receiving failed");
+                let _ = web_client.tell(actors::Disconnect((CloseCode::Error, format!("Error occured: {}", error)).into())).await;
+                break;
+            }
         }
     }
+    let _ = web_client.tell(StreamMessage::Finished("websocket")).await;
+    web_client.wait_for_shutdown().await;
+    drop(outbound);
+    // the actor is gone, so the writer drains what is left and finishes
+    let _ = writer.await;
+    info!("disconnected");
 }
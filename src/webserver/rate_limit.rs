@@ -0,0 +1,67 @@
+use std::{collections::HashMap, net::IpAddr, sync::Mutex, time::Instant};
+
+use crate::config::RateLimit;
+
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+impl TokenBucket {
+    pub fn new(limit: RateLimit) -> Self {
+        TokenBucket {
+            limit,
+            tokens: limit.burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes one token, `false` if the bucket ran dry
+    pub fn try_take(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst);
+        self.last_refill = now;
+    }
+
+    fn is_full(&mut self) -> bool {
+        self.refill();
+        self.tokens >= self.limit.burst
+    }
+}
+
+/// One token bucket per remote address, shared by all its connections
+pub struct IpRateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<IpAddr, TokenBucket>>,
+}
+impl IpRateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        IpRateLimiter {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn try_take(&self, ip: IpAddr) -> bool {
+        self.buckets.lock().unwrap()
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(self.limit))
+            .try_take()
+    }
+
+    /// Forget addresses whose bucket refilled completely, they behave like new ones anyway
+    pub fn prune(&self) {
+        self.buckets.lock().unwrap().retain(|_, bucket| !bucket.is_full());
+    }
+}
@@ -0,0 +1,40 @@
+use std::{io, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+
+use axum_server::tls_rustls::RustlsConfig;
+use tokio::task::JoinHandle;
+
+/// Loads certificate chain and private key from PEM files
+pub async fn load_tls_config(cert_path: &Path, key_path: &Path) -> io::Result<RustlsConfig> {
+    // only the first call installs the provider, later ones keep it
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(cert_path, key_path).await
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Watches certificate and key for changes and swaps them into the running server.
+/// A broken pair is reported and retried on the next tick, meanwhile the previous certificate stays in use.
+/// Checks happen at most once a second.
+pub fn spawn_certificate_reloader(tls_config: RustlsConfig, cert_path: PathBuf, key_path: PathBuf, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_seen = (modified(&cert_path), modified(&key_path));
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_secs(1)));
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let current = (modified(&cert_path), modified(&key_path));
+            if current == last_seen {
+                continue;
+            }
+            match tls_config.reload_from_pem_file(&cert_path, &key_path).await {
+                Ok(()) => {
+                    last_seen = current;
+                    tracing::info!(cert = %cert_path.display(), "reloaded tls certificate");
+                }
+                Err(error) => tracing::warn!(cert = %cert_path.display(), %error, "reloading tls certificate failed, keeping the old one"),
+            }
+        }
+    })
+}
@@ -0,0 +1,109 @@
+// Dashboard on top of the /admin routes, the token only lives as long as the tab
+const REFRESH_MS = 2000;
+let token = sessionStorage.getItem('admin_token') || '';
+
+window.addEventListener('load', function() {
+    'use strict';
+    const state = document.querySelector('#admin_state');
+    const token_input = document.querySelector('#admin_token');
+    token_input.value = token;
+
+    document.querySelector('#admin_login').addEventListener('submit', event => {
+        event.preventDefault();
+        token = token_input.value;
+        sessionStorage.setItem('admin_token', token);
+        refresh();
+    });
+
+    document.querySelector('#admin_notice form').addEventListener('submit', event => {
+        event.preventDefault();
+        const input = document.querySelector('#notice_message');
+        admin_fetch('POST', '/admin/notice', { message: input.value })
+            .then(response => response.json())
+            .then(answer => {
+                state.innerText = `notice sent to ${answer.clients} clients`;
+                input.value = '';
+            })
+            .catch(show_error);
+    });
+
+    function admin_fetch(method, path, body) {
+        const init = { method, headers: { 'Authorization': 'Bearer ' + token } };
+        if (body !== undefined) {
+            init.headers['Content-Type'] = 'application/json';
+            init.body = JSON.stringify(body);
+        }
+        return fetch(path, init).then(response => {
+            if (!response.ok) {
+                throw new Error(`${method} ${path}: ${response.status} ${response.statusText}`);
+            }
+            return response;
+        });
+    }
+
+    function show_error(error) {
+        console.error(error);
+        state.innerText = error.message;
+    }
+
+    function action_button(label, method, path) {
+        const button = document.createElement('button');
+        button.innerText = label;
+        button.addEventListener('click', _ => {
+            admin_fetch(method, path).then(refresh).catch(show_error);
+        });
+        return button;
+    }
+
+    function row(cells, button) {
+        const tr = document.createElement('tr');
+        for (const cell of cells) {
+            const td = document.createElement('td');
+            td.innerText = cell === null || cell === undefined ? '' : cell;
+            tr.appendChild(td);
+        }
+        const td = document.createElement('td');
+        td.appendChild(button);
+        tr.appendChild(td);
+        return tr;
+    }
+
+    function render(overview) {
+        const pods = document.querySelector('#admin_pods tbody');
+        pods.replaceChildren(...overview.pods.map(pod => row(
+            [pod.id, pod.name, pod.paths, new Date(pod.last_modified).toLocaleString()],
+            action_button('Remove', 'DELETE', `/admin/pods/${pod.id}`)
+        )));
+        const clients = document.querySelector('#admin_clients tbody');
+        clients.replaceChildren(...overview.clients.map(client => row(
+            [
+                client.id,
+                client.remote,
+                client.user_agent,
+                new Date(client.connected_at).toLocaleString(),
+                client.is_pod ? 'yes' : '',
+                client.share_link,
+                `${client.received_messages} msgs / ${client.received_bytes} B`,
+                `${client.sent_messages} msgs / ${client.sent_bytes} B`,
+            ],
+            action_button('Disconnect', 'DELETE', `/admin/clients/${client.id}`)
+        )));
+    }
+
+    function refresh() {
+        if (!token) {
+            state.innerText = 'enter the ADMIN_TOKEN of the server';
+            return;
+        }
+        admin_fetch('GET', '/admin/connections')
+            .then(response => response.json())
+            .then(overview => {
+                render(overview);
+                state.innerText = `${overview.clients.length} clients, ${overview.pods.length} pods at ${new Date().toLocaleTimeString()}`;
+            })
+            .catch(show_error);
+    }
+
+    refresh();
+    setInterval(refresh, REFRESH_MS);
+});
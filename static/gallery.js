@@ -4,6 +4,7 @@ const Gallery = {
     shared_files: [],
     message_handler: _ => {},
     reconnect_handler: _ => {},
+    session_handler: _ => {},
     connected: false,
 };
 
@@ -11,13 +12,32 @@ window.addEventListener('load', function() {
     'use strict';
     let galleries = [];
     let selected_gallery_id = undefined;
+    // `/` separated, '' is the root of the gallery
+    let selected_folder = '';
+    let folder_listing = undefined;
+    let folder_listing_requested = false;
+    // pages of the listing on its way, it replaces `folder_listing` once complete
+    let folder_listing_pages = undefined;
+    // while not empty the view shows the hits of all galleries instead
+    let search_query = '';
+    let search_results = undefined;
+    // of galleries, folder listings and search hits
+    let sort_order = {"key": "Curated", "direction": "Ascending"};
     const image_cache /*: Map<id, Map<String, CachedPicture>> */ = {};
     const galleries_element = document.querySelector('#galleries');
     const galleries_list = galleries_element.querySelector('ul');
     const gallery_view = galleries_element.querySelector('div');
+    const search_input = document.querySelector('#search');
+    search_input.value = '';
+    search_input.addEventListener('input', _ => search(search_input.value.trim()));
+    const sort_key = document.querySelector('#sort_key');
+    const sort_direction = document.querySelector('#sort_direction');
+    [sort_key, sort_direction].forEach(select => select.addEventListener('change', change_sort_order));
+    change_sort_order();
 
     Gallery.message_handler = message_handler;
     Gallery.reconnect_handler = reconnect_handler;
+    Gallery.session_handler = session_handler;
     Gallery.update_ui = update_ui;
 
     update_ui();
@@ -25,32 +45,95 @@ window.addEventListener('load', function() {
 
 
 function reconnect_handler(online) {
-    if (online) {
-        ws.send_object({"ClientRequest":"ListAllPods"});
+    // everything is kept until the server tells whether our session was resumed
+    Gallery.connected = online;
+}
+
+function session_handler(resumed) {
+    if (resumed) {
+        // missed updates are replayed, images and listings that were on their way are requested again
+        folder_listing = undefined;
+        folder_listing_requested = false;
+        folder_listing_pages = undefined;
+        update_view();
     } else {
         clear_image_cache();
 
         // drop other data
         galleries = [];
+        // paths are only fetched for the gallery that gets opened
+        ws.send_object({"ClientRequest":"ListPodSummaries"});
     }
+    // hits sent while offline are lost
+    search(search_query);
 }
 
 function message_handler(message) {
     if (typeof message.Pods !== 'undefined') {
         // Sometimes NewPod messages arrive before Pods message on reconnect
+        message.Pods.forEach(p => {
+            p.path_count = p.paths.length;
+            p.loaded = true;
+        });
         galleries = galleries.concat(message.Pods);
         clear_image_cache();
         galleries.forEach(p => image_cache[p.id] = []);
         update_ui();
     } else
+    if (typeof message.PodSummaries !== 'undefined') {
+        message.PodSummaries.forEach(summary => {
+            const pod_index = indexOfPod(summary.id);
+            if (pod_index === undefined) {
+                summary.paths = [];
+                summary.loaded = false;
+                galleries.push(summary);
+                image_cache[summary.id] = [];
+            } else if (!galleries[pod_index].loaded) {
+                Object.assign(galleries[pod_index], summary, {resyncing: false});
+            }
+        });
+        update_ui();
+        update_view();
+    } else
+    if (typeof message.FolderListing !== 'undefined') {
+        const listing = message.FolderListing;
+        if (listing.gallery_id === selected_gallery_id && listing.folder === selected_folder
+            && listing.sort.key === sort_order.key && listing.sort.direction === sort_order.direction) {
+            if (folder_listing_pages === undefined) {
+                folder_listing_pages = listing;
+            } else {
+                // pages of a newer version than the first get the listing asked for again once shown
+                folder_listing_pages.images = folder_listing_pages.images.concat(listing.images);
+                folder_listing_pages.metadata = Object.assign(folder_listing_pages.metadata || {}, listing.metadata);
+            }
+            if (listing.next !== null) {
+                request_folder_listing(listing.gallery_id, listing.next);
+                return;
+            }
+            folder_listing = folder_listing_pages;
+            folder_listing_pages = undefined;
+            folder_listing_requested = false;
+            update_view();
+        }
+    } else
+    if (typeof message.SearchResults !== 'undefined') {
+        // answers to queries typed over since are dropped
+        if (message.SearchResults.query === search_query) {
+            search_results = message.SearchResults;
+            update_view();
+        }
+    } else
     if (typeof message.NewPod !== 'undefined') {
         message.NewPod.paths = message.NewPod.paths || [];
+        message.NewPod.version = 0;
+        message.NewPod.path_count = 0;
+        message.NewPod.loaded = true;
         galleries.push(message.NewPod);
         image_cache[message.NewPod.id] = [];
         update_ui();
     } else
     if (typeof message.UnknownPod !== 'undefined') {
-        Gallery.reconnect_handler();
+        session_handler(false);
     } else
     if (typeof message.PodGone !== 'undefined') {
         galleries = galleries.filter(x => x.id !== message.PodGone);
@@ -71,6 +154,10 @@ function message_handler(message) {
 
         galleries[pod_index].paths = message.PodUpdatePaths.paths;
         galleries[pod_index].last_modified = last_modified;
+        galleries[pod_index].version = message.PodUpdatePaths.version;
+        galleries[pod_index].path_count = message.PodUpdatePaths.paths.length;
+        galleries[pod_index].loaded = true;
+        galleries[pod_index].resyncing = false;
 
         // prepare for Cache Layer
         if (replace_images || typeof image_cache[id] !== 'object') {
@@ -82,12 +169,61 @@ function message_handler(message) {
             update_view();
         }
     } else
+    if (typeof message.PathsAdded !== 'undefined' || typeof message.PathsRemoved !== 'undefined') {
+        const delta = message.PathsAdded || message.PathsRemoved;
+        const pod_index = indexOfPod(delta.id);
+        if (pod_index === undefined) {
+            return;
+        }
+        const gallery = galleries[pod_index];
+        if (delta.version !== gallery.version + 1) {
+            // an update went missing, the whole listing arrives as PodUpdatePaths, or the counts as PodSummaries
+            if (!gallery.resyncing) {
+                gallery.resyncing = true;
+                ws.send_object(gallery.loaded
+                    ? {"ClientRequest":{"ListPodStructure": delta.id}}
+                    : {"ClientRequest":"ListPodSummaries"});
+            }
+            return;
+        }
+        gallery.version = delta.version;
+        gallery.last_modified = new Date(delta.last_modified);
+        if (typeof message.PathsAdded !== 'undefined') {
+            gallery.path_count += delta.paths.length;
+            if (gallery.loaded) {
+                gallery.paths = gallery.paths.concat(delta.paths).sort();
+            }
+        } else {
+            gallery.path_count -= delta.paths.length;
+            if (gallery.loaded) {
+                gallery.paths = gallery.paths.filter(path => !delta.paths.includes(path));
+            }
+            delta.paths.forEach(path => delete (image_cache[delta.id] || {})[path]);
+        }
+
+        update_ui();
+        if (delta.id === selected_gallery_id) {
+            update_view();
+        }
+    } else
     if (typeof message.DeliverImage !== 'undefined') {
         const id = message.DeliverImage.gallery_id;
         image_cache[id][message.DeliverImage.path].save_blob(message.DeliverImage.blob);
-        if (id === selected_gallery_id) {
+        if (id === selected_gallery_id || search_query !== '') {
             update_view();
         }
+    } else
+    if (typeof message.ImageRejected !== 'undefined') {
+        const id = message.ImageRejected.gallery_id;
+        const cp = (image_cache[id] || {})[message.ImageRejected.path];
+        if (cp !== undefined) {
+            cp.reject(message.ImageRejected.reason);
+        }
+    } else
+    if (typeof message.ServerNotice !== 'undefined') {
+        const notice = document.querySelector('#server_notice');
+        notice.innerText = message.ServerNotice.message;
+        notice.hidden = false;
     } else {
         error(['client_response unimplemented', message]);
     }
@@ -107,7 +243,7 @@ function update_ui() {
     if (galleries.length === 0) {
         galleries_list.innerHTML = "<h3>No Gallery connected</h3>";
     } else {
-        galleries.sort((a, b) => a.name.localeCompare(b.name));
+        galleries.sort(compare_galleries);
         galleries_list.innerHTML = '';
 
         galleries.forEach(x => {
@@ -116,7 +252,7 @@ function update_ui() {
             const text = document.createElement('div');
 
             title.innerText = x.name || `unnamed Gallery #${x.id}`;
-            text.innerHTML = x.paths.length === 0 ? 'No images' : `${x.paths.length} images <br> last last_modified: ${x.last_modified}`;
+            text.innerHTML = x.path_count === 0 ? 'No images' : `${x.path_count} images <br> last last_modified: ${x.last_modified}`;
 
             div.appendChild(title);
             div.appendChild(text);
@@ -124,7 +260,7 @@ function update_ui() {
             div.addEventListener('click', ev => {
                 // switch to that gallery
                 selected_gallery_id = x.id;
-                update_view();
+                open_folder('');
             });
 
             galleries_list.appendChild(div);
@@ -133,8 +269,37 @@ function update_ui() {
     }
 }
 
+function change_sort_order() {
+    sort_order = {"key": sort_key.value, "direction": sort_direction.value};
+    folder_listing = undefined;
+    folder_listing_requested = false;
+    folder_listing_pages = undefined;
+    update_ui();
+    search(search_query);
+}
+
+/// Galleries in `sort_order` as far as their summaries tell, by name otherwise
+function compare_galleries(a, b) {
+    let order;
+    if (sort_order.key === 'Modified') {
+        order = new Date(a.last_modified) - new Date(b.last_modified);
+    } else if (sort_order.key === 'Size') {
+        order = a.path_count - b.path_count;
+    } else {
+        order = 0;
+    }
+    if (order === 0) {
+        order = a.name.localeCompare(b.name, undefined, {"numeric": sort_order.key === 'Natural'});
+    }
+    return sort_order.direction === 'Descending' ? -order : order;
+}
+
 /// Update the Gallery
 function update_view() {
+    if (search_query !== '') {
+        update_search_view();
+        return;
+    }
     if (selected_gallery_id === undefined) {
         gallery_view.innerHTML = '<div class="centered">No Gallery selected</div>';
         return;
@@ -146,8 +311,28 @@ function update_view() {
         return;
     }
 
+    const gallery = galleries[i];
+    if (folder_listing === undefined || folder_listing.version < gallery.version) {
+        // the paths changed since, the current listing stays until the new one arrives
+        if (!folder_listing_requested && Gallery.connected) {
+            folder_listing_requested = true;
+            request_folder_listing(gallery.id, null);
+        }
+        if (folder_listing === undefined) {
+            gallery_view.innerHTML = '<div class="centered">Loading…</div>';
+            return;
+        }
+    }
+
     gallery_view.innerHTML = '';
-    galleries[i].paths.forEach(path => {
+    const parent = selected_folder.includes('/') ? selected_folder.substring(0, selected_folder.lastIndexOf('/')) : '';
+    if (selected_folder !== '') {
+        gallery_view.appendChild(folder_entry('..', parent));
+    }
+    folder_listing.folders.forEach(name => {
+        gallery_view.appendChild(folder_entry(name + '/', selected_folder === '' ? name : selected_folder + '/' + name));
+    });
+    folder_listing.images.forEach(path => {
         let cp = image_cache[selected_gallery_id][path];
         if (cp === undefined) {
             cp = new CachedPicture(selected_gallery_id, path);
@@ -155,8 +340,62 @@ function update_view() {
         } else {
             cp.reappend_to_gallery();
         }
+        cp.set_metadata((folder_listing.metadata || {})[path]);
+    });
+}
+
+function search(query) {
+    search_query = query;
+    search_results = undefined;
+    if (query !== '' && Gallery.connected) {
+        ws.send_object({"ClientRequest":{"Search": {"query": query, "sort": sort_order}}});
+    }
+    update_view();
+}
+
+function update_search_view() {
+    if (search_results === undefined) {
+        gallery_view.innerHTML = '<div class="centered">Searching…</div>';
+        return;
+    }
+    gallery_view.innerHTML = '';
+    if (search_results.hits.length === 0) {
+        gallery_view.innerHTML = '<div class="centered">Nothing found</div>';
+    }
+    search_results.hits.forEach(hit => {
+        if (typeof image_cache[hit.gallery_id] !== 'object') {
+            image_cache[hit.gallery_id] = [];
+        }
+        const cp = image_cache[hit.gallery_id][hit.path];
+        if (cp === undefined) {
+            image_cache[hit.gallery_id][hit.path] = new CachedPicture(hit.gallery_id, hit.path);
+        } else {
+            cp.reappend_to_gallery();
+        }
     });
+}
 
+/// The page of the selected folder following `after`, the first one for `null`
+function request_folder_listing(gallery_id, after) {
+    ws.send_object({"ClientRequest":{"ListFolder": {"gallery_id": gallery_id, "folder": selected_folder, "sort": sort_order, "after": after}}});
+}
+
+function open_folder(folder) {
+    search_input.value = '';
+    search_query = '';
+    selected_folder = folder;
+    folder_listing = undefined;
+    folder_listing_requested = false;
+    folder_listing_pages = undefined;
+    update_view();
+}
+
+function folder_entry(label, folder) {
+    const div = document.createElement('div');
+    div.className = 'folder';
+    div.innerText = label;
+    div.addEventListener('click', _ => open_folder(folder));
+    return div;
 }
 
 function CachedPicture(id, path) {
@@ -169,9 +408,9 @@ function CachedPicture(id, path) {
     this.div.appendChild(this.img);
 
 
-    const text = document.createElement('div');
-    text.innerText = path;
-    this.div.appendChild(text);
+    this.text = document.createElement('div');
+    this.text.innerText = path;
+    this.div.appendChild(this.text);
 
     this.reappend_to_gallery();
 }
@@ -182,6 +421,9 @@ CachedPicture.prototype = {
         gallery_view.appendChild(this.div);
     },
     cache_update: function() {
+        if (this.rejected) {
+            return;
+        }
         if (this.blob === undefined) {
             ws.send_object({"ClientRequestAsync": {
                 "RequestImage": {
@@ -196,6 +438,22 @@ CachedPicture.prototype = {
     save_blob: function(blob) {
         this.blob = blob;
     },
+    set_metadata: function(metadata) {
+        if (metadata === undefined || this.rejected) {
+            return;
+        }
+        // reserves the space before the image arrives
+        if (metadata.width && metadata.height) {
+            this.img.style.aspectRatio = `${metadata.width} / ${metadata.height}`;
+        }
+        const details = [metadata.taken_at && metadata.taken_at.replace('T', ' '), metadata.camera].filter(x => x);
+        this.text.innerText = details.length === 0 ? this.path : `${this.path}\n${details.join(', ')}`;
+    },
+    reject: function(reason) {
+        // do not ask again, the pod would deliver the same thing
+        this.rejected = true;
+        this.text.innerText = `${this.path} (rejected: ${reason})`;
+    },
 };
 
 function clear_image_cache() {
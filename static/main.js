@@ -4,7 +4,16 @@ const log = console.log
 
 let html_logger = _ => {};
 let ws = undefined;
+// the browser answers pings on its own, these also let us notice a server that went silent
+const HEARTBEAT_INTERVAL_MS = 15000;
+const HEARTBEAT_TIMEOUT_MS = 45000;
+let last_message = Date.now();
+// sessions opened through a share link only get to see the shared gallery
+const share_token = new URLSearchParams(location.search).get('share');
+// told by the server on every connect, reconnecting with it keeps our id and replays what we missed
+let session_token = undefined;
 setup_ws();
+setInterval(heartbeat, HEARTBEAT_INTERVAL_MS);
 
 WebSocket.prototype.send_object = function(obj) {
     const str = JSON.stringify(obj);
@@ -45,10 +54,25 @@ function make_logger(element, state_element) {
     }
 }
 
+function heartbeat() {
+    if (ws.readyState !== ws.OPEN) {
+        return;
+    }
+    if (Date.now() - last_message > HEARTBEAT_TIMEOUT_MS) {
+        html_logger('! server stayed silent, reconnecting');
+        ws.close();
+        return;
+    }
+    ws.send(JSON.stringify({"ClientRequest":"Heartbeat"}));
+}
+
 function setup_ws() {
     // automatically enable WebSocket over TLS
-    //ws = new WebSocket('ws'+(location.protocol.indexOf('https') === 0 ? 's' : '')+'://'+location.host+'/ws/');
-    ws = new WebSocket('ws'+'://'+location.host+'/ws');
+    let endpoint = share_token ? '/share/' + encodeURIComponent(share_token) : '/ws';
+    if (session_token !== undefined) {
+        endpoint += '?resume=' + encodeURIComponent(session_token);
+    }
+    ws = new WebSocket('ws'+(location.protocol.indexOf('https') === 0 ? 's' : '')+'://'+location.host+endpoint);
 
     ws.onclose = _ => {
         html_logger(`-.-.-.-.-.-.-.-.-.-.-.-.-.-.-.- LOST WebSocket Connection -.-.-.-.-.-.-.-.-.-.-.-.-.-.-.-`);
@@ -63,9 +87,17 @@ function setup_ws() {
     }
 
     ws.onmessage = msg => {
+        last_message = Date.now();
+        if (msg.data === '{"ClientResponse":"Heartbeat"}') {
+            return;
+        }
         log(msg.data.substr(0, 42));
         html_logger('< ' + msg.data.substr(0, 256));
         const data = JSON.parse(msg.data);
+        if (typeof data.ClientResponse !== 'undefined' && typeof data.ClientResponse.Session !== 'undefined') {
+            session_token = data.ClientResponse.Session.token;
+            Gallery.session_handler(data.ClientResponse.Session.resumed);
+        } else
         if (typeof data.ClientResponse !== 'undefined') {
             Gallery.message_handler(data.ClientResponse);
         } else
@@ -78,6 +110,7 @@ function setup_ws() {
     };
 
     ws.onopen = _ => {
+        last_message = Date.now();
         log('Connected.');
         html_logger(`-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+- ESTABLISHED WebSocket Connection -+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-`);
 
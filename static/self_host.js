@@ -22,6 +22,9 @@ window.addEventListener('load', function() {
         registerSelf();
     }, false);
 
+    const share_links_list = document.querySelector('#pod_share_links ul');
+    document.querySelector('#share_create').addEventListener("click", createShareLink, false);
+
     Pod.message_handler = message_handler;
     Pod.reconnect_handler = reconnect_handler;
 
@@ -66,6 +69,27 @@ function message_handler(message) {
                 }
             }});
         }
+    } else
+    if (typeof message.ShareLink !== 'undefined') {
+        addShareLink(message.ShareLink);
+    } else
+    if (typeof message.ShareLinkRevoked !== 'undefined') {
+        const item = share_links_list.querySelector(`[data-token="${message.ShareLinkRevoked.token}"]`);
+        if (item) {
+            item.remove();
+        }
+    } else
+    if (typeof message.ShareLinkDenied !== 'undefined') {
+        html_logger('share link denied: ' + message.ShareLinkDenied.reason);
+    } else
+    if (typeof message.ImageRejected !== 'undefined') {
+        console.error(['server rejected image', message.ImageRejected]);
+        html_logger(`image ${message.ImageRejected.path} rejected: ${message.ImageRejected.reason}`);
+    } else
+    if (typeof message.PathsRejected !== 'undefined') {
+        for (const rejected of message.PathsRejected.paths) {
+            html_logger(`path ${rejected.path} left out: ${rejected.reason}`);
+        }
     }
     else {
         error(['pod_response unimplemented', message]);
@@ -81,7 +105,43 @@ function reconnect_handler(connected) {
 
     if (connected === false) {
         Pod.registered = false;
+        // the server forgets our share links together with the pod
+        share_links_list.innerHTML = '';
+    }
+}
+
+function createShareLink() {
+    if (!Pod.registered) {
+        html_logger('share your gallery before creating share links');
+        return;
     }
+    const hours = parseInt(document.querySelector('#share_hours').value, 10) || 24;
+    const max_views = parseInt(document.querySelector('#share_max_views').value, 10);
+    ws.send_object({"PodRequest":{
+        "CreateShareLink":{
+            "expires_at": new Date(Date.now() + hours * 3600 * 1000).toISOString(),
+            "max_views": isNaN(max_views) ? null : max_views,
+        }
+    }});
+}
+
+function addShareLink(share_link) {
+    const item = document.createElement('li');
+    const link = document.createElement('a');
+    const revoke = document.createElement('button');
+
+    item.dataset.token = share_link.token;
+    link.href = location.origin + location.pathname + '?share=' + encodeURIComponent(share_link.token);
+    link.innerText = link.href;
+    revoke.innerText = 'Revoke';
+    revoke.addEventListener('click', _ => {
+        ws.send_object({"PodRequest":{"RevokeShareLink":{"token": share_link.token}}});
+    });
+
+    item.appendChild(link);
+    item.appendChild(document.createTextNode(` expires ${new Date(share_link.expires_at).toLocaleString()} `));
+    item.appendChild(revoke);
+    share_links_list.appendChild(item);
 }
 
 
@@ -93,6 +153,8 @@ function handleFiles() {
     }
 
     let replace_images = false;
+    const added = [];
+    const added_metadata = {};
     for (let i = 0, numFiles = files.length; i < numFiles; ++i) {
         const file = files[i];
         if (file.type.indexOf('image/') === 0) {
@@ -102,12 +164,19 @@ function handleFiles() {
                 replace_images = true;
             }
             Pod.shared_files.push(file);
+            added.push(file.name);
+            added_metadata[file.name] = fileMetadata(file);
         } else {
             console.error(['Pod::handleFiles() invalid file type', file]);
         }
     }
 
-    publishPictures(replace_images);
+    if (!replace_images && Pod.connected && Pod.registered) {
+        // only the new files, the server knows about the others
+        ws.send_object({"PodRequest": {"PathsAdded": { "paths": added, "metadata": added_metadata } } });
+    } else {
+        publishPictures(replace_images);
+    }
     regenPreview();
 }
 
@@ -125,10 +194,17 @@ function publishPictures(replace_images) {
             registerSelf();
         }
         const paths = Pod.shared_files.reduce((b, cur) => { b.push(cur.name); return b }, []);
-        ws.send_object({"PodRequest": {"UpdatePaths": { "paths": paths, "replace_images": replace_images, } } });
+        const metadata = {};
+        Pod.shared_files.forEach(file => metadata[file.name] = fileMetadata(file));
+        ws.send_object({"PodRequest": {"UpdatePaths": { "paths": paths, "replace_images": replace_images, "metadata": metadata, } } });
     }
 }
 
+/// Size, type and modification time as the browser knows them, dimensions and EXIF data would need the file decoded
+function fileMetadata(file) {
+    return {"size": file.size, "mime": file.type, "modified": new Date(file.lastModified).toISOString()};
+}
+
 function updatePreviewTitle() {
     const title = document.querySelector('#pod_preview h1');
     const name = normalized_title();
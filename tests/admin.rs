@@ -0,0 +1,83 @@
+mod common;
+
+use infra::actors::{BroadcastNotice, KickClient, ListConnections, UnsubscribePod, websocket::CloseCode};
+use infra::protocols::{ClientRequest, JsonProtocol, PodRequest};
+use serde_json::json;
+
+use common::TestServer;
+
+#[tokio::test]
+async fn connections_are_listed_with_their_traffic() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (_pod, id) = server.pod("holiday").await;
+    client.recv().await;
+
+    let overview = server.hub.ask(ListConnections).await.unwrap();
+    assert_eq!(overview.pods.len(), 1);
+    assert_eq!((overview.pods[0].id, overview.pods[0].name.as_str()), (id, "holiday"));
+    assert_eq!(overview.clients.len(), 2);
+    let pod = overview.clients.iter().find(|client| client.id == id).expect("pod is a client as well");
+    assert!(pod.is_pod);
+    assert_eq!(pod.remote.ip(), server.addr.ip());
+    assert_eq!(pod.received_messages, 1);
+    assert!(pod.sent_messages >= 2 && pod.sent_bytes > 0, "Registered and NewPod went out, got {pod:?}");
+}
+
+#[tokio::test]
+async fn notices_reach_every_client() {
+    let server = TestServer::start().await;
+    let mut clients = vec![server.client().await, server.client().await];
+
+    let reached = server.hub.ask(BroadcastNotice("restarting in 5 minutes".into())).await.unwrap();
+    assert_eq!(reached, 2);
+    for client in &mut clients {
+        assert_eq!(client.recv().await, json!({"ClientResponse": {"ServerNotice": {"message": "restarting in 5 minutes"}}}));
+    }
+}
+
+#[tokio::test]
+async fn kicked_clients_are_disconnected() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let id = server.hub.ask(ListConnections).await.unwrap().clients[0].id;
+
+    assert!(server.hub.ask(KickClient(id)).await.unwrap());
+    assert_eq!(client.expect_close().await, Some(u16::from(CloseCode::Policy)));
+    assert!(!server.hub.ask(KickClient(id + 100)).await.unwrap());
+}
+
+#[tokio::test]
+async fn removed_pods_may_register_again() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+
+    assert!(server.hub.ask(UnsubscribePod(id)).await.unwrap());
+    for socket in [&mut client, &mut pod] {
+        assert_eq!(socket.recv().await, json!({"ClientResponse": {"PodGone": id}}));
+    }
+    assert!(!server.hub.ask(UnsubscribePod(id)).await.unwrap());
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::RegisterSelf { proposed_id: None, name: "holiday".into() })).await;
+    assert_eq!(pod.recv_unordered(2).await, vec![
+        json!({"ClientResponse": {"NewPod": {"id": id, "name": "holiday"}}}),
+        json!({"PodResponse": {"Registered": {"global_id": id}}}),
+    ]);
+}
+
+#[tokio::test]
+async fn removed_pods_lose_the_bigger_message_limit() {
+    let server = TestServer::start().await;
+    let (mut pod, id) = server.pod("holiday").await;
+
+    assert!(server.hub.ask(UnsubscribePod(id)).await.unwrap());
+    assert_eq!(pod.recv().await, json!({"ClientResponse": {"PodGone": id}}));
+    pod.send(&JsonProtocol::ClientRequest(ClientRequest::Heartbeat)).await;
+    assert_eq!(pod.recv().await, json!({"ClientResponse": "Heartbeat"}));
+    assert!(!server.hub.ask(ListConnections).await.unwrap().clients[0].is_pod);
+
+    pod.send_text(&"x".repeat(common::LIMITS.max_message_size * 2)).await;
+    assert_eq!(pod.expect_close().await, Some(u16::from(CloseCode::Size)));
+}
@@ -0,0 +1,150 @@
+//! Runs the server binary as separate nodes, every process can only join one swarm
+mod common;
+
+use std::{process::{Child, Command, Stdio}, time::Duration};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use infra::cluster;
+use infra::protocols::{ClientRequest, ClientRequestAsync, GalleryPath, JsonProtocol, PathMetadata, PodRequest};
+use rand::RngCore;
+use serde_json::{Value, json};
+
+use common::TestSocket;
+
+/// Peers look each other up every sync interval, a few of them may pass until they find each other
+const CONVERGENCE: Duration = Duration::from_secs(30);
+
+struct Node {
+    process: Child,
+    port: u16,
+    cluster_port: u16,
+}
+impl Node {
+    fn key() -> String {
+        let mut key = [0u8; 32];
+        rand::rng().fill_bytes(&mut key);
+        hex::encode(key)
+    }
+
+    fn peer_id(key: &str) -> String {
+        cluster::keypair_from_hex(key).unwrap().public().to_peer_id().to_string()
+    }
+
+    /// Starts a node trusting the nodes with the keys of `allowed`, it dials those in `dial`
+    async fn start(node_id: u16, key: String, allowed: &[&str], dial: &[&Node], extra_env: &[(&str, &str)]) -> Node {
+        let (port, cluster_port) = (free_port(), free_port());
+        let peers: Vec<String> = dial.iter().map(|node| format!("/ip4/127.0.0.1/tcp/{}", node.cluster_port)).collect();
+        let allowed_peers: Vec<String> = allowed.iter().map(|key| Node::peer_id(key)).collect();
+        let process = Command::new(env!("CARGO_BIN_EXE_image_gallery_server"))
+            .env_clear()
+            .env("HOST_IP", "127.0.0.1")
+            .env("PORT", port.to_string())
+            .env("FRONTEND_DIR", "./static")
+            .env("FILE_STORE_DIR", std::env::temp_dir())
+            .env("RUST_LOG", "warn")
+            .env("CLUSTER_NODE_ID", node_id.to_string())
+            .env("CLUSTER_LISTEN", format!("/ip4/127.0.0.1/tcp/{cluster_port}"))
+            .env("CLUSTER_PEERS", peers.join(","))
+            .env("CLUSTER_KEY", key)
+            .env("CLUSTER_ALLOWED_PEERS", allowed_peers.join(","))
+            .env("CLUSTER_SYNC_INTERVAL", "1")
+            .envs(extra_env.iter().copied())
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("unable to start server");
+        let node = Node { process, port, cluster_port };
+        tokio::time::timeout(CONVERGENCE, async {
+            while tokio_tungstenite::connect_async(node.url()).await.is_err() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }).await.expect("server did not come up");
+        node
+    }
+
+    fn url(&self) -> String {
+        format!("ws://127.0.0.1:{}/ws", self.port)
+    }
+
+    async fn client(&self) -> TestSocket {
+        let mut client = TestSocket::connect(&self.url()).await;
+        client.send(&JsonProtocol::ClientRequest(ClientRequest::ListAllPods)).await;
+        client.recv().await;
+        client
+    }
+
+    async fn pod(&self, name: &str) -> (TestSocket, u64) {
+        let mut pod = TestSocket::connect(&self.url()).await;
+        pod.send(&JsonProtocol::PodRequest(PodRequest::RegisterSelf { proposed_id: None, name: name.into() })).await;
+        let registered = pod.recv().await;
+        let id = registered["PodResponse"]["Registered"]["global_id"].as_u64()
+            .unwrap_or_else(|| panic!("expected Registered, got {registered}"));
+        (pod, id)
+    }
+}
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Skips everything else until a message matching `wanted` arrives
+async fn wait_for(socket: &mut TestSocket, wanted: impl Fn(&Value) -> bool) -> Value {
+    loop {
+        let message = socket.recv_within(CONVERGENCE).await;
+        if wanted(&message) {
+            return message;
+        }
+    }
+}
+
+fn png_data_url() -> String {
+    let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13, b'I', b'H', b'D', b'R'];
+    format!("data:image/png;base64,{}", STANDARD.encode(png))
+}
+
+#[tokio::test]
+async fn nodes_share_pods_and_only_with_allowed_peers() {
+    let (key_a, key_b) = (Node::key(), Node::key());
+    let node_a = Node::start(1, key_a.clone(), &[&key_b], &[], &[]).await;
+    // does not take png, which the first node does
+    let node_b = Node::start(2, key_b, &[&key_a], &[&node_a], &[("ALLOWED_IMAGE_TYPES", "image/jpeg")]).await;
+    // trusts the first node, which does not trust it back
+    let intruder = Node::start(3, Node::key(), &[&key_a], &[&node_a], &[]).await;
+
+    let mut client = node_b.client().await;
+    let mut watcher = node_a.client().await;
+    let (mut pod, pod_id) = node_a.pod("holiday").await;
+    let (_intruding_pod, intruding_id) = intruder.pod("phishing").await;
+    assert_eq!(cluster::node_of(pod_id), 1);
+
+    let new_pod = json!({"ClientResponse": {"NewPod": {"id": pod_id, "name": "holiday"}}});
+    wait_for(&mut client, |message| *message == new_pod).await;
+
+    let path = GalleryPath::new("a.png").unwrap();
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: vec![path.clone()], replace_images: false, curated: false, metadata: PathMetadata::new() })).await;
+    let update = wait_for(&mut client, |message| message["ClientResponse"]["PodUpdatePaths"].is_object()).await;
+    assert_eq!(update["ClientResponse"]["PodUpdatePaths"]["paths"], json!(["a.png"]));
+
+    client.send(&JsonProtocol::ClientRequestAsync(ClientRequestAsync::RequestImage { client_id: 0, gallery_id: pod_id, path: path.clone() })).await;
+    let request = wait_for(&mut pod, |message| message["PodResponse"]["RequestImage"].is_object()).await;
+    assert_eq!(request["PodResponse"]["RequestImage"]["client_id"], json!(client.id));
+    pod.send(&JsonProtocol::PodRequest(PodRequest::DeliverImage { client_id: client.id, path, blob: png_data_url() })).await;
+    assert_eq!(client.recv_within(CONVERGENCE).await, json!({"ClientResponse": {"ImageRejected": {
+        "gallery_id": pod_id,
+        "path": "a.png",
+        "reason": "image/png is not allowed",
+    }}}));
+
+    // plenty of sync intervals for the intruder to push its pod
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    watcher.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodSummaries)).await;
+    let summaries = wait_for(&mut watcher, |message| message["ClientResponse"]["PodSummaries"].is_array()).await;
+    let ids: Vec<u64> = summaries["ClientResponse"]["PodSummaries"].as_array().unwrap().iter().map(|pod| pod["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![pod_id]);
+    assert_ne!(intruding_id, pod_id);
+}
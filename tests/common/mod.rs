@@ -0,0 +1,206 @@
+//! Runs the server inside the test process and talks to it over real websockets, like browsers and pods do.
+#![allow(dead_code)]
+
+use std::{net::SocketAddr, time::Duration};
+
+use futures_util::{SinkExt, StreamExt};
+use infra::{
+    actors::Hub,
+    config::{ConnectionLimits, RateLimit},
+    protocols::{ClientRequest, JsonProtocol, PodId, PodRequest},
+    webserver::{self, AppState},
+};
+use kameo::actor::ActorRef;
+use serde_json::{Value, json};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+
+/// How long to wait for a message that must arrive
+pub const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait before deciding that nothing else arrives
+pub const SILENCE: Duration = Duration::from_millis(200);
+
+/// High enough that no test runs into them
+pub const LIMITS: ConnectionLimits = ConnectionLimits {
+    per_connection: RateLimit { per_second: 10_000.0, burst: 10_000.0 },
+    per_ip: RateLimit { per_second: 10_000.0, burst: 10_000.0 },
+    max_message_size: 1024 * 1024,
+    max_pod_message_size: 4 * 1024 * 1024,
+    max_queued_bytes: 64 * 1024 * 1024,
+    slow_consumer_grace: Duration::from_secs(60),
+};
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub hub: ActorRef<Hub>,
+}
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::start_with(Hub::default(), LIMITS).await
+    }
+
+    pub async fn start_with(hub: Hub, limits: ConnectionLimits) -> Self {
+        Self::serve(AppState::new(hub.start(), 0, limits)).await
+    }
+
+    /// Serves on an ephemeral port until the test ends
+    pub async fn serve(state: AppState) -> Self {
+        let hub = state.actor_ref.clone();
+        let app = webserver::router(state, "./static/");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("unable to bind test server");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+        });
+        TestServer { addr, hub }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("ws://{}{}", self.addr, path)
+    }
+
+    pub async fn connect(&self) -> TestSocket {
+        self.connect_to("/ws").await
+    }
+
+    /// Consumes the `Session` every socket is greeted with
+    pub async fn connect_to(&self, path: &str) -> TestSocket {
+        TestSocket::connect(&self.url(path)).await
+    }
+
+    /// Reconnects with the token of an earlier socket
+    pub async fn resume(&self, token: &str) -> TestSocket {
+        self.connect_to(&format!("/ws?resume={token}")).await
+    }
+
+    /// A browser that already is subscribed to broadcasts.
+    /// The `Pods` answer used to make sure of that is consumed.
+    pub async fn client(&self) -> TestSocket {
+        let mut client = self.connect().await;
+        client.send(&JsonProtocol::ClientRequest(ClientRequest::ListAllPods)).await;
+        let pods = client.recv().await;
+        assert!(pods["ClientResponse"]["Pods"].is_array(), "expected Pods, got {pods}");
+        client
+    }
+
+    /// A registered pod and its id. Pods are subscribed like every other socket,
+    /// so besides `Registered` the broadcast of their own `NewPod` is consumed too.
+    pub async fn pod(&self, name: &str) -> (TestSocket, PodId) {
+        let mut pod = self.connect().await;
+        pod.send(&JsonProtocol::PodRequest(PodRequest::RegisterSelf { proposed_id: None, name: name.into() })).await;
+        let messages = pod.recv_unordered(2).await;
+        let id = messages.iter()
+            .find_map(|message| message["PodResponse"]["Registered"]["global_id"].as_u64())
+            .unwrap_or_else(|| panic!("expected Registered, got {messages:?}"));
+        assert!(messages.contains(&json!({"ClientResponse": {"NewPod": {"id": id, "name": name}}})), "expected NewPod, got {messages:?}");
+        (pod, id)
+    }
+}
+
+pub struct TestSocket {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    /// From the `Session` greeting
+    pub id: PodId,
+    pub token: String,
+    pub resumed: bool,
+}
+impl TestSocket {
+    /// Connects to any server, the `Session` greeting is consumed
+    pub async fn connect(url: &str) -> TestSocket {
+        let (ws, _) = connect_async(url).await.expect("unable to connect to test server");
+        let mut socket = TestSocket { ws, id: 0, token: String::new(), resumed: false };
+        let greeting = socket.recv().await;
+        let session = &greeting["ClientResponse"]["Session"];
+        let (Some(id), Some(token), Some(resumed)) = (session["client_id"].as_u64(), session["token"].as_str(), session["resumed"].as_bool()) else {
+            panic!("expected Session, got {greeting}");
+        };
+        socket.id = id;
+        socket.token = token.into();
+        socket.resumed = resumed;
+        socket
+    }
+
+    pub async fn send(&mut self, message: &JsonProtocol) {
+        self.send_text(&serde_json::to_string(message).unwrap()).await;
+    }
+
+    pub async fn send_text(&mut self, text: &str) {
+        self.ws.send(Message::Text(text.into())).await.expect("unable to send");
+    }
+
+    /// Next protocol message as JSON. Timestamps differ on every run,
+    /// so every `last_modified` is replaced by `"<timestamp>"`.
+    pub async fn recv(&mut self) -> Value {
+        self.recv_within(RECEIVE_TIMEOUT).await
+    }
+
+    /// Like [`TestSocket::recv`], for messages that take longer than [`RECEIVE_TIMEOUT`]
+    pub async fn recv_within(&mut self, timeout: Duration) -> Value {
+        loop {
+            let frame = tokio::time::timeout(timeout, self.ws.next()).await
+                .expect("no message in time")
+                .expect("connection closed")
+                .expect("connection failed");
+            if let Message::Text(text) = frame {
+                let mut message: Value = serde_json::from_str(&text).expect("server sent invalid json");
+                mask_timestamps(&mut message);
+                return message;
+            }
+        }
+    }
+
+    /// The next `count` messages sorted, for answers whose order is not defined
+    pub async fn recv_unordered(&mut self, count: usize) -> Vec<Value> {
+        let mut messages = vec![];
+        for _ in 0..count {
+            messages.push(self.recv().await);
+        }
+        messages.sort_by_key(|message| message.to_string());
+        messages
+    }
+
+    /// Fails if another protocol message arrives
+    pub async fn expect_silence(&mut self) {
+        loop {
+            match tokio::time::timeout(SILENCE, self.ws.next()).await {
+                Err(_) => return,
+                Ok(Some(Ok(Message::Text(text)))) => panic!("unexpected message {text}"),
+                Ok(Some(Ok(_))) => {}
+                Ok(_) => return,
+            }
+        }
+    }
+
+    /// Waits for the server to close the connection and returns the close code
+    pub async fn expect_close(&mut self) -> Option<u16> {
+        loop {
+            let frame = tokio::time::timeout(RECEIVE_TIMEOUT, self.ws.next()).await.expect("connection stayed open");
+            match frame {
+                Some(Ok(Message::Close(frame))) => return frame.map(|frame| frame.code.into()),
+                Some(Ok(Message::Text(text))) => panic!("unexpected message {text} instead of close"),
+                Some(Ok(_)) => {}
+                _ => return None,
+            }
+        }
+    }
+
+    pub async fn close(mut self) {
+        let _ = self.ws.close(None).await;
+    }
+}
+
+fn mask_timestamps(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if key == "last_modified" {
+                    *value = Value::from("<timestamp>");
+                } else {
+                    mask_timestamps(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(mask_timestamps),
+        _ => {}
+    }
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use infra::client::{ClientError, Event, GalleryClient, PathQuery, PodClient, PodOptions};
+use infra::protocols::{ClientRequest, ClientResponse, GalleryPath, JsonProtocol, PathMetadata, SortDirection, SortKey, SortOrder, StructureQuery};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{WebSocketStream, accept_async, tungstenite::Message};
+
+use common::{RECEIVE_TIMEOUT, TestServer};
+
+type Socket = WebSocketStream<TcpStream>;
+
+fn path(path: &str) -> GalleryPath {
+    GalleryPath::new(path).unwrap()
+}
+
+fn paths(names: &[&str]) -> Vec<GalleryPath> {
+    names.iter().map(|name| path(name)).collect()
+}
+
+const BY_SIZE: SortOrder = SortOrder { key: SortKey::Size, direction: SortDirection::Ascending };
+
+/// Stands in for a server, every socket the client opens is handed to the test to answer as it likes
+async fn scripted_server() -> (String, mpsc::UnboundedReceiver<Socket>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("unable to bind scripted server");
+    let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+    let (sockets, accepted) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(socket) = accept_async(stream).await else { continue };
+            if sockets.send(socket).is_err() {
+                break;
+            }
+        }
+    });
+    (url, accepted)
+}
+
+async fn next_socket(accepted: &mut mpsc::UnboundedReceiver<Socket>) -> Socket {
+    tokio::time::timeout(RECEIVE_TIMEOUT, accepted.recv()).await
+        .expect("client did not connect in time")
+        .expect("scripted server stopped")
+}
+
+/// The next request the client sent on `socket`
+async fn request(socket: &mut Socket) -> ClientRequest {
+    loop {
+        let frame = tokio::time::timeout(RECEIVE_TIMEOUT, socket.next()).await
+            .expect("no request in time")
+            .expect("connection closed")
+            .expect("connection failed");
+        if let Message::Text(text) = frame {
+            match serde_json::from_str(&text).expect("client sent invalid json") {
+                JsonProtocol::ClientRequest(request) => return request,
+                other => panic!("expected a ClientRequest, got {other:?}"),
+            }
+        }
+    }
+}
+
+async fn respond(socket: &mut Socket, response: ClientResponse) {
+    let text = serde_json::to_string(&JsonProtocol::ClientResponse(response)).unwrap();
+    socket.send(Message::Text(text.into())).await.expect("unable to send");
+}
+
+#[tokio::test]
+async fn pods_are_browsed_on_a_server() {
+    let server = TestServer::start().await;
+    let mut watcher = server.client().await;
+    let _pod = PodClient::connect(&server.url("/ws"), "holiday".into(), paths(&["b.png", "a.png", "c.png"]), PathMetadata::new(), PodOptions::default()).await.unwrap();
+    let id = watcher.recv().await["ClientResponse"]["NewPod"]["id"].as_u64().unwrap();
+    watcher.recv().await;
+    let client = GalleryClient::connect(&server.url("/ws")).await.unwrap();
+
+    let pods = client.list_pods().await.unwrap();
+    assert_eq!(pods.iter().map(|pod| (pod.id, pod.name.as_str())).collect::<Vec<_>>(), vec![(id, "holiday")]);
+    let page = client.pod_paths(id, &PathQuery { limit: Some(2), ..PathQuery::default() }, None).await.unwrap();
+    assert_eq!((page.paths, page.next.clone(), page.total), (paths(&["a.png", "b.png"]), Some(path("b.png")), 3));
+    assert_eq!(client.pod_paths(id, &PathQuery::default(), page.next).await.unwrap().paths, paths(&["c.png"]));
+    assert_eq!(client.pod_structure(id, SortOrder::default()).await.unwrap(), paths(&["a.png", "b.png", "c.png"]));
+    assert!(matches!(client.pod_structure(id + 100, SortOrder::default()).await, Err(ClientError::UnknownPod(unknown)) if unknown == id + 100));
+}
+
+#[tokio::test]
+async fn responses_are_matched_to_their_requests() {
+    let (url, mut accepted) = scripted_server().await;
+    let client = GalleryClient::connect(&url).await.unwrap();
+    let mut socket = next_socket(&mut accepted).await;
+
+    let structure = tokio::spawn({
+        let client = client.clone();
+        async move { client.pod_structure(7, BY_SIZE).await }
+    });
+    assert!(matches!(request(&mut socket).await, ClientRequest::ListPodStructure(StructureQuery::Sorted { id: 7, sort: BY_SIZE })));
+    let last_modified = chrono::Utc::now();
+    let listed = |id, sort, names: &[&str]| ClientResponse::PodStructure { id, sort, paths: paths(names), last_modified, version: 1, metadata: PathMetadata::new() };
+    // an update of the same pod, the listing of another pod and one in another order answer something else
+    respond(&mut socket, ClientResponse::PodUpdatePaths {
+        id: 7,
+        paths: paths(&["a.png", "b.png"]),
+        replace_images: false,
+        last_modified,
+        version: 1,
+        metadata: PathMetadata::new(),
+        curated_order: vec![],
+    }).await;
+    respond(&mut socket, listed(8, BY_SIZE, &["x.png"])).await;
+    respond(&mut socket, listed(7, SortOrder::default(), &["a.png", "b.png"])).await;
+    respond(&mut socket, listed(7, BY_SIZE, &["b.png", "a.png"])).await;
+    assert_eq!(structure.await.unwrap().unwrap(), paths(&["b.png", "a.png"]));
+}
+
+#[tokio::test]
+async fn requests_are_sent_again_once_reconnected() {
+    let (url, mut accepted) = scripted_server().await;
+    let client = GalleryClient::connect(&url).await.unwrap();
+    let mut events = client.events();
+    let mut socket = next_socket(&mut accepted).await;
+
+    let pods = tokio::spawn({
+        let client = client.clone();
+        async move { client.list_pods().await }
+    });
+    assert!(matches!(request(&mut socket).await, ClientRequest::ListAllPods));
+    // gone before answering
+    drop(socket);
+
+    let mut socket = next_socket(&mut accepted).await;
+    assert!(matches!(request(&mut socket).await, ClientRequest::ListAllPods));
+    respond(&mut socket, ClientResponse::Pods(vec![])).await;
+    assert!(pods.await.unwrap().unwrap().is_empty());
+    let event = tokio::time::timeout(RECEIVE_TIMEOUT, events.next()).await.expect("no event in time");
+    assert!(matches!(event, Some(Event::Reconnected)), "{event:?}");
+}
+
+#[tokio::test]
+async fn requests_give_up_after_the_timeout() {
+    let (url, mut accepted) = scripted_server().await;
+    let client = GalleryClient::connect(&url).await.unwrap().with_timeout(Duration::from_millis(200));
+    let mut socket = next_socket(&mut accepted).await;
+
+    let summaries = tokio::spawn(async move { client.list_pod_summaries().await });
+    assert!(matches!(request(&mut socket).await, ClientRequest::ListPodSummaries));
+    assert!(matches!(summaries.await.unwrap(), Err(ClientError::Timeout)));
+}
@@ -0,0 +1,136 @@
+use infra::protocols::{GalleryPath, Glob, InvalidGalleryPath, MAX_GLOB_LENGTH, MAX_NAME_LENGTH, MAX_PATH_LENGTH};
+
+fn rejection(path: &str) -> Option<InvalidGalleryPath> {
+    GalleryPath::new(path).err()
+}
+
+#[test]
+fn relative_paths_are_valid() {
+    for path in ["a.png", "holiday/2024/beach.jpg", "folder/a:b.jpg", "c/d:e.png", "..hidden/.dotfile", "ünïcode/😀.png"] {
+        assert_eq!(GalleryPath::new(path).map(String::from), Ok(path.to_string()));
+    }
+}
+
+#[test]
+fn traversal_is_rejected() {
+    for path in ["..", ".", "../a.png", "a/../b.png", "a/./b.png", "a/.."] {
+        assert_eq!(rejection(path), Some(InvalidGalleryPath::Traversal), "{path}");
+    }
+}
+
+#[test]
+fn absolute_paths_are_rejected() {
+    for path in ["/a.png", "/", "C:", "c:/a.png", "C:a.png", "z:\u{a0}"] {
+        assert_eq!(rejection(path), Some(InvalidGalleryPath::Absolute), "{path}");
+    }
+    assert_eq!(rejection("C:\\a.png"), Some(InvalidGalleryPath::Backslash));
+}
+
+#[test]
+fn separators_must_be_slashes_between_names() {
+    assert_eq!(rejection("a\\b.png"), Some(InvalidGalleryPath::Backslash));
+    for path in ["a//b.png", "a/", "a/b/"] {
+        assert_eq!(rejection(path), Some(InvalidGalleryPath::EmptyComponent), "{path}");
+    }
+}
+
+#[test]
+fn control_characters_are_rejected() {
+    for path in ["a\nb.png", "a\0.png", "\u{7f}.png", "a/\u{85}.png", "tab\t.png"] {
+        assert_eq!(rejection(path), Some(InvalidGalleryPath::ControlCharacter), "{path:?}");
+    }
+}
+
+#[test]
+fn lengths_are_limited() {
+    assert_eq!(rejection(""), Some(InvalidGalleryPath::Empty));
+
+    let name = "n".repeat(MAX_NAME_LENGTH);
+    assert!(GalleryPath::new(name.as_str()).is_ok());
+    assert_eq!(rejection(&format!("{name}n")), Some(InvalidGalleryPath::NameTooLong));
+
+    let folders = format!("{}/", "f".repeat(200)).repeat(5);
+    let longest = format!("{folders}{}", "n".repeat(MAX_PATH_LENGTH - folders.len()));
+    assert!(GalleryPath::new(longest.as_str()).is_ok());
+    assert_eq!(rejection(&format!("{longest}n")), Some(InvalidGalleryPath::TooLong));
+}
+
+#[test]
+fn deserializing_validates() {
+    assert!(serde_json::from_str::<GalleryPath>(r#""a/b.png""#).is_ok());
+    assert!(serde_json::from_str::<GalleryPath>(r#""../b.png""#).is_err());
+}
+
+#[test]
+fn paths_stay_below_the_root() {
+    let root = std::path::Path::new("/srv/gallery");
+    assert_eq!(GalleryPath::new("a/b.png").unwrap().to_path(root), Some(root.join("a").join("b.png")));
+}
+
+fn glob_matches(glob: &str, path: &str) -> bool {
+    Glob::new(glob).expect("glob too long").matches(&GalleryPath::new(path).unwrap())
+}
+
+#[test]
+fn single_stars_stay_within_a_name() {
+    assert!(glob_matches("*.jpg", "beach.jpg"));
+    assert!(glob_matches("2024/*.jpg", "2024/beach.jpg"));
+    assert!(glob_matches("*/*", "2024/beach.jpg"));
+    assert!(glob_matches("beach*", "beach"));
+    assert!(!glob_matches("*.jpg", "2024/beach.jpg"));
+    assert!(!glob_matches("2024*", "2024/beach.jpg"));
+}
+
+#[test]
+fn double_stars_span_folders() {
+    assert!(glob_matches("**.jpg", "2024/summer/beach.jpg"));
+    assert!(glob_matches("**/beach.jpg", "2024/summer/beach.jpg"));
+    assert!(glob_matches("2024/**", "2024/summer/beach.jpg"));
+    assert!(glob_matches("**", "beach.jpg"));
+    // the slash has to be there
+    assert!(!glob_matches("**/beach.jpg", "beach.jpg"));
+    assert!(!glob_matches("**.png", "2024/summer/beach.jpg"));
+}
+
+#[test]
+fn question_marks_are_one_character_of_a_name() {
+    assert!(glob_matches("img?.jpg", "img1.jpg"));
+    assert!(glob_matches("??.jpg", "äö.jpg"));
+    assert!(!glob_matches("img?.jpg", "img.jpg"));
+    assert!(!glob_matches("img?.jpg", "img12.jpg"));
+    assert!(!glob_matches("a?b.jpg", "a/b.jpg"));
+}
+
+#[test]
+fn globs_are_capped_in_length() {
+    let longest = "?".repeat(MAX_GLOB_LENGTH);
+    assert!(glob_matches(&longest, &"a".repeat(MAX_GLOB_LENGTH)));
+    assert!(!glob_matches(&longest, &"a".repeat(MAX_GLOB_LENGTH + 1)));
+    assert!(Glob::new(&format!("{longest}?")).is_none());
+    // `**` is one token
+    assert!(Glob::new(&"**".repeat(MAX_GLOB_LENGTH)).is_some());
+    assert!(!GalleryPath::new("a").unwrap().matches_glob(&"*".repeat(2 * MAX_GLOB_LENGTH + 1)));
+}
+
+/// Straight from the definition, exponential but obviously right
+fn naive_matches(glob: &[char], path: &[char]) -> bool {
+    match glob {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|skip| naive_matches(rest, &path[skip..])),
+        ['*', rest @ ..] => (0..=path.len()).take_while(|&skip| !path[..skip].contains(&'/')).any(|skip| naive_matches(rest, &path[skip..])),
+        ['?', rest @ ..] => path.first().is_some_and(|&c| c != '/') && naive_matches(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && naive_matches(rest, &path[1..]),
+    }
+}
+
+#[test]
+fn globs_match_like_their_definition() {
+    let globs = ["*", "**", "a*", "*a", "a*b", "a**b", "*/*", "**/a", "?", "a?", "*?*", "a/**/b", "**a**", "*a*/b", "***", "?/?"];
+    let paths = ["a", "b", "ab", "ba", "a/b", "b/a", "a/a/b", "aa/b", "ab/ab", "a/b/a"];
+    for glob in globs {
+        for path in paths {
+            let expected = naive_matches(&glob.chars().collect::<Vec<_>>(), &path.chars().collect::<Vec<_>>());
+            assert_eq!(glob_matches(glob, path), expected, "{glob} on {path}");
+        }
+    }
+}
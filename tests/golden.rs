@@ -0,0 +1,89 @@
+//! Pins down the wire format. After an intended protocol change regenerate the files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+
+use std::{collections::BTreeSet, path::Path};
+
+use infra::protocols::{JsonProtocol, example_messages, protocol_schema};
+use serde_json::Value;
+
+fn assert_golden(file: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(file);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|error| panic!("unable to read {}: {error}", path.display()));
+    assert!(
+        expected == actual,
+        "{} is outdated, the wire format changed:\n--- expected\n{expected}\n--- actual\n{actual}\nrun `UPDATE_GOLDEN=1 cargo test --test golden` if this is intended",
+        path.display(),
+    );
+}
+
+fn golden_lines() -> String {
+    example_messages().iter()
+        .map(|message| serde_json::to_string(message).unwrap() + "\n")
+        .collect()
+}
+
+#[test]
+fn messages_keep_their_wire_format() {
+    assert_golden("tests/golden/messages.jsonl", &golden_lines());
+}
+
+#[test]
+fn published_schema_is_up_to_date() {
+    let schema = serde_json::to_string_pretty(&protocol_schema()).unwrap() + "\n";
+    assert_golden("documentation/protocol.schema.json", &schema);
+}
+
+#[test]
+fn golden_messages_read_back_unchanged() {
+    let golden = std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/messages.jsonl")).unwrap();
+    for line in golden.lines() {
+        let message: JsonProtocol = serde_json::from_str(line).unwrap_or_else(|error| panic!("{line} no longer parses: {error}"));
+        assert_eq!(serde_json::to_string(&message).unwrap(), line);
+    }
+}
+
+/// Names of the variants of a tagged enum in the schema: unit variants are strings, the others objects with a single key
+fn variant_names(one_of: &Value) -> Vec<String> {
+    one_of.as_array().expect("expected oneOf").iter()
+        .flat_map(|variant| {
+            let names = variant["enum"].as_array().cloned()
+                .or_else(|| variant.get("const").map(|name| vec![name.clone()]))
+                .or_else(|| variant["required"].as_array().cloned())
+                .unwrap_or_else(|| panic!("unexpected variant {variant}"));
+            names.into_iter().map(|name| name.as_str().unwrap().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn examples_cover_every_message() {
+    let schema = serde_json::to_value(protocol_schema()).unwrap();
+    let mut expected = BTreeSet::new();
+    for outer in schema["oneOf"].as_array().unwrap() {
+        let name = outer["required"][0].as_str().unwrap();
+        let reference = outer["properties"][name]["$ref"].as_str().unwrap();
+        let inner = &schema["$defs"][reference.trim_start_matches("#/$defs/")];
+        for variant in variant_names(&inner["oneOf"]) {
+            expected.insert(format!("{name}::{variant}"));
+        }
+    }
+
+    let covered: BTreeSet<String> = example_messages().iter()
+        .map(|message| {
+            let message = serde_json::to_value(message).unwrap();
+            let (name, inner) = message.as_object().unwrap().iter().next().unwrap();
+            let variant = match inner {
+                Value::String(variant) => variant.clone(),
+                inner => inner.as_object().unwrap().keys().next().unwrap().clone(),
+            };
+            format!("{name}::{variant}")
+        })
+        .collect();
+
+    let missing: Vec<_> = expected.difference(&covered).collect();
+    assert!(missing.is_empty(), "example_messages() has no example of {missing:?}");
+}
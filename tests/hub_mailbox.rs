@@ -0,0 +1,49 @@
+mod common;
+
+use futures_util::future::join_all;
+use infra::actors::{HUB_MAILBOX_CAPACITY, Hub, ListConnections};
+use infra::protocols::{ClientRequest, JsonProtocol};
+
+use common::TestServer;
+
+/// Senders wait for room in the mailbox instead of losing their messages
+#[tokio::test(flavor = "current_thread")]
+async fn a_full_mailbox_holds_senders_back() {
+    let hub = Hub::default().start();
+    let asks: Vec<_> = (0..HUB_MAILBOX_CAPACITY * 10)
+        .map(|_| tokio::spawn({
+            let hub = hub.clone();
+            async move { hub.ask(ListConnections).await }
+        }))
+        .collect();
+
+    let mut deepest = 0;
+    while asks.iter().any(|ask| !ask.is_finished()) {
+        deepest = deepest.max(Hub::mailbox_depth(&hub));
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(deepest, HUB_MAILBOX_CAPACITY);
+    for ask in join_all(asks).await {
+        assert!(ask.unwrap().is_ok());
+    }
+    assert_eq!(Hub::mailbox_depth(&hub), 0);
+}
+
+#[tokio::test]
+async fn bursts_beyond_the_capacity_are_all_answered() {
+    let server = TestServer::start().await;
+    let mut clients = join_all((0..8).map(|_| server.client())).await;
+    let burst = HUB_MAILBOX_CAPACITY * 4;
+    for client in &mut clients {
+        for _ in 0..burst {
+            client.send(&JsonProtocol::ClientRequest(ClientRequest::ListAllPods)).await;
+        }
+    }
+    for client in &mut clients {
+        for _ in 0..burst {
+            let pods = client.recv().await;
+            assert!(pods["ClientResponse"]["Pods"].is_array(), "expected Pods, got {pods}");
+        }
+        client.expect_silence().await;
+    }
+}
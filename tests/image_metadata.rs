@@ -0,0 +1,97 @@
+use std::io::Cursor;
+
+use chrono::NaiveDate;
+use exif::{Field, In, Rational, Tag, Value, experimental::Writer};
+use infra::image_metadata::read_metadata;
+use infra::protocols::{GpsPosition, ImageMetadata};
+
+/// A JPEG header with EXIF data and the frame size, no pixels
+fn jpeg(fields: &[Field], width: u16, height: u16) -> Vec<u8> {
+    let mut writer = Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    let mut tiff = Cursor::new(vec![]);
+    writer.write(&mut tiff, false).unwrap();
+    let exif = [&b"Exif\0\0"[..], tiff.get_ref()].concat();
+
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    jpeg.extend(u16::try_from(exif.len() + 2).unwrap().to_be_bytes());
+    jpeg.extend(exif);
+    // baseline frame: precision, height, width, one component
+    jpeg.extend([0xFF, 0xC0, 0x00, 0x0B, 0x08]);
+    jpeg.extend(height.to_be_bytes());
+    jpeg.extend(width.to_be_bytes());
+    jpeg.extend([0x01, 0x01, 0x11, 0x00, 0xFF, 0xD9]);
+    jpeg
+}
+
+fn field(tag: Tag, value: Value) -> Field {
+    Field { tag, ifd_num: In::PRIMARY, value }
+}
+
+fn degrees(degrees: u32, minutes: u32, seconds: u32) -> Value {
+    Value::Rational(vec![Rational::from((degrees, 1)), Rational::from((minutes, 1)), Rational::from((seconds, 1))])
+}
+
+#[test]
+fn camera_images_tell_everything() {
+    let image = jpeg(&[
+        field(Tag::Model, Value::Ascii(vec![b"Pixel 8".to_vec()])),
+        field(Tag::Orientation, Value::Short(vec![6])),
+        field(Tag::DateTimeOriginal, Value::Ascii(vec![b"2024:05:17 18:30:05".to_vec()])),
+        field(Tag::GPSLatitudeRef, Value::Ascii(vec![b"N".to_vec()])),
+        field(Tag::GPSLatitude, degrees(52, 31, 12)),
+        field(Tag::GPSLongitudeRef, Value::Ascii(vec![b"W".to_vec()])),
+        field(Tag::GPSLongitude, degrees(13, 24, 36)),
+    ], 4032, 3024);
+
+    let mut metadata = read_metadata(&image, 123_456);
+    let GpsPosition { latitude, longitude } = metadata.gps.take().expect("no position");
+    assert!((latitude - 52.52).abs() < 1e-9 && (longitude + 13.41).abs() < 1e-9, "{latitude}, {longitude}");
+    assert_eq!(metadata, ImageMetadata {
+        width: Some(4032),
+        height: Some(3024),
+        size: Some(123_456),
+        mime: Some("image/jpeg".into()),
+        modified: None,
+        taken_at: NaiveDate::from_ymd_opt(2024, 5, 17).unwrap().and_hms_opt(18, 30, 5),
+        camera: Some("Pixel 8".into()),
+        orientation: Some(6),
+        gps: None,
+    });
+}
+
+#[test]
+fn missing_and_broken_exif_data_is_left_out() {
+    let image = jpeg(&[
+        field(Tag::Orientation, Value::Short(vec![42])),
+        field(Tag::DateTimeOriginal, Value::Ascii(vec![b"    :  :     :  :  ".to_vec()])),
+        field(Tag::GPSLatitude, degrees(52, 31, 12)),
+    ], 640, 480);
+    let metadata = read_metadata(&image, 1000);
+    assert_eq!((metadata.width, metadata.height), (Some(640), Some(480)));
+    assert_eq!((metadata.taken_at, metadata.orientation, metadata.gps), (None, None, None));
+
+    // only the size is known of something unreadable
+    assert_eq!(read_metadata(b"not an image", 12), ImageMetadata { size: Some(12), ..ImageMetadata::default() });
+}
+
+#[test]
+fn dimensions_come_without_exif_data() {
+    let png = [
+        &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13][..],
+        b"IHDR",
+        &800u32.to_be_bytes(),
+        &600u32.to_be_bytes(),
+        &[8, 6, 0, 0, 0],
+    ].concat();
+    let metadata = read_metadata(&png, 2048);
+    assert_eq!(metadata, ImageMetadata {
+        width: Some(800),
+        height: Some(600),
+        size: Some(2048),
+        mime: Some("image/png".into()),
+        ..ImageMetadata::default()
+    });
+}
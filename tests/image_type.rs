@@ -0,0 +1,95 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use infra::image_type::{ImageRejection, ImageType};
+
+/// The first bytes of a file of every format, padded to more than what sniffing looks at
+fn header(image_type: ImageType) -> Vec<u8> {
+    let mut bytes = match image_type {
+        ImageType::Jpeg => vec![0xFF, 0xD8, 0xFF, 0xE0],
+        ImageType::Png => vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A],
+        ImageType::Gif => b"GIF89a".to_vec(),
+        ImageType::Webp => b"RIFF\x24\0\0\0WEBPVP8 ".to_vec(),
+        ImageType::Bmp => b"BM\x36\0\0\0".to_vec(),
+        ImageType::Avif => b"\0\0\0\x1cftypavif".to_vec(),
+    };
+    bytes.resize(32, 0);
+    bytes
+}
+
+fn data_url(mime: &str, bytes: &[u8]) -> String {
+    format!("data:{mime};base64,{}", STANDARD.encode(bytes))
+}
+
+#[test]
+fn every_format_is_recognized_by_its_magic_number() {
+    for image_type in ImageType::ALL {
+        assert_eq!(ImageType::sniff(&header(image_type)), Some(image_type), "{image_type:?}");
+    }
+    assert_eq!(ImageType::sniff(b"GIF87a\0\0\0\0\0\0"), Some(ImageType::Gif));
+    assert_eq!(ImageType::sniff(b"\0\0\0\x1cftypavis"), Some(ImageType::Avif));
+}
+
+#[test]
+fn other_files_are_not_images() {
+    let svg = br#"<svg xmlns="http://www.w3.org/2000/svg"><script/></svg>"#;
+    for bytes in [&svg[..], b"", b"\xFF\xD8", b"GIF88a\0\0\0\0\0\0", b"RIFF\0\0\0\0WAVEfmt ", b"%PDF-1.7\n%\xE2\xE3\xCF\xD3"] {
+        assert_eq!(ImageType::sniff(bytes), None, "{bytes:?}");
+    }
+}
+
+#[test]
+fn data_urls_are_sniffed_ignoring_the_declared_type() {
+    for image_type in ImageType::ALL {
+        let blob = data_url("application/octet-stream", &header(image_type));
+        let (sniffed, payload) = ImageType::sniff_data_url(&blob).unwrap();
+        assert_eq!(sniffed, image_type);
+        assert_eq!(sniffed.data_url(payload), data_url(image_type.mime(), &header(image_type)));
+    }
+}
+
+#[test]
+fn short_padded_payloads_are_sniffed_from_what_they_hold() {
+    let jpeg = "data:image/jpeg;base64,/9j/4AAQSkZJRg==";
+    assert_eq!(ImageType::sniff_data_url(jpeg), Ok((ImageType::Jpeg, "/9j/4AAQSkZJRg==")));
+}
+
+#[test]
+fn data_urls_must_be_base64_throughout() {
+    let png = data_url("image/png", &header(ImageType::Png));
+    assert!(ImageType::sniff_data_url(&png).is_ok());
+    for blob in [
+        format!("{png}!"),
+        format!("{png}AAAA"),
+        png.replace("AAAA", "AA=A"),
+        format!("{}{}", png.trim_end_matches('='), "==AAAA"),
+        format!("{}not base64 at all", &png[..png.find(',').unwrap() + 17]),
+    ] {
+        assert_eq!(ImageType::sniff_data_url(&blob), Err(ImageRejection::NotADataUrl), "{blob}");
+    }
+
+    let mut big = header(ImageType::Png);
+    big.resize(100_000, 7);
+    let big = data_url("image/png", &big);
+    assert!(ImageType::sniff_data_url(&big).is_ok());
+    let corrupt = format!("{}%{}", &big[..50_000], &big[50_001..]);
+    assert_eq!(ImageType::sniff_data_url(&corrupt), Err(ImageRejection::NotADataUrl));
+}
+
+#[test]
+fn other_blobs_are_rejected() {
+    assert_eq!(ImageType::sniff_data_url("https://example.com/a.png"), Err(ImageRejection::NotADataUrl));
+    assert_eq!(ImageType::sniff_data_url("data:image/png,rawbytes"), Err(ImageRejection::NotADataUrl));
+    assert_eq!(ImageType::sniff_data_url("data:image/png;base64,iVBO"), Err(ImageRejection::UnknownFormat));
+    assert_eq!(ImageType::sniff_data_url("data:image/png;base64,AAAAAAAAAAAAAA=="), Err(ImageRejection::UnknownFormat));
+    let svg = data_url("image/svg+xml", br#"<svg xmlns="http://www.w3.org/2000/svg"/>"#);
+    assert_eq!(ImageType::sniff_data_url(&svg), Err(ImageRejection::UnknownFormat));
+}
+
+#[test]
+fn mime_types_parse() {
+    for image_type in ImageType::ALL {
+        assert_eq!(image_type.mime().parse(), Ok(image_type));
+    }
+    assert_eq!(" IMAGE/JPEG ".parse(), Ok(ImageType::Jpeg));
+    assert_eq!("image/svg+xml".parse::<ImageType>(), Err(ImageRejection::UnknownFormat));
+    assert!(ImageType::DEFAULT_ALLOWED.iter().all(|image_type| ImageType::ALL.contains(image_type)));
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::sync::OnceLock;
+
+use infra::actors::Hub;
+use infra::monitoring;
+use infra::protocols::{ClientRequest, JsonProtocol, PodRequest, SortOrder, example_messages};
+use infra::webserver::AppState;
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use common::{LIMITS, TestServer};
+
+/// The recorder is global, every test of this file shares it
+fn recorder() -> PrometheusHandle {
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+    HANDLE.get_or_init(|| monitoring::install().expect("unable to install the recorder")).clone()
+}
+
+/// Number of requests of that kind recorded so far
+fn requests_counted(metrics: &str, kind: &str, request: &str) -> u64 {
+    let series = format!("{}_count{{kind=\"{kind}\",request=\"{request}\"}} ", monitoring::REQUEST_SECONDS);
+    metrics.lines()
+        .find_map(|line| line.strip_prefix(&series))
+        .map_or(0, |count| count.parse().unwrap())
+}
+
+#[test]
+fn variants_are_named_like_on_the_wire() {
+    for message in example_messages() {
+        let json = serde_json::to_value(&message).unwrap();
+        let inner = &json[message.kind()];
+        let name = inner.as_str().or_else(|| inner.as_object().and_then(|inner| inner.keys().next()).map(String::as_str));
+        assert_eq!(Some(message.variant()), name, "{json}");
+    }
+}
+
+#[tokio::test]
+async fn requests_are_timed_by_what_they_ask_for() {
+    let metrics = recorder();
+    let server = TestServer::serve(AppState::new(Hub::default().start(), 0, LIMITS).with_metrics(metrics.clone())).await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let before = metrics.render();
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodPaths {
+        id, prefix: None, glob: None, after: None, limit: None, sort: SortOrder::default(),
+    })).await;
+    client.recv().await;
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdateTitle { name: "summer".into() })).await;
+    client.recv().await;
+
+    let after = metrics.render();
+    for (kind, request) in [("ClientRequest", "ListPodPaths"), ("PodRequest", "UpdateTitle")] {
+        assert_eq!(requests_counted(&after, kind, request), requests_counted(&before, kind, request) + 1, "{kind} {request}\n{after}");
+    }
+}
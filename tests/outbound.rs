@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use infra::actors::outbound::{OutboundQueue, OutboundReceiver, Overflow, TOO_SLOW};
+use infra::actors::websocket::{CloseCode, Message};
+use infra::protocols::{ClientResponse, GalleryPath, PathMetadata};
+use serde_json::{Value, json};
+
+fn path(path: &str) -> GalleryPath {
+    GalleryPath::new(path).unwrap()
+}
+
+fn paths(id: u64, names: &[&str], replace_images: bool) -> ClientResponse {
+    ClientResponse::PodUpdatePaths {
+        id,
+        paths: names.iter().map(|name| path(name)).collect(),
+        replace_images,
+        last_modified: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        version: 1,
+        metadata: PathMetadata::new(),
+        curated_order: vec![],
+    }
+}
+
+fn added(id: u64, names: &[&str], version: u64) -> ClientResponse {
+    ClientResponse::PathsAdded {
+        id,
+        paths: names.iter().map(|name| path(name)).collect(),
+        last_modified: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        version,
+        metadata: PathMetadata::new(),
+    }
+}
+
+fn image(gallery_id: u64, name: &str, size: usize) -> ClientResponse {
+    ClientResponse::DeliverImage { gallery_id, path: path(name), mime: "image/png".into(), blob: "x".repeat(size) }
+}
+
+/// Everything queued, text frames as JSON
+async fn drain(receiver: &mut OutboundReceiver) -> Vec<Value> {
+    let mut frames = vec![];
+    while let Ok(Some(frame)) = tokio::time::timeout(Duration::from_millis(20), receiver.recv()).await {
+        frames.push(match frame {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            Message::Close(reason) => json!({"Close": reason.map(|reason| u16::from(reason.code))}),
+            other => json!(format!("{other:?}")),
+        });
+    }
+    frames
+}
+
+#[tokio::test]
+async fn stale_updates_are_replaced_by_the_latest() {
+    let (queue, mut receiver) = OutboundQueue::new(1024 * 1024, Duration::from_secs(10));
+    queue.push_response(&ClientResponse::NewPod { id: 1, name: "a".into() }).unwrap();
+    queue.push_response(&paths(1, &["a.png"], true)).unwrap();
+    queue.push_response(&ClientResponse::PodUpdateName { id: 1, name: "b".into() }).unwrap();
+    queue.push_response(&paths(1, &["a.png", "b.png"], false)).unwrap();
+    queue.push_response(&paths(2, &["c.png"], false)).unwrap();
+    queue.push_response(&ClientResponse::PodUpdateName { id: 1, name: "c".into() }).unwrap();
+
+    let frames = drain(&mut receiver).await;
+    assert_eq!(frames.len(), 4, "{frames:?}");
+    assert_eq!(frames[0], json!({"ClientResponse": {"NewPod": {"id": 1, "name": "a"}}}));
+    // in place of the first update, still asking to drop the images
+    assert_eq!(frames[1]["ClientResponse"]["PodUpdatePaths"]["paths"], json!(["a.png", "b.png"]));
+    assert_eq!(frames[1]["ClientResponse"]["PodUpdatePaths"]["replace_images"], json!(true));
+    assert_eq!(frames[2], json!({"ClientResponse": {"PodUpdateName": {"id": 1, "name": "c"}}}));
+    assert_eq!(frames[3]["ClientResponse"]["PodUpdatePaths"]["id"], json!(2));
+}
+
+#[tokio::test]
+async fn full_listings_replace_the_deltas_before_them() {
+    let (queue, mut receiver) = OutboundQueue::new(1024 * 1024, Duration::from_secs(10));
+    queue.push_response(&added(1, &["a.png"], 2)).unwrap();
+    queue.push_response(&added(2, &["b.png"], 2)).unwrap();
+    queue.push_response(&added(1, &["c.png"], 3)).unwrap();
+    queue.push_response(&paths(1, &["a.png", "c.png"], false)).unwrap();
+    queue.push_response(&added(1, &["d.png"], 5)).unwrap();
+
+    let frames = drain(&mut receiver).await;
+    assert_eq!(frames.len(), 3, "{frames:?}");
+    assert_eq!(frames[0]["ClientResponse"]["PathsAdded"]["id"], json!(2));
+    assert_eq!(frames[1]["ClientResponse"]["PodUpdatePaths"]["paths"], json!(["a.png", "c.png"]));
+    assert_eq!(frames[2]["ClientResponse"]["PathsAdded"]["paths"], json!(["d.png"]));
+}
+
+#[tokio::test]
+async fn replies_are_neither_replaced_nor_replace_updates() {
+    let (queue, mut receiver) = OutboundQueue::new(1024 * 1024, Duration::from_secs(10));
+    queue.push_response(&added(1, &["b.png"], 2)).unwrap();
+    queue.push_reply(&paths(1, &["b.png", "a.png"], false)).unwrap();
+    let frames = drain(&mut receiver).await;
+    assert_eq!(frames.len(), 2, "{frames:?}");
+    assert_eq!(frames[0]["ClientResponse"]["PathsAdded"]["paths"], json!(["b.png"]));
+
+    queue.push_reply(&paths(1, &["b.png", "a.png"], false)).unwrap();
+    queue.push_response(&paths(1, &["a.png", "b.png", "c.png"], false)).unwrap();
+    let frames = drain(&mut receiver).await;
+    assert_eq!(frames.len(), 2, "{frames:?}");
+    // in the order asked for
+    assert_eq!(frames[0]["ClientResponse"]["PodUpdatePaths"]["paths"], json!(["b.png", "a.png"]));
+    assert_eq!(frames[1]["ClientResponse"]["PodUpdatePaths"]["paths"], json!(["a.png", "b.png", "c.png"]));
+}
+
+#[tokio::test]
+async fn nothing_about_a_gone_pod_is_sent_but_pod_gone() {
+    let (queue, mut receiver) = OutboundQueue::new(1024 * 1024, Duration::from_secs(10));
+    queue.push_response(&ClientResponse::NewPod { id: 1, name: "a".into() }).unwrap();
+    queue.push_response(&paths(1, &["a.png"], false)).unwrap();
+    queue.push_response(&added(1, &["b.png"], 2)).unwrap();
+    queue.push_response(&image(1, "a.png", 10)).unwrap();
+    queue.push_response(&image(2, "b.png", 10)).unwrap();
+    queue.push_response(&ClientResponse::PodGone(1)).unwrap();
+
+    let frames = drain(&mut receiver).await;
+    assert_eq!(frames.len(), 3, "{frames:?}");
+    assert_eq!(frames[0], json!({"ClientResponse": {"NewPod": {"id": 1, "name": "a"}}}));
+    assert_eq!(frames[1]["ClientResponse"]["DeliverImage"]["gallery_id"], json!(2));
+    assert_eq!(frames[2], json!({"ClientResponse": {"PodGone": 1}}));
+}
+
+#[tokio::test]
+async fn images_give_way_when_the_queue_is_full() {
+    let (queue, mut receiver) = OutboundQueue::new(1200, Duration::from_secs(10));
+    // about 490 bytes each, dropping the oldest is enough
+    for name in ["a.png", "b.png", "c.png"] {
+        queue.push_response(&image(1, name, 400)).unwrap();
+    }
+    queue.push_response(&ClientResponse::PodGone(7)).unwrap();
+
+    let frames = drain(&mut receiver).await;
+    assert_eq!(frames[0], json!({"ClientResponse": {"ImageRejected": {"gallery_id": 1, "path": "a.png", "reason": TOO_SLOW}}}));
+    assert_eq!(frames[1]["ClientResponse"]["DeliverImage"]["path"], json!("b.png"));
+    assert_eq!(frames[2]["ClientResponse"]["DeliverImage"]["path"], json!("c.png"));
+    assert_eq!(frames[3], json!({"ClientResponse": {"PodGone": 7}}));
+}
+
+#[tokio::test]
+async fn sockets_staying_over_the_limit_overflow() {
+    let (queue, mut receiver) = OutboundQueue::new(200, Duration::from_millis(50));
+    // 88 bytes each
+    let pods = |id| ClientResponse::NewPod { id, name: "x".repeat(40) };
+    queue.push_response(&pods(1)).unwrap();
+    queue.push_response(&pods(2)).unwrap();
+    // over the limit, but within the grace period
+    assert_eq!(queue.push_response(&pods(3)), Ok(()));
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(matches!(queue.push_response(&pods(4)), Err(Overflow { .. })));
+
+    queue.close_now((CloseCode::Policy, "too slow").into());
+    assert_eq!(drain(&mut receiver).await, vec![json!({"Close": 1008})]);
+
+    // twice the limit does not wait for the grace period
+    let (queue, _receiver) = OutboundQueue::new(200, Duration::from_secs(60));
+    assert_eq!(queue.push_response(&pods(1)), Ok(()));
+    assert!(matches!(queue.push_response(&ClientResponse::NewPod { id: 2, name: "x".repeat(300) }), Err(Overflow { .. })));
+}
+
+#[tokio::test]
+async fn the_receiver_ends_with_the_last_sender() {
+    let (queue, mut receiver) = OutboundQueue::new(100, Duration::from_secs(10));
+    let other = queue.clone();
+    queue.push(Message::Ping(String::new())).unwrap();
+    drop(queue);
+    drop(other);
+    assert!(matches!(receiver.recv().await, Some(Message::Ping(_))));
+    assert!(receiver.recv().await.is_none());
+}
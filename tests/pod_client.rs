@@ -0,0 +1,45 @@
+mod common;
+
+use infra::client::{PodClient, PodOptions};
+use infra::protocols::{GalleryPath, GpsPosition, ImageMetadata, PathMetadata};
+use serde_json::json;
+
+use common::TestServer;
+
+fn path(path: &str) -> GalleryPath {
+    GalleryPath::new(path).unwrap()
+}
+
+fn located(name: &str) -> PathMetadata {
+    PathMetadata::from([(path(name), ImageMetadata {
+        width: Some(640),
+        gps: Some(GpsPosition { latitude: 52.52, longitude: 13.405 }),
+        ..ImageMetadata::default()
+    })])
+}
+
+#[tokio::test]
+async fn positions_are_left_out_unless_shared() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+
+    let pod = PodClient::connect(&server.url("/ws"), "holiday".into(), vec![path("a.jpg")], located("a.jpg"), PodOptions::default()).await.unwrap();
+    client.recv().await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodUpdatePaths"]["metadata"], json!({"a.jpg": {"width": 640}}));
+    pod.add_paths(vec![path("b.jpg")], located("b.jpg")).unwrap();
+    assert_eq!(client.recv().await["ClientResponse"]["PathsAdded"]["metadata"], json!({"b.jpg": {"width": 640}}));
+}
+
+#[tokio::test]
+async fn positions_are_announced_when_shared() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+
+    let options = PodOptions { share_location: true, ..PodOptions::default() };
+    let _pod = PodClient::connect(&server.url("/ws"), "holiday".into(), vec![path("a.jpg")], located("a.jpg"), options).await.unwrap();
+    client.recv().await;
+    assert_eq!(
+        client.recv().await["ClientResponse"]["PodUpdatePaths"]["metadata"],
+        json!({"a.jpg": {"width": 640, "gps": {"latitude": 52.52, "longitude": 13.405}}}),
+    );
+}
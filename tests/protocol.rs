@@ -0,0 +1,542 @@
+mod common;
+
+use std::time::Duration;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use infra::actors::{Hub, Shutdown, websocket::CloseCode};
+use infra::protocols::{ClientRequest, ClientRequestAsync, GalleryPath, ImageMetadata, JsonProtocol, PathMetadata, PodRequest, SortDirection, SortKey, SortOrder, StructureQuery};
+use infra::config::{ConnectionLimits, Heartbeat};
+use infra::webserver::AppState;
+use serde_json::json;
+
+use common::TestServer;
+
+fn path(path: &str) -> GalleryPath {
+    GalleryPath::new(path).unwrap()
+}
+
+fn png_data_url() -> String {
+    let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13, b'I', b'H', b'D', b'R'];
+    format!("data:image/png;base64,{}", STANDARD.encode(png))
+}
+
+#[tokio::test]
+async fn registration_is_announced_to_every_client() {
+    let server = TestServer::start().await;
+    let mut clients = vec![server.client().await, server.client().await, server.client().await];
+
+    let mut pod = server.connect().await;
+    pod.send(&JsonProtocol::PodRequest(PodRequest::RegisterSelf { proposed_id: None, name: "holiday".into() })).await;
+    let messages = pod.recv_unordered(2).await;
+    let id = messages[1]["PodResponse"]["Registered"]["global_id"].clone();
+    assert!(id.is_u64(), "expected Registered, got {messages:?}");
+    // the pod is a subscribed client as well
+    assert_eq!(messages, vec![
+        json!({"ClientResponse": {"NewPod": {"id": id, "name": "holiday"}}}),
+        json!({"PodResponse": {"Registered": {"global_id": id}}}),
+    ]);
+
+    for client in &mut clients {
+        assert_eq!(client.recv().await, json!({"ClientResponse": {"NewPod": {"id": id, "name": "holiday"}}}));
+        client.expect_silence().await;
+    }
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::RegisterSelf { proposed_id: None, name: "again".into() })).await;
+    assert_eq!(pod.recv().await, json!({"PodResponse": {"AlreadyRegistered": {"global_id": id}}}));
+    clients[0].expect_silence().await;
+}
+
+#[tokio::test]
+async fn updates_reach_clients_and_listings() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"NewPod": {"id": id, "name": "holiday"}}}));
+
+    // the pod sees its own updates like every other subscriber
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdateTitle { name: "summer".into() })).await;
+    for socket in [&mut client, &mut pod] {
+        assert_eq!(socket.recv().await, json!({"ClientResponse": {"PodUpdateName": {"id": id, "name": "summer"}}}));
+    }
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths {
+        paths: vec![path("b.png"), path("a/c.png"), path("b.png")],
+        replace_images: true,
+        curated: false,
+        metadata: PathMetadata::new(),
+    })).await;
+    for socket in [&mut client, &mut pod] {
+        assert_eq!(socket.recv().await, json!({"ClientResponse": {"PodUpdatePaths": {
+            "id": id,
+            "paths": ["a/c.png", "b.png"],
+            "replace_images": true,
+            "last_modified": "<timestamp>",
+            "version": 1,
+        }}}));
+    }
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListAllPods)).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"Pods": [
+        {"id": id, "name": "summer", "paths": ["a/c.png", "b.png"], "last_modified": "<timestamp>", "version": 1},
+    ]}}));
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(id.into()))).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodUpdatePaths": {
+        "id": id,
+        "paths": ["a/c.png", "b.png"],
+        "replace_images": false,
+        "last_modified": "<timestamp>",
+        "version": 1,
+    }}}));
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodStructure((id + 100).into()))).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"UnknownPod": id + 100}}));
+    pod.expect_silence().await;
+}
+
+#[tokio::test]
+async fn path_changes_are_sent_as_versioned_deltas() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let paths = |names: &[&str]| names.iter().map(|name| path(name)).collect::<Vec<_>>();
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: paths(&["a.png", "b.png", "c.png", "d.png"]), replace_images: false, curated: false, metadata: PathMetadata::new() })).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodUpdatePaths"]["version"], json!(1));
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: paths(&["a.png", "c.png", "d.png", "e.png"]), replace_images: false, curated: false, metadata: PathMetadata::new() })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PathsRemoved": {"id": id, "paths": ["b.png"], "last_modified": "<timestamp>", "version": 2}}}));
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PathsAdded": {"id": id, "paths": ["e.png"], "last_modified": "<timestamp>", "version": 3}}}));
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsAdded { paths: paths(&["f.png", "a.png"]), metadata: PathMetadata::new() })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PathsAdded": {"id": id, "paths": ["f.png"], "last_modified": "<timestamp>", "version": 4}}}));
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsRemoved { paths: paths(&["c.png", "x.png"]) })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PathsRemoved": {"id": id, "paths": ["c.png"], "last_modified": "<timestamp>", "version": 5}}}));
+
+    // nothing changed, nothing to tell
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsAdded { paths: paths(&["a.png"]), metadata: PathMetadata::new() })).await;
+    client.expect_silence().await;
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(id.into()))).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodUpdatePaths": {
+        "id": id,
+        "paths": ["a.png", "d.png", "e.png", "f.png"],
+        "replace_images": false,
+        "last_modified": "<timestamp>",
+        "version": 5,
+    }}}));
+}
+
+#[tokio::test]
+async fn paths_are_listed_in_filtered_pages() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let names = ["2023/x.png", "2024/a.png", "2024/b.jpg", "2024/c.png", "2024/deep/d.png", "z.png"];
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: names.iter().map(|name| path(name)).collect(), replace_images: false, curated: false, metadata: PathMetadata::new() })).await;
+    client.recv().await;
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodSummaries)).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodSummaries": [
+        {"id": id, "name": "holiday", "path_count": 6, "last_modified": "<timestamp>", "version": 1},
+    ]}}));
+
+    let list = |prefix: Option<&str>, glob: Option<&str>, after: Option<&str>, limit| JsonProtocol::ClientRequest(ClientRequest::ListPodPaths {
+        id,
+        prefix: prefix.map(Into::into),
+        glob: glob.map(Into::into),
+        after: after.map(path),
+        limit,
+        sort: SortOrder::default(),
+    });
+    client.send(&list(Some("2024/"), None, None, Some(2))).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodPaths": {
+        "id": id, "paths": ["2024/a.png", "2024/b.jpg"], "next": "2024/b.jpg", "total": 4, "version": 1,
+    }}}));
+    client.send(&list(Some("2024/"), None, Some("2024/b.jpg"), Some(2))).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodPaths": {
+        "id": id, "paths": ["2024/c.png", "2024/deep/d.png"], "next": null, "total": 4, "version": 1,
+    }}}));
+
+    // `*` stays within a folder, `**` does not
+    client.send(&list(None, Some("2024/*.png"), None, None)).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["paths"], json!(["2024/a.png", "2024/c.png"]));
+    client.send(&list(None, Some("**/?.png"), None, None)).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["paths"], json!(["2023/x.png", "2024/a.png", "2024/c.png", "2024/deep/d.png"]));
+    client.send(&list(None, Some("**.png"), Some("2024/c.png"), Some(0))).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodPaths": {
+        "id": id, "paths": ["2024/deep/d.png"], "next": "2024/deep/d.png", "total": 5, "version": 1,
+    }}}));
+
+    client.send(&list(None, None, None, None)).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["total"], json!(6));
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodPaths { id: id + 100, prefix: None, glob: None, after: None, limit: None, sort: SortOrder::default() })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"UnknownPod": id + 100}}));
+}
+
+#[tokio::test]
+async fn folders_are_listed_one_level_at_a_time() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let names = ["2024/a-b/x.png", "2024/a.png", "2024/a/y.png", "2024/a/z/w.png", "cover.png"];
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: names.iter().map(|name| path(name)).collect(), replace_images: false, curated: false, metadata: PathMetadata::new() })).await;
+    client.recv().await;
+
+    let list = |folder: &str| JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: id, folder: folder.into(), sort: SortOrder::default(), after: None, limit: None });
+    client.send(&list("")).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"FolderListing": {
+        "gallery_id": id, "folder": "", "folders": ["2024"], "images": ["cover.png"], "sort": {"key": "Curated", "direction": "Ascending"}, "next": null, "total": 1, "version": 1,
+    }}}));
+    client.send(&list("2024/")).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"FolderListing": {
+        "gallery_id": id, "folder": "2024", "folders": ["a", "a-b"], "images": ["2024/a.png"], "sort": {"key": "Curated", "direction": "Ascending"}, "next": null, "total": 1, "version": 1,
+    }}}));
+    client.send(&list("2024/a")).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"FolderListing": {
+        "gallery_id": id, "folder": "2024/a", "folders": ["z"], "images": ["2024/a/y.png"], "sort": {"key": "Curated", "direction": "Ascending"}, "next": null, "total": 1, "version": 1,
+    }}}));
+    client.send(&list("2025")).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"FolderListing": {
+        "gallery_id": id, "folder": "2025", "folders": [], "images": [], "sort": {"key": "Curated", "direction": "Ascending"}, "next": null, "total": 0, "version": 1,
+    }}}));
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: id + 100, folder: "".into(), sort: SortOrder::default(), after: None, limit: None })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"UnknownPod": id + 100}}));
+}
+
+#[tokio::test]
+async fn folder_images_are_paged() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let names = ["2024/a.png", "2024/b.png", "2024/c.png", "2024/d.png", "2024/e.png", "2024/sub/x.png"];
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: names.iter().map(|name| path(name)).collect(), replace_images: false, curated: false, metadata: PathMetadata::new() })).await;
+    client.recv().await;
+
+    let list = |after: Option<&str>| JsonProtocol::ClientRequest(ClientRequest::ListFolder {
+        gallery_id: id, folder: "2024".into(), sort: SortOrder::default(), after: after.map(path), limit: Some(2),
+    });
+    client.send(&list(None)).await;
+    let first = &client.recv().await["ClientResponse"]["FolderListing"];
+    assert_eq!((&first["folders"], &first["images"], &first["next"], &first["total"]), (&json!(["sub"]), &json!(["2024/a.png", "2024/b.png"]), &json!("2024/b.png"), &json!(5)));
+    client.send(&list(Some("2024/b.png"))).await;
+    let second = &client.recv().await["ClientResponse"]["FolderListing"];
+    assert_eq!((&second["folders"], &second["images"], &second["next"]), (&json!([]), &json!(["2024/c.png", "2024/d.png"]), &json!("2024/d.png")));
+    client.send(&list(Some("2024/d.png"))).await;
+    let last = &client.recv().await["ClientResponse"]["FolderListing"];
+    assert_eq!((&last["images"], &last["next"], &last["total"]), (&json!(["2024/e.png"]), &json!(null), &json!(5)));
+}
+
+#[tokio::test]
+async fn image_metadata_travels_with_the_paths() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let paths = vec![path("a.png"), path("b.png"), path("c.png")];
+    let sized = |width| PathMetadata::from([
+        (path("a.png"), ImageMetadata { width: Some(width), height: Some(20), ..ImageMetadata::default() }),
+        (path("gone.png"), ImageMetadata::default()),
+    ]);
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: paths.clone(), replace_images: false, curated: false, metadata: sized(10) })).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodUpdatePaths"]["metadata"], json!({"a.png": {"width": 10, "height": 20}}));
+
+    // a changed image is a different one
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths, replace_images: false, curated: false, metadata: sized(30) })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PathsRemoved": {"id": id, "paths": ["a.png"], "last_modified": "<timestamp>", "version": 2}}}));
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PathsAdded": {
+        "id": id, "paths": ["a.png"], "last_modified": "<timestamp>", "version": 3, "metadata": {"a.png": {"width": 30, "height": 20}},
+    }}}));
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: id, folder: "".into(), sort: SortOrder::default(), after: None, limit: None })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"FolderListing": {
+        "gallery_id": id, "folder": "", "folders": [], "images": ["a.png", "b.png", "c.png"], "sort": {"key": "Curated", "direction": "Ascending"}, "next": null, "total": 3, "version": 3,
+        "metadata": {"a.png": {"width": 30, "height": 20}},
+    }}}));
+}
+
+#[tokio::test]
+async fn paths_are_listed_in_the_order_asked_for() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let taken = |day| chrono::NaiveDate::from_ymd_opt(2024, 5, day).unwrap().and_hms_opt(12, 0, 0);
+    let metadata = PathMetadata::from([
+        (path("img10.png"), ImageMetadata { size: Some(300), taken_at: taken(2), ..ImageMetadata::default() }),
+        (path("img2.png"), ImageMetadata { size: Some(100), ..ImageMetadata::default() }),
+        (path("IMG1.png"), ImageMetadata { size: Some(200), taken_at: taken(1), ..ImageMetadata::default() }),
+    ]);
+    let paths = metadata.keys().cloned().collect();
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths, replace_images: false, curated: false, metadata })).await;
+    client.recv().await;
+
+    let sort = |key, direction| SortOrder { key, direction };
+    let list = |sort, after: Option<&str>, limit| JsonProtocol::ClientRequest(ClientRequest::ListPodPaths {
+        id,
+        prefix: None,
+        glob: None,
+        after: after.map(path),
+        limit,
+        sort,
+    });
+    for (order, expected) in [
+        (sort(SortKey::Name, SortDirection::Ascending), ["IMG1.png", "img10.png", "img2.png"]),
+        (sort(SortKey::Natural, SortDirection::Ascending), ["IMG1.png", "img2.png", "img10.png"]),
+        (sort(SortKey::Size, SortDirection::Descending), ["img10.png", "IMG1.png", "img2.png"]),
+        // without a capture time last in either direction
+        (sort(SortKey::Taken, SortDirection::Ascending), ["IMG1.png", "img10.png", "img2.png"]),
+        (sort(SortKey::Taken, SortDirection::Descending), ["img10.png", "IMG1.png", "img2.png"]),
+    ] {
+        client.send(&list(order, None, None)).await;
+        assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["paths"], json!(expected), "{order:?}");
+    }
+
+    let natural = sort(SortKey::Natural, SortDirection::Ascending);
+    client.send(&list(natural, None, Some(2))).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["next"], json!("img2.png"));
+    client.send(&list(natural, Some("img2.png"), Some(2))).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["paths"], json!(["img10.png"]));
+
+    let by_size = StructureQuery::Sorted { id, sort: sort(SortKey::Size, SortDirection::Ascending) };
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(by_size))).await;
+    let structure = &client.recv().await["ClientResponse"]["PodStructure"];
+    assert_eq!(structure["paths"], json!(["img2.png", "IMG1.png", "img10.png"]));
+    assert_eq!(structure["sort"], json!({"key": "Size", "direction": "Ascending"}));
+
+    let descending = sort(SortKey::Natural, SortDirection::Descending);
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: id, folder: "".into(), sort: descending, after: None, limit: None })).await;
+    let listing = &client.recv().await["ClientResponse"]["FolderListing"];
+    assert_eq!(listing["images"], json!(["img10.png", "img2.png", "IMG1.png"]));
+    assert_eq!(listing["sort"], json!({"key": "Natural", "direction": "Descending"}));
+}
+
+#[tokio::test]
+async fn curated_order_is_kept_until_another_is_asked_for() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    let paths = |names: &[&str]| names.iter().map(|name| path(name)).collect::<Vec<_>>();
+    let structure = JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(id.into()));
+    let curated = JsonProtocol::ClientRequest(ClientRequest::ListPodStructure(StructureQuery::Sorted { id, sort: SortOrder::default() }));
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: paths(&["c.png", "a.png", "b.png"]), replace_images: false, curated: true, metadata: PathMetadata::new() })).await;
+    let update = client.recv().await["ClientResponse"]["PodUpdatePaths"].clone();
+    assert_eq!((&update["version"], &update["paths"]), (&json!(1), &json!(["a.png", "b.png", "c.png"])));
+    assert_eq!(update["curated_order"], json!(["c.png", "a.png", "b.png"]));
+    // asked for, the whole listing looks like the update
+    client.send(&structure).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodUpdatePaths"], update);
+    client.send(&curated).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodStructure"]["paths"], json!(["c.png", "a.png", "b.png"]));
+    let by_name = SortOrder { key: SortKey::Name, direction: SortDirection::Ascending };
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListFolder { gallery_id: id, folder: "".into(), sort: by_name, after: None, limit: None })).await;
+    assert_eq!(client.recv().await["ClientResponse"]["FolderListing"]["images"], json!(["a.png", "b.png", "c.png"]));
+
+    // new paths go to the end, known ones keep their place
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsAdded { paths: paths(&["d.png", "a.png"]), metadata: PathMetadata::new() })).await;
+    client.recv().await;
+    let page = |after: Option<&str>| JsonProtocol::ClientRequest(ClientRequest::ListPodPaths {
+        id,
+        prefix: None,
+        glob: None,
+        after: after.map(path),
+        limit: Some(2),
+        sort: SortOrder::default(),
+    });
+    client.send(&page(None)).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["paths"], json!(["c.png", "a.png"]));
+    client.send(&page(Some("a.png"))).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodPaths"]["paths"], json!(["b.png", "d.png"]));
+
+    // the same paths in another order outdate every listing
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: paths(&["d.png", "c.png", "b.png", "a.png"]), replace_images: false, curated: true, metadata: PathMetadata::new() })).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodUpdatePaths"]["version"], json!(3));
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsRemoved { paths: paths(&["c.png"]) })).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodUpdatePaths"]["curated_order"], json!(["d.png", "b.png", "a.png"]));
+    client.send(&curated).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodStructure"]["paths"], json!(["d.png", "b.png", "a.png"]));
+
+    // declaring no order falls back to names
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: paths(&["d.png", "b.png", "a.png"]), replace_images: false, curated: false, metadata: PathMetadata::new() })).await;
+    client.recv().await;
+    client.send(&curated).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodStructure"]["paths"], json!(["a.png", "b.png", "d.png"]));
+    client.send(&structure).await;
+    assert_eq!(client.recv().await["ClientResponse"]["PodUpdatePaths"].get("curated_order"), None);
+}
+
+#[tokio::test]
+async fn images_travel_from_pod_to_the_requesting_client() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let mut bystander = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+    bystander.recv().await;
+
+    client.send(&JsonProtocol::ClientRequestAsync(ClientRequestAsync::RequestImage {
+        gallery_id: id,
+        path: path("a.png"),
+        client_id: 0,
+    })).await;
+    let request = pod.recv().await;
+    let client_id = request["PodResponse"]["RequestImage"]["client_id"].as_u64().expect("expected RequestImage");
+    assert_eq!(request, json!({"PodResponse": {"RequestImage": {"client_id": client_id, "path": "a.png"}}}));
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::DeliverImage {
+        client_id,
+        path: path("a.png"),
+        blob: png_data_url(),
+    })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"DeliverImage": {
+        "gallery_id": id,
+        "path": "a.png",
+        "mime": "image/png",
+        "blob": png_data_url(),
+    }}}));
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::DeliverImage {
+        client_id,
+        path: path("evil.png"),
+        blob: format!("data:image/png;base64,{}", STANDARD.encode("<script>alert(1)</script>")),
+    })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"ImageRejected": {
+        "gallery_id": id,
+        "path": "evil.png",
+        "reason": "not a known image format",
+    }}}));
+    assert_eq!(pod.recv().await, json!({"PodResponse": {"ImageRejected": {
+        "client_id": client_id,
+        "path": "evil.png",
+        "reason": "not a known image format",
+    }}}));
+
+    bystander.expect_silence().await;
+}
+
+#[tokio::test]
+async fn disconnected_pods_are_gone() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (pod, id) = server.pod("holiday").await;
+    client.recv().await;
+
+    pod.close().await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodGone": id}}));
+
+    client.send(&JsonProtocol::ClientRequestAsync(ClientRequestAsync::RequestImage {
+        gallery_id: id,
+        path: path("a.png"),
+        client_id: 0,
+    })).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"UnknownPod": id}}));
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListAllPods)).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"Pods": []}}));
+}
+
+#[tokio::test]
+async fn disconnected_clients_do_not_disturb_pods() {
+    let server = TestServer::start().await;
+    let client = server.client().await;
+    let (mut pod, _) = server.pod("holiday").await;
+    let mut other = server.client().await;
+
+    client.close().await;
+    pod.expect_silence().await;
+    other.expect_silence().await;
+}
+
+#[tokio::test]
+async fn invalid_paths_are_left_out_and_reported() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, id) = server.pod("holiday").await;
+    client.recv().await;
+
+    pod.send_text(&json!({"PodRequest": {"UpdatePaths": {
+        "paths": ["a.png", "../etc/passwd", "b.png"],
+        "replace_images": false,
+        "metadata": {"b.png": {"width": 10}, "/abs.png": {"width": 20}},
+    }}}).to_string()).await;
+    let update = json!({"ClientResponse": {"PodUpdatePaths": {
+        "id": id,
+        "paths": ["a.png", "b.png"],
+        "replace_images": false,
+        "last_modified": "<timestamp>",
+        "version": 1,
+        "metadata": {"b.png": {"width": 10}},
+    }}});
+    assert_eq!(client.recv().await, update);
+    assert_eq!(pod.recv_unordered(2).await, vec![update, json!({"PodResponse": {"PathsRejected": {"paths": [
+        {"path": "../etc/passwd", "reason": "path must not contain `.` or `..`"},
+        {"path": "/abs.png", "reason": "path must be relative"},
+    ]}}})]);
+}
+
+#[tokio::test]
+async fn oversized_messages_close_the_socket() {
+    let limits = ConnectionLimits { max_message_size: 100, ..common::LIMITS };
+    let server = TestServer::start_with(Hub::default(), limits).await;
+    let mut client = server.client().await;
+
+    client.send_text(&"x".repeat(150)).await;
+    assert_eq!(client.expect_close().await, Some(u16::from(CloseCode::Size)));
+}
+
+#[tokio::test]
+async fn messages_above_every_limit_close_the_socket() {
+    let limits = ConnectionLimits { max_message_size: 100, max_pod_message_size: 200, ..common::LIMITS };
+    let server = TestServer::start_with(Hub::default(), limits).await;
+    let mut client = server.client().await;
+
+    client.send_text(&"x".repeat(300)).await;
+    assert_eq!(client.expect_close().await, Some(u16::from(CloseCode::Size)));
+}
+
+#[tokio::test]
+async fn pods_may_send_bigger_messages() {
+    let server = TestServer::start().await;
+    let (mut pod, _) = server.pod("holiday").await;
+
+    pod.send_text(&"x".repeat(common::LIMITS.max_message_size * 2)).await;
+    pod.send(&JsonProtocol::ClientRequest(ClientRequest::Heartbeat)).await;
+    assert_eq!(pod.recv().await, json!({"ClientResponse": "Heartbeat"}));
+}
+
+#[tokio::test]
+async fn shutdown_closes_every_socket() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let (mut pod, _) = server.pod("holiday").await;
+    client.recv().await;
+
+    server.hub.ask(Shutdown(CloseCode::Restart)).await.unwrap();
+    for socket in [&mut client, &mut pod] {
+        assert_eq!(socket.expect_close().await, Some(u16::from(CloseCode::Restart)));
+    }
+    server.hub.wait_for_shutdown().await;
+    assert!(tokio_tungstenite::connect_async(server.url("/ws")).await.is_err(), "upgrades are refused while shutting down");
+}
+
+#[tokio::test]
+async fn silent_pods_are_dropped() {
+    let heartbeat = Heartbeat { interval: Duration::from_millis(50), timeout: Duration::from_millis(200) };
+    let server = TestServer::serve(AppState::new(Hub::default().start(), 0, common::LIMITS).with_heartbeat(heartbeat)).await;
+    let mut client = server.client().await;
+    // only sockets being read answer pings
+    let (_silent, id) = server.pod("holiday").await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"NewPod": {"id": id, "name": "holiday"}}}));
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodGone": id}}));
+
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::Heartbeat)).await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": "Heartbeat"}));
+}
@@ -0,0 +1,47 @@
+use std::{net::IpAddr, time::Duration};
+
+use infra::config::RateLimit;
+use infra::webserver::rate_limit::{IpRateLimiter, TokenBucket};
+
+const LIMIT: RateLimit = RateLimit { per_second: 20.0, burst: 3.0 };
+
+#[test]
+fn buckets_allow_a_burst() {
+    let mut bucket = TokenBucket::new(LIMIT);
+    assert!((0..3).all(|_| bucket.try_take()));
+    assert!(!bucket.try_take());
+}
+
+#[test]
+fn buckets_refill_over_time() {
+    let mut bucket = TokenBucket::new(LIMIT);
+    while bucket.try_take() {}
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(bucket.try_take());
+}
+
+#[test]
+fn buckets_refill_up_to_the_burst() {
+    let mut bucket = TokenBucket::new(LIMIT);
+    std::thread::sleep(Duration::from_millis(300));
+    assert!((0..3).all(|_| bucket.try_take()));
+    assert!(!bucket.try_take());
+}
+
+#[test]
+fn addresses_have_their_own_bucket() {
+    let limiter = IpRateLimiter::new(LIMIT);
+    let (first, second): (IpAddr, IpAddr) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+    assert!((0..3).all(|_| limiter.try_take(first)));
+    assert!(!limiter.try_take(first));
+    assert!(limiter.try_take(second));
+}
+
+#[test]
+fn pruning_keeps_drained_buckets() {
+    let limiter = IpRateLimiter::new(LIMIT);
+    let ip: IpAddr = "10.0.0.1".parse().unwrap();
+    while limiter.try_take(ip) {}
+    limiter.prune();
+    assert!(!limiter.try_take(ip), "a pruned address would start with a full bucket");
+}
@@ -0,0 +1,61 @@
+use std::{os::unix::{ffi::OsStrExt, fs::symlink}, path::PathBuf};
+
+use infra::client::scan_images;
+use infra::protocols::GalleryPath;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+
+/// An empty directory of its own for every test
+fn scratch(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gallery-scan-{}-{test}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn scanned(root: &std::path::Path) -> Vec<GalleryPath> {
+    let (mut paths, metadata) = scan_images(root).unwrap();
+    paths.sort();
+    assert_eq!(metadata.len(), paths.len());
+    paths
+}
+
+fn paths(paths: &[&str]) -> Vec<GalleryPath> {
+    paths.iter().map(|path| GalleryPath::new(*path).unwrap()).collect()
+}
+
+#[test]
+fn finds_images_in_subdirectories() {
+    let root = scratch("nested");
+    std::fs::create_dir_all(root.join("2024/summer")).unwrap();
+    std::fs::write(root.join("a.png"), PNG).unwrap();
+    std::fs::write(root.join("2024/summer/b.png"), PNG).unwrap();
+    std::fs::write(root.join("notes.txt"), "no image").unwrap();
+
+    assert_eq!(scanned(&root), paths(&["2024/summer/b.png", "a.png"]));
+}
+
+#[test]
+fn symbolic_links_are_not_followed() {
+    let root = scratch("symlinks");
+    let outside = scratch("symlinks-outside");
+    std::fs::write(outside.join("secret.png"), PNG).unwrap();
+    std::fs::write(root.join("a.png"), PNG).unwrap();
+    symlink(&outside, root.join("escape")).unwrap();
+    symlink(outside.join("secret.png"), root.join("linked.png")).unwrap();
+    // would never end if followed
+    symlink(&root, root.join("loop")).unwrap();
+
+    assert_eq!(scanned(&root), paths(&["a.png"]));
+}
+
+#[test]
+fn names_that_are_not_utf8_are_skipped() {
+    let root = scratch("utf8");
+    std::fs::write(root.join("a.png"), PNG).unwrap();
+    std::fs::write(root.join(std::ffi::OsStr::from_bytes(b"b\xff.png")), PNG).unwrap();
+    std::fs::create_dir(root.join(std::ffi::OsStr::from_bytes(b"c\xff"))).unwrap();
+    std::fs::write(root.join(std::ffi::OsStr::from_bytes(b"c\xff")).join("d.png"), PNG).unwrap();
+
+    assert_eq!(scanned(&root), paths(&["a.png"]));
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use infra::protocols::{ClientRequest, GalleryPath, ImageMetadata, JsonProtocol, PathMetadata, PodId, PodRequest, SearchFilters, SearchHit, SortDirection, SortKey, SortOrder};
+use serde_json::{Value, json};
+
+use common::{TestServer, TestSocket};
+
+fn path(path: &str) -> GalleryPath {
+    GalleryPath::new(path).unwrap()
+}
+
+fn day(day: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 5, day).unwrap().and_hms_opt(12, 0, 0).unwrap()
+}
+
+/// A pod sharing `paths`, the images taken on the given day of May 2024
+async fn pod(server: &TestServer, name: &str, paths: &[(&str, Option<u32>)]) -> (TestSocket, PodId) {
+    let (mut pod, id) = server.pod(name).await;
+    let metadata: PathMetadata = paths.iter()
+        .filter_map(|&(name, taken)| Some((path(name), ImageMetadata { taken_at: Some(day(taken?)), ..ImageMetadata::default() })))
+        .collect();
+    let paths = paths.iter().map(|&(name, _)| path(name)).collect();
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths, replace_images: false, curated: false, metadata })).await;
+    pod.recv().await;
+    (pod, id)
+}
+
+async fn search(client: &mut TestSocket, query: &str, filters: SearchFilters, after: Option<SearchHit>, limit: Option<u32>) -> Value {
+    sorted_search(client, SortOrder::default(), after, limit, query, filters).await
+}
+
+async fn sorted_search(client: &mut TestSocket, sort: SortOrder, after: Option<SearchHit>, limit: Option<u32>, query: &str, filters: SearchFilters) -> Value {
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::Search { query: query.into(), filters, sort, after, limit })).await;
+    loop {
+        let message = client.recv().await;
+        if let Some(results) = message["ClientResponse"].get("SearchResults") {
+            return results.clone();
+        }
+    }
+}
+
+#[tokio::test]
+async fn file_names_are_found_in_every_gallery() {
+    let server = TestServer::start().await;
+    let (_holiday, holiday) = pod(&server, "Holiday", &[("beach/Sunset.png", Some(3)), ("beach/sand.png", Some(4)), ("city.png", None)]).await;
+    let (_family, family) = pod(&server, "Family", &[("sunset at home.png", Some(20)), ("cake.png", Some(21))]).await;
+    let mut client = server.client().await;
+
+    let results = search(&mut client, "SUNSET", SearchFilters::default(), None, None).await;
+    assert_eq!(results, json!({
+        "query": "SUNSET",
+        "hits": [{"gallery_id": holiday, "path": "beach/Sunset.png"}, {"gallery_id": family, "path": "sunset at home.png"}],
+        "next": null,
+        "total": 2,
+    }));
+
+    let folder = SearchFilters { folder: Some("beach/".into()), ..SearchFilters::default() };
+    assert_eq!(search(&mut client, "", folder, None, None).await["hits"], json!([
+        {"gallery_id": holiday, "path": "beach/Sunset.png"}, {"gallery_id": holiday, "path": "beach/sand.png"},
+    ]));
+    let gallery = SearchFilters { gallery: Some("fam".into()), ..SearchFilters::default() };
+    assert_eq!(search(&mut client, "", gallery, None, None).await["total"], json!(2));
+
+    // images without a capture time never match a date range
+    let dates = SearchFilters { taken_after: Some(day(4)), taken_before: Some(day(21)), ..SearchFilters::default() };
+    assert_eq!(search(&mut client, "", dates, None, None).await["hits"], json!([
+        {"gallery_id": holiday, "path": "beach/sand.png"}, {"gallery_id": family, "path": "sunset at home.png"},
+    ]));
+}
+
+#[tokio::test]
+async fn hits_are_paged() {
+    let server = TestServer::start().await;
+    let (_pod, id) = pod(&server, "Holiday", &[("a.png", None), ("b.png", None), ("c.png", None)]).await;
+    let mut client = server.client().await;
+
+    let first = search(&mut client, ".png", SearchFilters::default(), None, Some(2)).await;
+    assert_eq!(first["hits"], json!([{"gallery_id": id, "path": "a.png"}, {"gallery_id": id, "path": "b.png"}]));
+    assert_eq!(first["total"], json!(3));
+    let after: SearchHit = serde_json::from_value(first["next"].clone()).unwrap();
+    let second = search(&mut client, ".png", SearchFilters::default(), Some(after), Some(2)).await;
+    assert_eq!(second["hits"], json!([{"gallery_id": id, "path": "c.png"}]));
+    assert_eq!(second["next"], json!(null));
+}
+
+#[tokio::test]
+async fn gone_pods_are_no_longer_found() {
+    let server = TestServer::start().await;
+    let (pod, _) = pod(&server, "Holiday", &[("a.png", None)]).await;
+    let mut client = server.client().await;
+    assert_eq!(search(&mut client, "a", SearchFilters::default(), None, None).await["total"], json!(1));
+
+    pod.close().await;
+    let mut total = json!(1);
+    for _ in 0..50 {
+        total = search(&mut client, "a", SearchFilters::default(), None, None).await["total"].clone();
+        if total == json!(0) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(total, json!(0));
+}
+
+#[tokio::test]
+async fn hits_are_sorted_across_galleries() {
+    let server = TestServer::start().await;
+    let (_holiday, holiday) = pod(&server, "Holiday", &[("a.png", Some(3)), ("b.png", Some(1))]).await;
+    let (_family, family) = pod(&server, "Family", &[("c.png", Some(2)), ("d.png", None)]).await;
+    let mut client = server.client().await;
+
+    // images without a capture time last
+    let sort = SortOrder { key: SortKey::Taken, direction: SortDirection::Descending };
+    let first = sorted_search(&mut client, sort, None, Some(3), "", SearchFilters::default()).await;
+    assert_eq!(first["hits"], json!([
+        {"gallery_id": holiday, "path": "a.png"}, {"gallery_id": family, "path": "c.png"}, {"gallery_id": holiday, "path": "b.png"},
+    ]));
+    let after = serde_json::from_value(first["next"].clone()).unwrap();
+    let second = sorted_search(&mut client, sort, after, Some(3), "", SearchFilters::default()).await;
+    assert_eq!(second["hits"], json!([{"gallery_id": family, "path": "d.png"}]));
+}
+
+#[tokio::test]
+async fn changes_of_pods_are_followed() {
+    let server = TestServer::start().await;
+    let (mut pod, id) = pod(&server, "Holiday", &[("a.png", None), ("b.png", None), ("c.png", None)]).await;
+    let mut client = server.client().await;
+    let hits = |names: &[&str]| json!(names.iter().map(|name| json!({"gallery_id": id, "path": name})).collect::<Vec<_>>());
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsAdded { paths: vec![path("d.png")], metadata: PathMetadata::new() })).await;
+    assert!(client.recv().await["ClientResponse"]["PathsAdded"].is_object());
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsRemoved { paths: vec![path("a.png")] })).await;
+    assert!(client.recv().await["ClientResponse"]["PathsRemoved"].is_object());
+    assert_eq!(search(&mut client, "", SearchFilters::default(), None, None).await["hits"], hits(&["b.png", "c.png", "d.png"]));
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdateTitle { name: "Summer".into() })).await;
+    client.recv().await;
+    let gallery = SearchFilters { gallery: Some("summer".into()), ..SearchFilters::default() };
+    assert_eq!(search(&mut client, "", gallery, None, None).await["total"], json!(3));
+
+    // added paths follow the curated ones
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdatePaths { paths: vec![path("c.png"), path("b.png")], replace_images: false, curated: true, metadata: PathMetadata::new() })).await;
+    client.recv().await;
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsAdded { paths: vec![path("a.png")], metadata: PathMetadata::new() })).await;
+    client.recv().await;
+    assert_eq!(search(&mut client, "", SearchFilters::default(), None, None).await["hits"], hits(&["c.png", "b.png", "a.png"]));
+}
+
+#[tokio::test]
+async fn added_paths_are_merged_in_order() {
+    let server = TestServer::start().await;
+    let (mut pod, id) = pod(&server, "Holiday", &[("b.png", None), ("d.png", None)]).await;
+    let mut client = server.client().await;
+
+    let added = vec![path("e.png"), path("a.png"), path("d.png"), path("c.png"), path("a.png")];
+    pod.send(&JsonProtocol::PodRequest(PodRequest::PathsAdded { paths: added, metadata: PathMetadata::new() })).await;
+    client.recv().await;
+    let hits: Vec<_> = ["a.png", "b.png", "c.png", "d.png", "e.png"].iter().map(|name| json!({"gallery_id": id, "path": name})).collect();
+    assert_eq!(search(&mut client, "", SearchFilters::default(), None, None).await["hits"], json!(hits));
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use std::time::Duration;
+
+use infra::actors::{KickClient, ListConnections, ResumeSession, websocket::CloseCode};
+use infra::protocols::{JsonProtocol, PodId, PodRequest};
+use serde_json::json;
+
+use common::{TestServer, TestSocket};
+
+/// Closes the socket and waits until the Hub noticed, so nothing is sent to it anymore
+async fn disconnect(server: &TestServer, socket: TestSocket) {
+    let id = socket.id;
+    socket.close().await;
+    tokio::time::timeout(common::RECEIVE_TIMEOUT, async {
+        while server.hub.ask(ListConnections).await.unwrap().clients.iter().any(|client| client.id == id) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }).await.expect("client stayed subscribed");
+}
+
+#[tokio::test]
+async fn resumed_sessions_get_what_they_missed() {
+    let server = TestServer::start().await;
+    let client = server.client().await;
+    assert!(!client.resumed);
+    let (id, token) = (client.id, client.token.clone());
+    disconnect(&server, client).await;
+
+    let (mut pod, pod_id) = server.pod("holiday").await;
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdateTitle { name: "summer".into() })).await;
+    pod.recv().await;
+
+    let mut client = server.resume(&token).await;
+    assert!(client.resumed);
+    assert_eq!(client.id, id);
+    assert_ne!(client.token, token, "tokens are used up");
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"NewPod": {"id": pod_id, "name": "holiday"}}}));
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"PodUpdateName": {"id": pod_id, "name": "summer"}}}));
+    client.expect_silence().await;
+}
+
+#[tokio::test]
+async fn unknown_tokens_start_a_new_session() {
+    let server = TestServer::start().await;
+    let client = server.client().await;
+    let (id, token) = (client.id, client.token.clone());
+    disconnect(&server, client).await;
+    server.resume(&token).await;
+
+    for token in [token, format!("{id:x}.c0ffee"), "garbage".into()] {
+        let client = server.resume(&token).await;
+        assert!(!client.resumed, "{token} was accepted");
+        assert_ne!(client.id, id);
+    }
+}
+
+#[tokio::test]
+async fn resuming_an_open_session_closes_the_old_socket() {
+    let server = TestServer::start().await;
+    let mut old = server.client().await;
+
+    let mut client = server.resume(&old.token).await;
+    assert!(client.resumed);
+    assert_eq!(client.id, old.id);
+    assert_eq!(old.expect_close().await, Some(u16::from(CloseCode::Policy)));
+
+    // the old socket leaving must not unsubscribe the new one
+    let (_pod, pod_id) = server.pod("holiday").await;
+    assert_eq!(client.recv().await, json!({"ClientResponse": {"NewPod": {"id": pod_id, "name": "holiday"}}}));
+    let clients: Vec<PodId> = server.hub.ask(ListConnections).await.unwrap().clients.iter().map(|client| client.id).collect();
+    assert!(clients.contains(&client.id), "{clients:?}");
+}
+
+#[tokio::test]
+async fn kicked_sessions_cannot_be_resumed() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+
+    assert!(server.hub.ask(KickClient(client.id)).await.unwrap());
+    assert_eq!(client.expect_close().await, Some(u16::from(CloseCode::Policy)));
+    assert!(!server.resume(&client.token).await.resumed);
+}
+
+#[tokio::test]
+async fn checking_a_token_leaves_the_session_alone() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+
+    // as if the upgrade failed after the check
+    let resumable = server.hub.ask(ResumeSession { token: client.token.clone() }).await.unwrap();
+    assert_eq!(resumable.map(|session| session.id), Some(client.id));
+    client.expect_silence().await;
+
+    let resumed = server.resume(&client.token).await;
+    assert!(resumed.resumed, "the token was used up by the check");
+    assert_eq!(client.expect_close().await, Some(u16::from(CloseCode::Policy)));
+}
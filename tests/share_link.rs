@@ -0,0 +1,157 @@
+mod common;
+
+use chrono::{Duration, DurationRound, Utc};
+use infra::actors::websocket::CloseCode;
+use infra::protocols::{ClientRequest, JsonProtocol, PodId, PodRequest};
+use infra::share_link::{ShareClaims, ShareLinkError, ShareLinkSigner};
+use serde_json::json;
+use tokio_tungstenite::{connect_async, tungstenite};
+
+use common::{TestServer, TestSocket};
+
+fn claims(expires_in: Duration) -> ShareClaims {
+    // tokens carry whole seconds
+    let expires_at = (Utc::now() + expires_in).duration_trunc(Duration::seconds(1)).unwrap();
+    ShareClaims { pod_id: 0x2a, link_id: 7, expires_at }
+}
+
+#[test]
+fn signed_tokens_verify() {
+    let signer = ShareLinkSigner::new("secret");
+    let claims = claims(Duration::hours(1));
+    assert_eq!(signer.verify(&signer.sign(&claims)), Ok(claims));
+}
+
+#[test]
+fn tampered_tokens_are_refused() {
+    let signer = ShareLinkSigner::new("secret");
+    let token = signer.sign(&claims(Duration::hours(1)));
+    let (payload, signature) = token.rsplit_once('.').unwrap();
+
+    let other_pod = format!("2b{}.{signature}", &payload[2..]);
+    assert_eq!(signer.verify(&other_pod), Err(ShareLinkError::BadSignature));
+    let mut flipped = signature.to_string();
+    flipped.replace_range(..1, if signature.starts_with('0') { "1" } else { "0" });
+    assert_eq!(signer.verify(&format!("{payload}.{flipped}")), Err(ShareLinkError::BadSignature));
+    assert_eq!(ShareLinkSigner::new("other secret").verify(&token), Err(ShareLinkError::BadSignature));
+}
+
+#[test]
+fn malformed_tokens_are_refused() {
+    let signer = ShareLinkSigner::new("secret");
+    for token in ["", "garbage", "2a.7.1.zz", "2a.7.nothex"] {
+        assert_eq!(signer.verify(token), Err(ShareLinkError::Malformed), "{token}");
+    }
+    // correctly signed, but not made of the expected fields
+    let signature = hex::encode(hmac_of("secret", "2a.7"));
+    assert_eq!(signer.verify(&format!("2a.7.{signature}")), Err(ShareLinkError::Malformed));
+}
+
+#[test]
+fn expired_tokens_are_refused() {
+    let signer = ShareLinkSigner::new("secret");
+    let token = signer.sign(&claims(-Duration::seconds(1)));
+    assert_eq!(signer.verify(&token), Err(ShareLinkError::Expired));
+}
+
+fn hmac_of(secret: &str, payload: &str) -> Vec<u8> {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(payload.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Asks the pod for a share link and returns its token
+async fn share(pod: &mut TestSocket, expires_in: Duration, max_views: Option<u32>) -> String {
+    pod.send(&JsonProtocol::PodRequest(PodRequest::CreateShareLink { expires_at: Utc::now() + expires_in, max_views })).await;
+    let response = pod.recv().await;
+    response["PodResponse"]["ShareLink"]["token"].as_str()
+        .unwrap_or_else(|| panic!("expected ShareLink, got {response}"))
+        .into()
+}
+
+/// HTTP status the server refused the share socket with
+async fn refused(server: &TestServer, token: &str) -> u16 {
+    match connect_async(server.url(&format!("/share/{token}"))).await {
+        Err(tungstenite::Error::Http(response)) => response.status().as_u16(),
+        Err(error) => panic!("expected a refusal, got {error}"),
+        Ok(_) => panic!("share link was accepted"),
+    }
+}
+
+async fn list_all_pods(client: &mut TestSocket) -> Vec<PodId> {
+    client.send(&JsonProtocol::ClientRequest(ClientRequest::ListAllPods)).await;
+    let pods = client.recv().await;
+    pods["ClientResponse"]["Pods"].as_array()
+        .unwrap_or_else(|| panic!("expected Pods, got {pods}"))
+        .iter()
+        .map(|pod| pod["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn share_sessions_only_see_their_gallery() {
+    let server = TestServer::start().await;
+    let (mut pod, pod_id) = server.pod("holiday").await;
+    let (_other, _) = server.pod("work").await;
+    pod.recv().await;
+    let token = share(&mut pod, Duration::hours(1), None).await;
+
+    let mut shared = server.connect_to(&format!("/share/{token}")).await;
+    assert_eq!(list_all_pods(&mut shared).await, vec![pod_id]);
+
+    server.pod("private").await;
+    pod.send(&JsonProtocol::PodRequest(PodRequest::UpdateTitle { name: "summer".into() })).await;
+    assert_eq!(shared.recv().await, json!({"ClientResponse": {"PodUpdateName": {"id": pod_id, "name": "summer"}}}));
+    shared.expect_silence().await;
+}
+
+#[tokio::test]
+async fn share_links_run_out_of_views() {
+    let server = TestServer::start().await;
+    let (mut pod, _) = server.pod("holiday").await;
+    let token = share(&mut pod, Duration::hours(1), Some(1)).await;
+
+    server.connect_to(&format!("/share/{token}")).await;
+    assert_eq!(refused(&server, &token).await, 410);
+    assert_eq!(refused(&server, "garbage").await, 403);
+}
+
+#[tokio::test]
+async fn revoking_closes_the_share_sessions() {
+    let server = TestServer::start().await;
+    let (mut pod, _) = server.pod("holiday").await;
+    let token = share(&mut pod, Duration::hours(1), None).await;
+    let mut shared = server.connect_to(&format!("/share/{token}")).await;
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::RevokeShareLink { token: token.clone() })).await;
+    assert_eq!(pod.recv().await, json!({"PodResponse": {"ShareLinkRevoked": {"token": token}}}));
+    assert_eq!(shared.expect_close().await, Some(CloseCode::Policy.into()));
+    assert_eq!(refused(&server, &token).await, 410);
+}
+
+#[tokio::test]
+async fn expiring_closes_the_share_sessions() {
+    let server = TestServer::start().await;
+    let (mut pod, _) = server.pod("holiday").await;
+    let token = share(&mut pod, Duration::seconds(2), None).await;
+    let mut shared = server.connect_to(&format!("/share/{token}")).await;
+
+    assert_eq!(shared.expect_close().await, Some(CloseCode::Policy.into()));
+    assert_eq!(refused(&server, &token).await, 410);
+}
+
+#[tokio::test]
+async fn share_sessions_are_not_resumed_once_their_link_is_gone() {
+    let server = TestServer::start().await;
+    let (mut pod, _) = server.pod("holiday").await;
+    let token = share(&mut pod, Duration::hours(1), None).await;
+    let shared = server.connect_to(&format!("/share/{token}")).await;
+    let session_token = shared.token.clone();
+    shared.close().await;
+
+    pod.send(&JsonProtocol::PodRequest(PodRequest::RevokeShareLink { token: token.clone() })).await;
+    pod.recv().await;
+    assert_eq!(refused(&server, &format!("{token}?resume={session_token}")).await, 410);
+    assert!(!server.resume(&session_token).await.resumed);
+}